@@ -17,6 +17,28 @@ mod m20260102_132957_add_is_active_is_staff_to_users;
 mod m20260102_133327_drop_carts;
 mod m20260102_133835_make_reviews_by_product;
 mod m20260103_144317_cart_item_unique_constraint;
+mod m20260110_091502_review_moderation;
+mod m20260110_143020_review_votes;
+mod m20260111_101244_brand_pages;
+mod m20260112_084530_wishlist_price_alerts;
+mod m20260113_160210_wishlist_lists;
+mod m20260114_093015_guest_carts;
+mod m20260115_101530_cart_item_prices;
+mod m20260116_140245_cart_reminders;
+mod m20260117_083012_idempotency_keys;
+mod m20260118_091430_order_invoices;
+mod m20260119_102140_user_sessions;
+mod m20260120_083517_login_throttling;
+mod m20260121_094210_two_factor_auth;
+mod m20260122_101530_user_identities;
+mod m20260123_084210_hash_user_tokens;
+mod m20260124_101205_keep_orders_of_deleted_users;
+mod m20260125_093040_audit_events;
+mod m20260126_084515_user_roles;
+mod m20260127_090412_expire_unlock_tokens;
+mod m20260128_091530_two_factor_enrollment_links;
+mod m20260129_093010_account_deletion_links;
+mod m20260130_084020_idempotency_key_leases;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -38,6 +60,28 @@ impl MigratorTrait for Migrator {
             Box::new(m20260102_133327_drop_carts::Migration),
             Box::new(m20260102_133835_make_reviews_by_product::Migration),
             Box::new(m20260103_144317_cart_item_unique_constraint::Migration),
+            Box::new(m20260110_091502_review_moderation::Migration),
+            Box::new(m20260110_143020_review_votes::Migration),
+            Box::new(m20260111_101244_brand_pages::Migration),
+            Box::new(m20260112_084530_wishlist_price_alerts::Migration),
+            Box::new(m20260113_160210_wishlist_lists::Migration),
+            Box::new(m20260114_093015_guest_carts::Migration),
+            Box::new(m20260115_101530_cart_item_prices::Migration),
+            Box::new(m20260116_140245_cart_reminders::Migration),
+            Box::new(m20260117_083012_idempotency_keys::Migration),
+            Box::new(m20260118_091430_order_invoices::Migration),
+            Box::new(m20260119_102140_user_sessions::Migration),
+            Box::new(m20260120_083517_login_throttling::Migration),
+            Box::new(m20260121_094210_two_factor_auth::Migration),
+            Box::new(m20260122_101530_user_identities::Migration),
+            Box::new(m20260123_084210_hash_user_tokens::Migration),
+            Box::new(m20260124_101205_keep_orders_of_deleted_users::Migration),
+            Box::new(m20260125_093040_audit_events::Migration),
+            Box::new(m20260126_084515_user_roles::Migration),
+            Box::new(m20260127_090412_expire_unlock_tokens::Migration),
+            Box::new(m20260128_091530_two_factor_enrollment_links::Migration),
+            Box::new(m20260129_093010_account_deletion_links::Migration),
+            Box::new(m20260130_084020_idempotency_key_leases::Migration),
             // inject-above (do not remove this comment)
         ]
     }
@@ -0,0 +1,54 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.create_type(
+            Type::create()
+                .as_enum("review_status")
+                .values(["PENDING", "PUBLISHED", "REJECTED"])
+                .to_owned(),
+        )
+        .await?;
+        // Reviews written before moderation existed stay visible.
+        m.alter_table(
+            Table::alter()
+                .table("reviews")
+                .add_column(
+                    ColumnDef::new("status")
+                        .enumeration("review_status", ["PENDING", "PUBLISHED", "REJECTED"])
+                        .not_null()
+                        .default("PUBLISHED"),
+                )
+                .to_owned(),
+        )
+        .await?;
+        create_table(
+            m,
+            "review_reports",
+            &[("id", ColType::PkAuto), ("reason", ColType::TextNull)],
+            &[("review", ""), ("user", "")],
+        )
+        .await?;
+        m.create_index(
+            Index::create()
+                .table("review_reports")
+                .name("review_reports_review_id_user_id_key")
+                .col("review_id")
+                .col("user_id")
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "review_reports").await?;
+        remove_column(m, "reviews", "status").await?;
+        drop_enum_type(m, "review_status").await
+    }
+}
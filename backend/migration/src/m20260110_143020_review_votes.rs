@@ -0,0 +1,53 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "review_votes",
+            &[("id", ColType::PkAuto), ("helpful", ColType::Boolean)],
+            &[("review", ""), ("user", "")],
+        )
+        .await?;
+        m.create_index(
+            Index::create()
+                .table("review_votes")
+                .name("review_votes_review_id_user_id_key")
+                .col("review_id")
+                .col("user_id")
+                .unique()
+                .to_owned(),
+        )
+        .await?;
+        // Vote counts are kept on the review so listings can sort by them
+        m.alter_table(
+            Table::alter()
+                .table("reviews")
+                .add_column(
+                    ColumnDef::new("helpful_count")
+                        .integer()
+                        .not_null()
+                        .default(0),
+                )
+                .add_column(
+                    ColumnDef::new("unhelpful_count")
+                        .integer()
+                        .not_null()
+                        .default(0),
+                )
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "reviews", "unhelpful_count").await?;
+        remove_column(m, "reviews", "helpful_count").await?;
+        drop_table(m, "review_votes").await
+    }
+}
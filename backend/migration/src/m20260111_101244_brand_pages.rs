@@ -0,0 +1,57 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("brands")
+                .add_column(ColumnDef::new("slug").string().null())
+                .add_column(ColumnDef::new("logo_url").string().null())
+                .add_column(ColumnDef::new("website_url").string().null())
+                .to_owned(),
+        )
+        .await?;
+
+        // Derive slugs for existing brands from their names, disambiguating
+        // brands that end up with the same slug by their ID.
+        let db = m.get_connection();
+        db.execute_unprepared(
+            "UPDATE brands SET slug = trim(both '-' from \
+             lower(regexp_replace(name, '[^a-zA-Z0-9]+', '-', 'g')))",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE brands SET slug = slug || '-' || id WHERE id NOT IN \
+             (SELECT min(id) FROM brands GROUP BY slug)",
+        )
+        .await?;
+
+        m.alter_table(
+            Table::alter()
+                .table("brands")
+                .modify_column(ColumnDef::new("slug").string().not_null())
+                .to_owned(),
+        )
+        .await?;
+        m.create_index(
+            Index::create()
+                .table("brands")
+                .name("brands_slug_key")
+                .col("slug")
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "brands", "website_url").await?;
+        remove_column(m, "brands", "logo_url").await?;
+        remove_column(m, "brands", "slug").await
+    }
+}
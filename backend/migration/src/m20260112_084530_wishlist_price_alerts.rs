@@ -0,0 +1,52 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("wishlists")
+                .add_column(ColumnDef::new("saved_price").double().null())
+                .add_column(
+                    ColumnDef::new("price_alert_sent_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await?;
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(
+                    ColumnDef::new("price_alerts_enabled")
+                        .boolean()
+                        .not_null()
+                        .default(true),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // Existing wishlist entries start out at today's price
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE wishlists SET saved_price = (SELECT products.price * \
+                 (100 - coalesce(products.discount_percentage, 0)) / 100.0 \
+                 FROM products WHERE products.id = wishlists.product_id)",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "users", "price_alerts_enabled").await?;
+        remove_column(m, "wishlists", "price_alert_sent_at").await?;
+        remove_column(m, "wishlists", "saved_price").await
+    }
+}
@@ -0,0 +1,111 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "wishlist_lists",
+            &[
+                ("id", ColType::PkAuto),
+                ("name", ColType::String),
+                ("is_default", ColType::Boolean),
+                ("share_token", ColType::StringNull),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+        m.create_index(
+            Index::create()
+                .table("wishlist_lists")
+                .name("wishlist_lists_share_token_key")
+                .col("share_token")
+                .unique()
+                .to_owned(),
+        )
+        .await?;
+
+        // A user has at most one default list
+        let db = m.get_connection();
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX wishlist_lists_default_key ON wishlist_lists (user_id) \
+             WHERE is_default",
+        )
+        .await?;
+
+        // Every user with saved products gets a default list holding them
+        db.execute_unprepared(
+            "INSERT INTO wishlist_lists (user_id, name, is_default) \
+             SELECT DISTINCT user_id, 'Wishlist', true FROM wishlists",
+        )
+        .await?;
+
+        m.alter_table(
+            Table::alter()
+                .table("wishlists")
+                .add_column(ColumnDef::new("wishlist_list_id").integer().null())
+                .add_column(ColumnDef::new("product_variant_id").integer().null())
+                .to_owned(),
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE wishlists SET wishlist_list_id = (SELECT wishlist_lists.id \
+             FROM wishlist_lists WHERE wishlist_lists.user_id = wishlists.user_id \
+             AND wishlist_lists.is_default)",
+        )
+        .await?;
+
+        m.alter_table(
+            Table::alter()
+                .table("wishlists")
+                .modify_column(ColumnDef::new("wishlist_list_id").integer().not_null())
+                .add_foreign_key(
+                    TableForeignKey::new()
+                        .name("fk-wishlists-wishlist_list_id-to-wishlist_lists")
+                        .from_tbl("wishlists")
+                        .from_col("wishlist_list_id")
+                        .to_tbl("wishlist_lists")
+                        .to_col("id")
+                        .on_delete(ForeignKeyAction::Cascade)
+                        .on_update(ForeignKeyAction::Cascade),
+                )
+                .add_foreign_key(
+                    TableForeignKey::new()
+                        .name("fk-wishlists-product_variant_id-to-product_variants")
+                        .from_tbl("wishlists")
+                        .from_col("product_variant_id")
+                        .to_tbl("product_variants")
+                        .to_col("id")
+                        .on_delete(ForeignKeyAction::SetNull)
+                        .on_update(ForeignKeyAction::Cascade),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // A product, or a variant of it, is saved at most once per list
+        db.execute_unprepared(
+            "DELETE FROM wishlists a USING wishlists b WHERE a.id > b.id \
+             AND a.wishlist_list_id = b.wishlist_list_id AND a.product_id = b.product_id \
+             AND a.product_variant_id IS NOT DISTINCT FROM b.product_variant_id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX wishlists_list_product_variant_key ON wishlists \
+             (wishlist_list_id, product_id, product_variant_id) NULLS NOT DISTINCT",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "wishlists", "product_variant_id").await?;
+        remove_column(m, "wishlists", "wishlist_list_id").await?;
+        drop_table(m, "wishlist_lists").await
+    }
+}
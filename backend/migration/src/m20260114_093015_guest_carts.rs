@@ -0,0 +1,84 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "guest_carts",
+            &[
+                ("id", ColType::PkAuto),
+                ("pid", ColType::UuidUniq),
+                ("expires_at", ColType::TimestampWithTimeZone),
+            ],
+            &[],
+        )
+        .await?;
+
+        m.alter_table(
+            Table::alter()
+                .table("cart_items")
+                .modify_column(ColumnDef::new("user_id").integer().null())
+                .add_column(ColumnDef::new("guest_cart_id").integer().null())
+                .add_foreign_key(
+                    TableForeignKey::new()
+                        .name("fk-cart_items-guest_cart_id-to-guest_carts")
+                        .from_tbl("cart_items")
+                        .from_col("guest_cart_id")
+                        .to_tbl("guest_carts")
+                        .to_col("id")
+                        .on_delete(ForeignKeyAction::Cascade)
+                        .on_update(ForeignKeyAction::Cascade),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // A cart line belongs to exactly one of a user or a guest cart
+        m.get_connection()
+            .execute_unprepared(
+                "ALTER TABLE cart_items ADD CONSTRAINT cart_items_owner_check \
+                 CHECK (num_nonnulls(user_id, guest_cart_id) = 1)",
+            )
+            .await?;
+
+        m.create_index(
+            Index::create()
+                .table("cart_items")
+                .name("cart_items_guest_cart_id_product_variant_id_key")
+                .col("guest_cart_id")
+                .col("product_variant_id")
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.drop_index(
+            Index::drop()
+                .table("cart_items")
+                .name("cart_items_guest_cart_id_product_variant_id_key")
+                .to_owned(),
+        )
+        .await?;
+        let db = m.get_connection();
+        db.execute_unprepared("ALTER TABLE cart_items DROP CONSTRAINT cart_items_owner_check")
+            .await?;
+        db.execute_unprepared("DELETE FROM cart_items WHERE user_id IS NULL")
+            .await?;
+        remove_column(m, "cart_items", "guest_cart_id").await?;
+        m.alter_table(
+            Table::alter()
+                .table("cart_items")
+                .modify_column(ColumnDef::new("user_id").integer().not_null())
+                .to_owned(),
+        )
+        .await?;
+        drop_table(m, "guest_carts").await
+    }
+}
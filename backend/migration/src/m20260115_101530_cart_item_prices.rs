@@ -0,0 +1,34 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("cart_items")
+                .add_column(ColumnDef::new("unit_price").double().null())
+                .to_owned(),
+        )
+        .await?;
+
+        // Existing lines take today's price, so they don't all report a change
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE cart_items SET unit_price = products.price * \
+                 (100 - LEAST(GREATEST(COALESCE(products.discount_percentage, 0), 0), 100)) / 100.0 \
+                 FROM product_variants JOIN products ON products.id = product_variants.product_id \
+                 WHERE product_variants.id = cart_items.product_variant_id",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "cart_items", "unit_price").await
+    }
+}
@@ -0,0 +1,40 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "cart_reminders",
+            &[
+                ("id", ColType::PkAuto),
+                ("cart_updated_at", ColType::TimestampWithTimeZone),
+                ("sequence", ColType::Integer),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+
+        // Lets concurrent runs of the reminder task claim a reminder without
+        // sending it twice
+        m.create_index(
+            Index::create()
+                .table("cart_reminders")
+                .name("cart_reminders_user_id_cart_updated_at_sequence_key")
+                .col("user_id")
+                .col("cart_updated_at")
+                .col("sequence")
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "cart_reminders").await
+    }
+}
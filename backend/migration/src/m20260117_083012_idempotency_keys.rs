@@ -0,0 +1,40 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "idempotency_keys",
+            &[
+                ("id", ColType::PkAuto),
+                ("key", ColType::String),
+                ("request_fingerprint", ColType::String),
+                ("response_status", ColType::SmallIntegerNull),
+                ("response_content_type", ColType::StringNull),
+                ("response_body", ColType::TextNull),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+
+        m.create_index(
+            Index::create()
+                .table("idempotency_keys")
+                .name("idempotency_keys_user_id_key_key")
+                .col("user_id")
+                .col("key")
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "idempotency_keys").await
+    }
+}
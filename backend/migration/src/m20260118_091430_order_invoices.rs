@@ -0,0 +1,62 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("orders")
+                .add_column(
+                    ColumnDef::new("invoice_number")
+                        .integer()
+                        .null()
+                        .unique_key(),
+                )
+                .add_column(
+                    ColumnDef::new("invoiced_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // A single row counter; incrementing it locks the row until the
+        // transaction ends, so numbers are handed out without gaps
+        create_table(
+            m,
+            "invoice_sequences",
+            &[("id", ColType::PkAuto), ("last_number", ColType::Integer)],
+            &[],
+        )
+        .await?;
+
+        // Orders that are already past payment get numbers in the order
+        // they were placed
+        let db = m.get_connection();
+        db.execute_unprepared(
+            "UPDATE orders SET invoice_number = numbered.number, invoiced_at = orders.updated_at \
+             FROM (SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, id) AS number FROM orders \
+             WHERE status IN ('PAID', 'SHIPPED', 'DELIVERED')) AS numbered \
+             WHERE numbered.id = orders.id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "INSERT INTO invoice_sequences (last_number) \
+             SELECT COALESCE(MAX(invoice_number), 0) FROM orders",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "invoice_sequences").await?;
+        remove_column(m, "orders", "invoiced_at").await?;
+        remove_column(m, "orders", "invoice_number").await
+    }
+}
@@ -0,0 +1,38 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "user_sessions",
+            &[
+                ("id", ColType::PkAuto),
+                ("family", ColType::Uuid),
+                ("token_hash", ColType::StringUniq),
+                ("expires_at", ColType::TimestampWithTimeZone),
+                ("rotated_at", ColType::TimestampWithTimeZoneNull),
+                ("revoked_at", ColType::TimestampWithTimeZoneNull),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+
+        m.create_index(
+            Index::create()
+                .table("user_sessions")
+                .name("user_sessions_family_idx")
+                .col("family")
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "user_sessions").await
+    }
+}
@@ -0,0 +1,56 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(
+                    ColumnDef::new("failed_login_count")
+                        .integer()
+                        .not_null()
+                        .default(0),
+                )
+                .add_column(
+                    ColumnDef::new("last_failed_login_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .add_column(
+                    ColumnDef::new("locked_until")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .add_column(ColumnDef::new("unlock_token").string().null())
+                .to_owned(),
+        )
+        .await?;
+
+        // Fixed window counters of requests per client IP or email address
+        create_table(
+            m,
+            "auth_throttles",
+            &[
+                ("id", ColType::PkAuto),
+                ("key", ColType::StringUniq),
+                ("hits", ColType::Integer),
+                ("window_started_at", ColType::TimestampWithTimeZone),
+            ],
+            &[],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "auth_throttles").await?;
+        remove_column(m, "users", "unlock_token").await?;
+        remove_column(m, "users", "locked_until").await?;
+        remove_column(m, "users", "last_failed_login_at").await?;
+        remove_column(m, "users", "failed_login_count").await
+    }
+}
@@ -0,0 +1,59 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(ColumnDef::new("totp_secret").string().null())
+                .add_column(
+                    ColumnDef::new("totp_enabled_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                // Time step of the last accepted code, so a code can't be
+                // used twice
+                .add_column(ColumnDef::new("totp_last_step").big_integer().null())
+                .to_owned(),
+        )
+        .await?;
+
+        create_table(
+            m,
+            "user_recovery_codes",
+            &[
+                ("id", ColType::PkAuto),
+                ("code_hash", ColType::String),
+                ("used_at", ColType::TimestampWithTimeZoneNull),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+
+        create_table(
+            m,
+            "two_factor_challenges",
+            &[
+                ("id", ColType::PkAuto),
+                ("token_hash", ColType::StringUniq),
+                ("expires_at", ColType::TimestampWithTimeZone),
+                ("attempts", ColType::IntegerWithDefault(0)),
+            ],
+            &[("user", "")],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "two_factor_challenges").await?;
+        drop_table(m, "user_recovery_codes").await?;
+        remove_column(m, "users", "totp_last_step").await?;
+        remove_column(m, "users", "totp_enabled_at").await?;
+        remove_column(m, "users", "totp_secret").await
+    }
+}
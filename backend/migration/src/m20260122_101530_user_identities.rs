@@ -0,0 +1,58 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "user_identities",
+            &[
+                ("id", ColType::PkAuto),
+                ("provider", ColType::String),
+                // The provider's stable identifier of the account
+                ("subject", ColType::String),
+                ("email", ColType::StringNull),
+            ],
+            &[("user", "")],
+        )
+        .await?;
+
+        m.create_index(
+            Index::create()
+                .table("user_identities")
+                .name("user_identities_provider_subject_key")
+                .col("provider")
+                .col("subject")
+                .unique()
+                .to_owned(),
+        )
+        .await?;
+
+        create_table(
+            m,
+            "oauth_states",
+            &[
+                ("id", ColType::PkAuto),
+                ("state_hash", ColType::StringUniq),
+                ("provider", ColType::String),
+                ("code_verifier", ColType::String),
+                ("nonce", ColType::String),
+                ("expires_at", ColType::TimestampWithTimeZone),
+                // Set when the flow links an identity to a signed in user
+                // rather than signing in
+                ("link_user_id", ColType::IntegerNull),
+            ],
+            &[],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "oauth_states").await?;
+        drop_table(m, "user_identities").await
+    }
+}
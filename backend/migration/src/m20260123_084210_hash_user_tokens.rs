@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // Tokens are now stored as SHA-256 hashes. Hashing the outstanding
+        // ones keeps links already emailed working
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE users SET \
+                 reset_token = encode(sha256(convert_to(reset_token, 'UTF8')), 'hex'), \
+                 email_verification_token = \
+                 encode(sha256(convert_to(email_verification_token, 'UTF8')), 'hex'), \
+                 magic_link_token = encode(sha256(convert_to(magic_link_token, 'UTF8')), 'hex')",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // Hashes can't be turned back into tokens, so outstanding links stop
+        // working
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE users SET reset_token = NULL, email_verification_token = NULL, \
+                 magic_link_token = NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+}
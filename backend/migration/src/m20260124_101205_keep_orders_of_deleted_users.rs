@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const ORDERS_USER_FK: &str = "fk-users-user_id-to-orders";
+
+async fn replace_user_fk(m: &SchemaManager<'_>, on_delete: ForeignKeyAction) -> Result<(), DbErr> {
+    m.drop_foreign_key(
+        ForeignKey::drop()
+            .table("orders")
+            .name(ORDERS_USER_FK)
+            .to_owned(),
+    )
+    .await?;
+    m.create_foreign_key(
+        ForeignKey::create()
+            .name(ORDERS_USER_FK)
+            .from("orders", "user_id")
+            .to("users", "id")
+            .on_delete(on_delete)
+            .on_update(ForeignKeyAction::Cascade)
+            .to_owned(),
+    )
+    .await
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // Orders are financial records and outlive the account that placed
+        // them; deleting a user leaves them without a customer
+        m.alter_table(
+            Table::alter()
+                .table("orders")
+                .modify_column(ColumnDef::new("user_id").integer().null())
+                .to_owned(),
+        )
+        .await?;
+        replace_user_fk(m, ForeignKeyAction::SetNull).await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        replace_user_fk(m, ForeignKeyAction::Cascade).await?;
+        m.get_connection()
+            .execute_unprepared("DELETE FROM orders WHERE user_id IS NULL")
+            .await?;
+        m.alter_table(
+            Table::alter()
+                .table("orders")
+                .modify_column(ColumnDef::new("user_id").integer().not_null())
+                .to_owned(),
+        )
+        .await
+    }
+}
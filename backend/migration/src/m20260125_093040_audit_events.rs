@@ -0,0 +1,62 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "audit_events",
+            &[
+                ("id", ColType::PkAuto),
+                // Who did it; null for failed sign-ins and deleted accounts
+                ("actor_id", ColType::IntegerNull),
+                ("action", ColType::String),
+                ("entity_type", ColType::String),
+                ("entity_id", ColType::String),
+                // Only the fields that changed, as they were and became
+                ("before", ColType::JsonBinaryNull),
+                ("after", ColType::JsonBinaryNull),
+            ],
+            &[],
+        )
+        .await?;
+
+        // The log outlives the accounts it mentions
+        m.create_foreign_key(
+            ForeignKey::create()
+                .name("fk-audit_events-actor_id-to-users")
+                .from("audit_events", "actor_id")
+                .to("users", "id")
+                .on_delete(ForeignKeyAction::SetNull)
+                .on_update(ForeignKeyAction::Cascade)
+                .to_owned(),
+        )
+        .await?;
+
+        m.create_index(
+            Index::create()
+                .table("audit_events")
+                .name("audit_events_entity")
+                .col("entity_type")
+                .col("entity_id")
+                .to_owned(),
+        )
+        .await?;
+        m.create_index(
+            Index::create()
+                .table("audit_events")
+                .name("audit_events_created_at")
+                .col("created_at")
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "audit_events").await
+    }
+}
@@ -0,0 +1,52 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const ROLES: [&str; 4] = ["ADMIN", "CATALOG_MANAGER", "FULFILLMENT", "SUPPORT"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.create_type(Type::create().as_enum("user_role").values(ROLES).to_owned())
+            .await?;
+        // Customers have no role
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(
+                    ColumnDef::new("role")
+                        .enumeration("user_role", ROLES)
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await?;
+        // Staff could do everything so far
+        m.get_connection()
+            .execute_unprepared("UPDATE users SET role = 'ADMIN' WHERE is_staff")
+            .await?;
+        remove_column(m, "users", "is_staff").await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(
+                    ColumnDef::new("is_staff")
+                        .boolean()
+                        .not_null()
+                        .default(false),
+                )
+                .to_owned(),
+        )
+        .await?;
+        m.get_connection()
+            .execute_unprepared("UPDATE users SET is_staff = role IS NOT NULL")
+            .await?;
+        remove_column(m, "users", "role").await?;
+        drop_enum_type(m, "user_role").await
+    }
+}
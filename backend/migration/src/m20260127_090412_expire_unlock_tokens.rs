@@ -0,0 +1,39 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                .add_column(
+                    ColumnDef::new("unlock_sent_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // Unlock tokens are now stored hashed and expire. Outstanding links
+        // stop working, the locks still run out by themselves
+        m.get_connection()
+            .execute_unprepared("UPDATE users SET unlock_token = NULL")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // Hashes can't be turned back into tokens, so outstanding links stop
+        // working
+        m.get_connection()
+            .execute_unprepared("UPDATE users SET unlock_token = NULL")
+            .await?;
+        remove_column(m, "users", "unlock_sent_at").await
+    }
+}
@@ -0,0 +1,41 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                // Hash of the token of the emailed link staff set up
+                // two-factor authentication with
+                .add_column(ColumnDef::new("totp_enrollment_token").string().null())
+                .add_column(
+                    ColumnDef::new("totp_enrollment_sent_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await?;
+
+        // Secrets of unfinished set ups were handed out on password logins
+        // alone, so they are dropped and set up again from a link
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE users SET totp_secret = NULL, totp_last_step = NULL \
+                 WHERE totp_enabled_at IS NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "users", "totp_enrollment_sent_at").await?;
+        remove_column(m, "users", "totp_enrollment_token").await
+    }
+}
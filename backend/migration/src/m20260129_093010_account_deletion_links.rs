@@ -0,0 +1,30 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table("users")
+                // Hash of the token of the emailed link a user confirms
+                // deleting their account with instead of their password
+                .add_column(ColumnDef::new("deletion_token").string().null())
+                .add_column(
+                    ColumnDef::new("deletion_sent_at")
+                        .timestamp_with_time_zone()
+                        .null(),
+                )
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "users", "deletion_sent_at").await?;
+        remove_column(m, "users", "deletion_token").await
+    }
+}
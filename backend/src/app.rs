@@ -18,7 +18,12 @@ use utoipa::OpenApi;
 use migration::Migrator;
 
 #[allow(unused_imports)]
-use crate::{controllers, models::_entities::users, tasks, workers::downloader::DownloadWorker};
+use crate::{
+    controllers, initializers,
+    models::_entities::users,
+    tasks,
+    workers::{downloader::DownloadWorker, price_drop::PriceDropWorker},
+};
 
 pub struct App;
 #[async_trait]
@@ -46,8 +51,8 @@ impl Hooks for App {
     }
 
     async fn initializers(_ctx: &AppContext) -> Result<Vec<Box<dyn Initializer>>> {
-        Ok(vec![Box::new(
-            loco_openapi::OpenapiInitializerWithSetup::new(
+        Ok(vec![
+            Box::new(loco_openapi::OpenapiInitializerWithSetup::new(
                 |ctx| {
                     #[derive(OpenApi)]
                     #[openapi(modifiers(&SecurityAddon), info(title = "Shoes Store API"))]
@@ -57,22 +62,32 @@ impl Hooks for App {
                     ApiDoc::openapi()
                 },
                 Some(vec![
+                    controllers::audit_events::api_routes(),
                     controllers::auth::api_routes(),
                     controllers::brands::api_routes(),
                     controllers::cart_items::api_routes(),
                     controllers::categories::api_routes(),
+                    controllers::moderation::api_routes(),
                     controllers::orders::api_routes(),
                     controllers::products::api_routes(),
                     controllers::product_variants::api_routes(),
+                    controllers::reports::api_routes(),
                     controllers::reviews::api_routes(),
+                    controllers::roles::api_routes(),
                     controllers::wishlists::api_routes(),
                 ]),
-            ),
-        )])
+            )),
+            Box::new(initializers::idempotency::IdempotencyInitializer),
+            Box::new(initializers::error_envelope::ErrorEnvelopeInitializer),
+        ])
     }
 
     fn routes(_ctx: &AppContext) -> AppRoutes {
         AppRoutes::with_default_routes() // controller routes below
+            .add_route(controllers::roles::routes())
+            .add_route(controllers::audit_events::routes())
+            .add_route(controllers::moderation::routes())
+            .add_route(controllers::reports::routes())
             .add_route(controllers::orders::routes())
             .add_route(controllers::wishlists::routes())
             .add_route(controllers::reviews::routes())
@@ -85,11 +100,17 @@ impl Hooks for App {
     }
     async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {
         queue.register(DownloadWorker::build(ctx)).await?;
+        queue.register(PriceDropWorker::build(ctx)).await?;
         Ok(())
     }
 
     #[allow(unused_variables)]
     fn register_tasks(tasks: &mut Tasks) {
+        tasks.register(tasks::cart_reminders::SendCartReminders);
+        tasks.register(tasks::expire_guest_carts::ExpireGuestCarts);
+        tasks.register(tasks::purge_idempotency_keys::PurgeIdempotencyKeys);
+        tasks.register(tasks::purge_user_sessions::PurgeUserSessions);
+        tasks.register(tasks::purge_auth_throttles::PurgeAuthThrottles);
         // tasks-inject (do not remove)
     }
     async fn truncate(ctx: &AppContext) -> Result<()> {
@@ -119,6 +140,8 @@ impl Hooks for App {
         seed!(orders);
         seed!(order_items);
         seed!(reviews);
+        seed!(review_reports);
+        seed!(wishlist_lists);
         seed!(wishlists);
 
         Ok(())
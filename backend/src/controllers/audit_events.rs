@@ -0,0 +1,114 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use chrono::{DateTime, Utc};
+use loco_openapi::prelude::{routes, OpenApiRouter};
+use loco_rs::{model::query::PaginationQuery, prelude::*};
+use sea_orm::{PaginatorTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::ErrorDetail,
+    models::{
+        audit_events::{Column, Entity},
+        users,
+    },
+    permissions::{require, Authorized},
+    views::{audit_events::AuditEvent, pagination::PageResponse, users::User},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AuditQuery {
+    /// Only list events caused by this user.
+    #[serde(default)]
+    pub actor_id: Option<i32>,
+    /// Only list events of this action, e.g. `update` or `login_failed`.
+    #[serde(default)]
+    pub action: Option<String>,
+    /// Only list events about this kind of entity, e.g. `product` or `user`.
+    #[serde(default)]
+    pub entity_type: Option<String>,
+    /// Only list events about the entity with this ID. Users are identified
+    /// by their pid.
+    #[serde(default)]
+    pub entity_id: Option<String>,
+    /// Only list events at or after this time.
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only list events before this time.
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Lists the audit log, newest first: changes staff made to the catalog,
+/// orders and reviews, and sign-ins and password changes.
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    tags = ["Audit"],
+    summary = "List audit events",
+    responses(
+        (status = OK, description = "Audit events listed", body = PageResponse<AuditEvent>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn list(
+    _: Authorized<require::AuditRead>,
+    State(ctx): State<AppContext>,
+    Query(pagination): Query<PaginationQuery>,
+    Query(params): Query<AuditQuery>,
+) -> Result<Response> {
+    let mut query = Entity::find();
+    if let Some(actor_id) = params.actor_id {
+        query = query.filter(Column::ActorId.eq(actor_id));
+    }
+    if let Some(action) = params.action {
+        query = query.filter(Column::Action.eq(action));
+    }
+    if let Some(entity_type) = params.entity_type {
+        query = query.filter(Column::EntityType.eq(entity_type));
+    }
+    if let Some(entity_id) = params.entity_id {
+        query = query.filter(Column::EntityId.eq(entity_id));
+    }
+    if let Some(from) = params.from {
+        query = query.filter(Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = params.to {
+        query = query.filter(Column::CreatedAt.lt(to));
+    }
+
+    let paginator = query
+        .find_also_related(users::Entity)
+        .order_by_desc(Column::CreatedAt)
+        .order_by_desc(Column::Id)
+        .paginate(&ctx.db, pagination.page_size);
+    let counts = paginator.num_items_and_pages().await?;
+    let items = paginator
+        .fetch_page(pagination.page - 1)
+        .await?
+        .into_iter()
+        .map(|(event, actor)| AuditEvent {
+            event,
+            actor: actor.map(|u| User {
+                id: u.id,
+                name: u.name,
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    format::json(PageResponse {
+        items,
+        counts: counts.into(),
+    })
+}
+
+pub fn routes() -> Routes {
+    Routes::new().prefix("api/admin/audit/").add("/", get(list))
+}
+
+pub fn api_routes() -> OpenApiRouter<AppContext> {
+    OpenApiRouter::new().routes(routes!(list))
+}
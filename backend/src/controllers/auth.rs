@@ -1,29 +1,39 @@
 // backend/src/controllers/auth.rs
-use std::sync::OnceLock;
-
+use axum::http::{header::CONTENT_DISPOSITION, StatusCode};
+use chrono::Duration;
 use loco_openapi::prelude::{routes, OpenApiRouter};
 use loco_rs::prelude::*;
-use regex::Regex;
+use sea_orm::QueryOrder;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::ErrorDetail,
+    controllers::{
+        bad_gateway, bad_request, cart_items::GuestCart, conflict, forbidden, gone,
+        too_many_requests, ErrorDetail,
+    },
     mailers::auth::AuthMailer,
     models::{
-        _entities::users,
-        users::{LoginParams, Model, RegisterParams},
+        _entities::{cart_items, order_items, orders, reviews, users, wishlist_lists, wishlists},
+        audit_events::{self, NewEvent},
+        auth_throttles,
+        guest_carts::MergeReduction,
+        oauth_states, product_variants, two_factor_challenges, user_identities,
+        user_recovery_codes,
+        user_sessions::{self, Refresh},
+        users::{FailedLogin, LoginParams, Model, RegisterParams, ResetToken},
+    },
+    oidc, registration,
+    settings::{OAuthProviderSettings, RateLimitSettings, Settings, VerificationGate},
+    views::{
+        auth::{
+            AccountExport, CurrentResponse, IdentityResponse, LoginResponse,
+            OAuthAuthorizationResponse, ProfileExport, RecoveryCodesResponse, TotpSetupResponse,
+            TwoFactorChallengeResponse, WishlistExport,
+        },
+        orders::Order,
     },
-    views::auth::{CurrentResponse, LoginResponse},
 };
 
-pub static EMAIL_DOMAIN_RE: OnceLock<Regex> = OnceLock::new();
-
-fn get_allow_email_domain_re() -> &'static Regex {
-    EMAIL_DOMAIN_RE.get_or_init(|| {
-        Regex::new(r"@example\.com$|@gmail\.com$").expect("Failed to compile regex")
-    })
-}
-
 #[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct ForgotParams {
     pub email: String,
@@ -51,27 +61,410 @@ pub struct ChangePasswordParams {
     pub new_password: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RefreshParams {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TwoFactorVerifyParams {
+    pub challenge_token: String,
+    /// Code from the authenticator app, or one of the recovery codes.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TwoFactorCodeParams {
+    /// Code from the authenticator app.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TwoFactorDisableParams {
+    pub password: String,
+    /// Code from the authenticator app, or one of the recovery codes.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct DeleteAccountParams {
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Token from the emailed deletion link, instead of the password.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Code from the authenticator app, or one of the recovery codes. Needed
+    /// when two-factor authentication is enabled.
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+/// What the identity provider sent the browser back to the redirect URI with.
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct OAuthCallbackParams {
+    pub code: String,
+    pub state: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct UserUpdateParams {
-    pub name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Whether to receive emails when wishlisted products drop in price.
+    #[serde(default)]
+    pub price_alerts_enabled: Option<bool>,
+}
+
+/// Moves the anonymous cart a visitor built before signing in into the cart of
+/// their account, and returns the lines whose quantity had to be lowered.
+/// Failures are logged rather than failing the sign in.
+async fn merge_guest_cart(
+    ctx: &AppContext,
+    guest: GuestCart,
+    user: &users::Model,
+) -> Vec<MergeReduction> {
+    let GuestCart(Some(cart)) = guest else {
+        return Vec::new();
+    };
+
+    let merged = match Settings::from_context(ctx) {
+        Ok(settings) => cart.merge_into(&ctx.db, user.id, &settings.carts).await,
+        Err(err) => Err(ModelError::to_msg(err)),
+    };
+    merged.unwrap_or_else(|err| {
+        tracing::error!(
+            err = err.to_string(),
+            user_pid = user.pid.to_string(),
+            "could not merge guest cart"
+        );
+        Vec::new()
+    })
+}
+
+/// The client IP rate limits are counted against, as resolved by the
+/// `remote_ip` middleware: the address the request came from, or the
+/// rightmost address in `X-Forwarded-For` that isn't a trusted proxy.
+pub fn client_ip(ip: RemoteIP) -> String {
+    match ip {
+        RemoteIP::Forwarded(ip) | RemoteIP::Socket(ip) => ip.to_string(),
+        RemoteIP::None => "unknown".to_string(),
+    }
+}
+
+/// Counts a request against a rate limit key and returns the seconds to wait
+/// when there were more than `limit` in the current window.
+pub async fn throttle(
+    ctx: &AppContext,
+    limits: &RateLimitSettings,
+    key: &str,
+    limit: i32,
+) -> Result<Option<u64>> {
+    let window = Duration::minutes(limits.window_minutes);
+    Ok(auth_throttles::Model::hit(&ctx.db, key, limit, window).await?)
+}
+
+/// Rate limits the endpoints that send emails, per client IP and per
+/// recipient, so they can't be used to flood someone's mailbox.
+async fn throttle_emails(
+    ctx: &AppContext,
+    limits: &RateLimitSettings,
+    ip: RemoteIP,
+    email: &str,
+) -> Result<Option<u64>> {
+    let ip_key = format!("emails:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(ctx, limits, &ip_key, limits.emails_per_ip).await? {
+        return Ok(Some(wait));
+    }
+
+    let address_key = format!("emails:to:{}", email.trim().to_lowercase());
+    throttle(ctx, limits, &address_key, limits.emails_per_address).await
+}
+
+/// Records something that happened to a user's account in the audit log.
+/// `by_user` is false when whoever did it is not known to be the user, as
+/// with a wrong password.
+async fn audit_account(
+    ctx: &AppContext,
+    user: &users::Model,
+    action: &'static str,
+    by_user: bool,
+) -> Result<()> {
+    audit_events::Model::record(
+        &ctx.db,
+        NewEvent {
+            actor_id: by_user.then_some(user.id),
+            action,
+            entity_type: "user",
+            entity_id: user.pid.to_string(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Starts a new session for a user who just signed in and issues its access
+/// and refresh tokens. Failed password attempts are forgotten.
+async fn sign_in(ctx: &AppContext, user: &users::Model) -> Result<LoginResponse> {
+    let settings = Settings::from_context(ctx)?;
+    if user.failed_login_count > 0 || user.unlock_token.is_some() {
+        user.clone().into_active_model().unlock(&ctx.db).await?;
+    }
+    let (_, refresh_token) =
+        user_sessions::Model::start(&ctx.db, user.id, settings.sessions.refresh_token_ttl_days)
+            .await?;
+    audit_account(ctx, user, "login", true).await?;
+
+    let jwt_secret = ctx.config.get_jwt_config()?;
+    let token = user
+        .generate_jwt(&jwt_secret.secret, jwt_secret.expiration)
+        .or_else(|e| {
+            tracing::debug!(err = ?e, "could not create JWT token");
+            unauthorized("unauthorized!")
+        })?;
+
+    Ok(LoginResponse::new(user, &token, &refresh_token))
+}
+
+/// Finishes a login whose password or magic link checked out. Users with
+/// two-factor authentication get a challenge to answer with a code instead of
+/// tokens. Staff who must use it but don't yet are turned away and emailed a
+/// link to set it up with, so the password alone isn't enough to do that.
+/// Unverified users are turned away when the policy requires verifying first.
+async fn complete_login(
+    ctx: &AppContext,
+    user: users::Model,
+    guest: GuestCart,
+) -> Result<Response> {
+    let settings = Settings::from_context(ctx)?;
+    if user.email_verified_at.is_none()
+        && settings.email_verification.required_for == VerificationGate::Login
+    {
+        return forbidden("Verify your email address before signing in.");
+    }
+    let enrolled = user.totp_enabled_at.is_some();
+    let two_factor = enrolled || (user.is_staff() && settings.two_factor.required_for_staff);
+    if !two_factor {
+        let mut response = sign_in(ctx, &user).await?;
+        response.cart_reductions = merge_guest_cart(ctx, guest, &user).await;
+        return format::json(response);
+    }
+
+    if !enrolled {
+        let ttl_minutes = settings.two_factor.enrollment_link_ttl_minutes;
+        // One link at a time, so logins can't be used to flood the mailbox
+        if !user.totp_enrollment_link_valid(ttl_minutes) {
+            let (user, token) = user
+                .into_active_model()
+                .set_totp_enrollment_sent(&ctx.db)
+                .await?;
+            AuthMailer::send_two_factor_setup(ctx, &user, &token, ttl_minutes).await?;
+        }
+        return forbidden(
+            "Set up two-factor authentication with the link emailed to you before signing in.",
+        );
+    }
+
+    challenge(ctx, &settings, &user, None).await
+}
+
+/// Starts the second step of a login, answered at `/api/auth/2fa/verify`.
+/// `setup` is the authenticator secret of a user who is setting it up.
+async fn challenge(
+    ctx: &AppContext,
+    settings: &Settings,
+    user: &users::Model,
+    setup: Option<TotpSetupResponse>,
+) -> Result<Response> {
+    let (_, challenge_token) = two_factor_challenges::Model::start(
+        &ctx.db,
+        user.id,
+        settings.two_factor.challenge_ttl_minutes,
+    )
+    .await?;
+
+    format::render()
+        .status(StatusCode::ACCEPTED)
+        .json(TwoFactorChallengeResponse {
+            challenge_token,
+            setup,
+        })
+}
+
+/// Outcome of checking a password or second factor.
+enum Verification {
+    Valid,
+    Invalid,
+    /// Too many wrong codes were tried, the seconds to wait.
+    Throttled(u64),
+}
+
+/// Checks a second factor: a code from the authenticator app, or else, with
+/// `recovery_codes`, one of the recovery codes, which is used up.
+///
+/// Wrong codes are counted per account, whichever challenge or endpoint they
+/// were tried at, and further attempts are delayed and locked out like wrong
+/// passwords.
+async fn verify_second_factor(
+    ctx: &AppContext,
+    user: &users::Model,
+    code: &str,
+    recovery_codes: bool,
+) -> Result<Verification> {
+    let settings = Settings::from_context(ctx)?;
+    let key = format!("2fa:user:{}", user.id);
+    let window = Duration::minutes(settings.rate_limits.window_minutes);
+    if let Some(wait) =
+        auth_throttles::Model::login_wait(&ctx.db, &key, &settings.login, window).await?
+    {
+        return Ok(Verification::Throttled(wait));
+    }
+
+    if user.verify_totp(&ctx.db, code).await?
+        || (recovery_codes && user_recovery_codes::Model::redeem(&ctx.db, user.id, code).await?)
+    {
+        return Ok(Verification::Valid);
+    }
+    auth_throttles::Model::hit(&ctx.db, &key, i32::MAX, window).await?;
+    tracing::info!(pid = user.pid.to_string(), "invalid two-factor code");
+    Ok(Verification::Invalid)
+}
+
+/// Checks the password of a signed in user confirming a sensitive change.
+/// Wrong passwords are counted per account and further attempts are delayed
+/// and locked out like at login, so a stolen access token can't be used to
+/// guess the password.
+async fn verify_current_password(
+    ctx: &AppContext,
+    user: &users::Model,
+    password: &str,
+) -> Result<Verification> {
+    let settings = Settings::from_context(ctx)?;
+    let key = format!("password:user:{}", user.id);
+    let window = Duration::minutes(settings.rate_limits.window_minutes);
+    if let Some(wait) =
+        auth_throttles::Model::login_wait(&ctx.db, &key, &settings.login, window).await?
+    {
+        return Ok(Verification::Throttled(wait));
+    }
+
+    if user.verify_password(password) {
+        return Ok(Verification::Valid);
+    }
+    auth_throttles::Model::hit(&ctx.db, &key, i32::MAX, window).await?;
+    tracing::info!(pid = user.pid.to_string(), "invalid current password");
+    Ok(Verification::Invalid)
+}
+
+/// Finds a configured identity provider. Providers without a client id are
+/// turned off.
+fn oauth_provider<'a>(settings: &'a Settings, name: &str) -> Result<&'a OAuthProviderSettings> {
+    settings
+        .oauth
+        .providers
+        .get(name)
+        .filter(|provider| !provider.client_id.is_empty())
+        .ok_or(Error::NotFound)
+}
+
+async fn discover_provider(settings: &OAuthProviderSettings) -> Result<oidc::Provider<'_>> {
+    match oidc::Provider::discover(settings).await {
+        Ok(provider) => Ok(provider),
+        Err(err) => {
+            tracing::error!(
+                err = err.to_string(),
+                issuer = settings.issuer,
+                "could not discover identity provider"
+            );
+            bad_gateway("The identity provider could not be reached.")
+        }
+    }
+}
+
+/// Starts an authorization with an identity provider, to sign in or, with
+/// `link_user_id`, to link an account to that user, and returns where to send
+/// the user.
+async fn start_oauth(
+    ctx: &AppContext,
+    provider_name: &str,
+    link_user_id: Option<i32>,
+) -> Result<Response> {
+    let settings = Settings::from_context(ctx)?;
+    let provider = discover_provider(oauth_provider(&settings, provider_name)?).await?;
+
+    let (pending, state) = oauth_states::Model::start(
+        &ctx.db,
+        provider_name,
+        link_user_id,
+        settings.oauth.state_ttl_minutes,
+    )
+    .await?;
+    let authorization_url = provider
+        .authorization_url(&state, &pending.nonce, &pending.code_verifier)
+        .map_err(|err| Error::string(&err.to_string()))?;
+
+    format::json(OAuthAuthorizationResponse { authorization_url })
+}
+
+/// Redeems the code an identity provider sent the user back with. Returns
+/// `None` when the state is unknown, expired or was not started for
+/// `link_user_id`, or the provider refused the code.
+async fn finish_oauth(
+    ctx: &AppContext,
+    provider_name: &str,
+    link_user_id: Option<i32>,
+    params: &OAuthCallbackParams,
+) -> Result<Option<oidc::Identity>> {
+    let settings = Settings::from_context(ctx)?;
+    let provider_settings = oauth_provider(&settings, provider_name)?;
+
+    let Some(pending) = oauth_states::Model::take(&ctx.db, provider_name, &params.state).await?
+    else {
+        return Ok(None);
+    };
+    if pending.link_user_id != link_user_id {
+        return Ok(None);
+    }
+
+    let provider = discover_provider(provider_settings).await?;
+    match provider
+        .exchange(&params.code, &pending.code_verifier, &pending.nonce)
+        .await
+    {
+        Ok(identity) => Ok(Some(identity)),
+        Err(err) => {
+            tracing::warn!(
+                err = err.to_string(),
+                provider = provider_name,
+                "could not redeem authorization code"
+            );
+            Ok(None)
+        }
+    }
 }
 
 /// Register function creates a new user with the given parameters and sends a
-/// welcome email to the user
+/// welcome email to the user. The email domain and password have to pass the
+/// configured registration policy.
 #[utoipa::path(
     post,
     path = "/api/auth/register",
     tags = ["Authentication"],
     summary = "Register an account",
     responses(
-        (status = OK, description = "Registered an account")
+        (status = OK, description = "Registered an account"),
+        (status = BAD_REQUEST, description = "Email domain not allowed or password too weak", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 async fn register(
     State(ctx): State<AppContext>,
+    guest: GuestCart,
     Json(params): Json<RegisterParams>,
 ) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    registration::check_registration(&settings.registration, &params.email, &params.password)?;
+
     let res = users::Model::create_with_password(&ctx.db, &params).await;
 
     let user = match res {
@@ -86,19 +479,20 @@ async fn register(
         }
     };
 
-    let user = user
+    let (user, verify_token) = user
         .into_active_model()
         .set_email_verification_sent(&ctx.db)
         .await?;
-    let user = user.into_active_model().verified(&ctx.db).await?;
+    merge_guest_cart(&ctx, guest, &user).await;
 
-    AuthMailer::send_welcome(&ctx, &user).await?;
+    AuthMailer::send_welcome(&ctx, &user, &verify_token).await?;
 
     format::json(())
 }
 
-/// Verify register user. if the user not verified his email, he can't login to
-/// the system.
+/// Verifies the email address of a user from the link in their welcome email.
+/// Links expire and can only be used once. Depending on the configured policy,
+/// unverified users can't sign in or can't check out.
 #[utoipa::path(
     get,
     path = "/api/auth/verify/{token}",
@@ -106,22 +500,24 @@ async fn register(
     summary = "Verify account",
     responses(
         (status = OK, description = "Verified succesfully"),
-        (status = UNAUTHORIZED, description = "Invalid verification token", body = ErrorDetail)
+        (status = UNAUTHORIZED, description = "Invalid, expired or used verification token", body = ErrorDetail)
     )
 )]
 #[debug_handler]
 async fn verify(State(ctx): State<AppContext>, Path(token): Path<String>) -> Result<Response> {
-    let Ok(user) = users::Model::find_by_verification_token(&ctx.db, &token).await else {
+    let settings = Settings::from_context(&ctx)?;
+    let Ok(user) = users::Model::find_by_verification_token(
+        &ctx.db,
+        &token,
+        settings.email_verification.token_ttl_hours,
+    )
+    .await
+    else {
         return unauthorized("invalid token");
     };
 
-    if user.email_verified_at.is_some() {
-        tracing::info!(pid = user.pid.to_string(), "user already verified");
-    } else {
-        let active_model = user.into_active_model();
-        let user = active_model.verified(&ctx.db).await?;
-        tracing::info!(pid = user.pid.to_string(), "user verified");
-    }
+    let user = user.into_active_model().verified(&ctx.db).await?;
+    tracing::info!(pid = user.pid.to_string(), "user verified");
 
     format::json(())
 }
@@ -136,57 +532,99 @@ async fn verify(State(ctx): State<AppContext>, Path(token): Path<String>) -> Res
     tags = ["Authentication"],
     summary = "Send password reset request",
     responses(
-        (status = OK, description = "Password reset request received")
+        (status = OK, description = "Password reset request received"),
+        (status = TOO_MANY_REQUESTS, description = "Too many emails requested", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 async fn forgot(
     State(ctx): State<AppContext>,
+    ip: RemoteIP,
     Json(params): Json<ForgotParams>,
 ) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    if let Some(wait) = throttle_emails(&ctx, &settings.rate_limits, ip, &params.email).await? {
+        return too_many_requests(wait);
+    }
+
     let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
         // we don't want to expose our users email. if the email is invalid we still
         // returning success to the caller
         return format::json(());
     };
 
-    let user = user
+    let (user, reset_token) = user
         .into_active_model()
         .set_forgot_password_sent(&ctx.db)
         .await?;
 
-    AuthMailer::forgot_password(&ctx, &user).await?;
+    AuthMailer::forgot_password(&ctx, &user, &reset_token).await?;
 
     format::json(())
 }
 
-/// reset user password by the given parameters
+/// reset user password by the given parameters. Reset links expire, and only
+/// the latest one sent to a user works, once. An expired link is answered with
+/// `410 Gone` so the client can offer to send a new one; unknown or used
+/// links are answered like a successful reset.
 #[utoipa::path(
     post,
     path = "/api/auth/reset",
     tags = ["Authentication"],
     summary = "Reset password",
     responses(
-        (status = OK, description = "Password reset request received")
+        (status = OK, description = "Password reset request received"),
+        (status = BAD_REQUEST, description = "Password too weak", body = ErrorDetail),
+        (status = GONE, description = "The reset link expired", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many attempts", body = ErrorDetail),
     )
 )]
 #[debug_handler]
-async fn reset(State(ctx): State<AppContext>, Json(params): Json<ResetParams>) -> Result<Response> {
-    let Ok(user) = users::Model::find_by_reset_token(&ctx.db, &params.token).await else {
-        // we don't want to expose our users email. if the email is invalid we still
-        // returning success to the caller
-        tracing::info!("reset token not found");
+async fn reset(
+    State(ctx): State<AppContext>,
+    ip: RemoteIP,
+    Json(params): Json<ResetParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let limits = &settings.rate_limits;
+    let key = format!("tokens:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(&ctx, limits, &key, limits.token_attempts_per_ip).await? {
+        return too_many_requests(wait);
+    }
+    registration::check_password(&settings.registration, "password", &params.password)?;
 
-        return format::json(());
+    let ttl_minutes = settings.password_reset.token_ttl_minutes;
+    let user = match users::Model::find_by_reset_token(&ctx.db, &params.token, ttl_minutes).await {
+        Ok(ResetToken::Valid(user)) => *user,
+        Ok(ResetToken::Expired) => {
+            tracing::info!("reset token expired");
+            return gone("The password reset link expired, request a new one.");
+        }
+        Err(_) => {
+            // we don't want to expose our users email. if the email is invalid we still
+            // returning success to the caller
+            tracing::info!("reset token not found");
+
+            return format::json(());
+        }
     };
-    user.into_active_model()
+    let user = user
+        .into_active_model()
         .reset_password(&ctx.db, &params.password)
         .await?;
+    user_sessions::Model::revoke_all(&ctx.db, user.id).await?;
+    audit_account(&ctx, &user, "password_reset", true).await?;
 
     format::json(())
 }
 
-/// Creates a user login and returns a token
+/// Creates a user login and returns a token.
+///
+/// Attempts are rate limited per client IP. After repeated wrong passwords
+/// each further attempt on the account has to wait longer, and eventually
+/// the account is locked for a while and its owner is emailed an unlock link.
+/// Emails without an account are delayed the same way, so the responses
+/// don't tell whether an account exists.
 #[utoipa::path(
     post,
     path = "/api/auth/login",
@@ -194,36 +632,446 @@ async fn reset(State(ctx): State<AppContext>, Json(params): Json<ResetParams>) -
     summary = "Log in",
     responses(
         (status = OK, description = "Login successful", body = LoginResponse),
+        (status = ACCEPTED, description = "A code from the authenticator app is needed", body = TwoFactorChallengeResponse),
+        (status = FORBIDDEN, description = "Email not verified, or two-factor authentication has to be set up first", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Invalid credentials", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many attempts or account locked", body = ErrorDetail),
     )
 )]
 #[debug_handler]
-async fn login(State(ctx): State<AppContext>, Json(params): Json<LoginParams>) -> Result<Response> {
+async fn login(
+    State(ctx): State<AppContext>,
+    ip: RemoteIP,
+    guest: GuestCart,
+    Json(params): Json<LoginParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let limits = &settings.rate_limits;
+    let key = format!("logins:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(&ctx, limits, &key, limits.logins_per_ip).await? {
+        return too_many_requests(wait);
+    }
+
     let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
-        tracing::debug!(
-            email = params.email,
-            "login attempt with non-existent email"
-        );
+        let key = format!("logins:email:{}", params.email.to_lowercase());
+        let window = Duration::minutes(limits.window_minutes);
+        if let Some(wait) =
+            auth_throttles::Model::login_wait(&ctx.db, &key, &settings.login, window).await?
+        {
+            return too_many_requests(wait);
+        }
+        users::Model::verify_dummy_password(&params.password);
+        auth_throttles::Model::hit(&ctx.db, &key, i32::MAX, window).await?;
         return unauthorized("Invalid credentials!");
     };
 
+    if let Some(wait) = user.login_wait(&settings.login) {
+        tracing::info!(pid = user.pid.to_string(), "login attempt while throttled");
+        return too_many_requests(wait);
+    }
+
     let valid = user.verify_password(&params.password);
 
     if !valid {
         tracing::debug!("invalid password");
+        audit_account(&ctx, &user, "login_failed", false).await?;
+        if let FailedLogin::Locked(user, token) =
+            user.record_failed_login(&ctx.db, &settings.login).await?
+        {
+            tracing::warn!(
+                pid = user.pid.to_string(),
+                "account locked after repeated failed logins"
+            );
+            AuthMailer::send_unlock(&ctx, &user, &token, settings.login.lockout_minutes).await?;
+        }
+        return unauthorized("Invalid credentials!");
+    }
+
+    complete_login(&ctx, user, guest).await
+}
+
+/// Second step of a login with two-factor authentication: exchanges the
+/// challenge returned by the first step and a code for tokens. When the login
+/// set up two-factor authentication, the response includes recovery codes.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/verify",
+    tags = ["Authentication"],
+    summary = "Verify two-factor code",
+    responses(
+        (status = OK, description = "Login successful", body = LoginResponse),
+        (status = UNAUTHORIZED, description = "Invalid or expired challenge, or wrong code", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong codes", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_verify(
+    State(ctx): State<AppContext>,
+    guest: GuestCart,
+    Json(params): Json<TwoFactorVerifyParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let Some(challenge) = two_factor_challenges::Model::find_by_token(
+        &ctx.db,
+        &params.challenge_token,
+        settings.two_factor.max_challenge_attempts,
+    )
+    .await?
+    else {
+        return unauthorized("unauthorized!");
+    };
+    let user = users::Entity::find_by_id(challenge.user_id)
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(|| Error::Unauthorized("unauthorized!".to_string()))?;
+
+    // Setting up only takes a code from the new authenticator
+    let enrolling = user.totp_enabled_at.is_none();
+    match verify_second_factor(&ctx, &user, &params.code, !enrolling).await? {
+        Verification::Valid => {}
+        Verification::Invalid => {
+            challenge.record_failure(&ctx.db).await?;
+            return unauthorized("unauthorized!");
+        }
+        Verification::Throttled(wait) => return too_many_requests(wait),
+    }
+    if !challenge.consume(&ctx.db).await? {
         return unauthorized("unauthorized!");
     }
 
-    let jwt_secret = ctx.config.get_jwt_config()?;
+    let (user, recovery_codes) = if enrolling {
+        let user = user.into_active_model().enable_totp(&ctx.db).await?;
+        let codes = user_recovery_codes::Model::regenerate(&ctx.db, user.id).await?;
+        AuthMailer::send_two_factor_enabled(&ctx, &user).await?;
+        (user, Some(codes))
+    } else {
+        (user, None)
+    };
+
+    let mut response = sign_in(&ctx, &user).await?;
+    response.recovery_codes = recovery_codes;
+    response.cart_reductions = merge_guest_cart(&ctx, guest, &user).await;
+
+    format::json(response)
+}
+
+/// Starts setting up two-factor authentication from the link emailed to staff
+/// who have to use it. Returns the authenticator secret with a challenge,
+/// which is answered with a first code at `/api/auth/2fa/verify` to finish
+/// setting it up and sign in. Following the link again returns the same
+/// secret.
+#[utoipa::path(
+    get,
+    path = "/api/auth/2fa/enroll/{token}",
+    tags = ["Authentication"],
+    summary = "Set up two-factor authentication from emailed link",
+    responses(
+        (status = ACCEPTED, description = "Authenticator secret and challenge", body = TwoFactorChallengeResponse),
+        (status = UNAUTHORIZED, description = "Invalid or expired link", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many attempts", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_enroll(
+    State(ctx): State<AppContext>,
+    ip: RemoteIP,
+    Path(token): Path<String>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let limits = &settings.rate_limits;
+    let key = format!("tokens:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(&ctx, limits, &key, limits.token_attempts_per_ip).await? {
+        return too_many_requests(wait);
+    }
+
+    let Ok(user) = users::Model::find_by_totp_enrollment_token(
+        &ctx.db,
+        &token,
+        settings.two_factor.enrollment_link_ttl_minutes,
+    )
+    .await
+    else {
+        return unauthorized("invalid token");
+    };
+
+    let user = user
+        .into_active_model()
+        .start_totp_enrollment(&ctx.db)
+        .await?;
+    let setup = TotpSetupResponse::new(&user, &settings.two_factor.issuer)
+        .ok_or_else(|| Error::string("the user has no authenticator secret"))?;
+
+    challenge(&ctx, &settings, &user, Some(setup)).await
+}
+
+/// Starts setting up two-factor authentication. The returned secret is added
+/// to an authenticator app and confirmed with a code at `/api/auth/2fa/enable`.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/setup",
+    tags = ["Authentication"],
+    summary = "Set up two-factor authentication",
+    responses(
+        (status = OK, description = "Authenticator secret", body = TotpSetupResponse),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = CONFLICT, description = "Two-factor authentication is already enabled", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_setup(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    if auth.user.totp_enabled_at.is_some() {
+        return conflict("Two-factor authentication is already enabled.");
+    }
+
+    let settings = Settings::from_context(&ctx)?;
+    let user = auth
+        .user
+        .into_active_model()
+        .start_totp_enrollment(&ctx.db)
+        .await?;
+    let setup = TotpSetupResponse::new(&user, &settings.two_factor.issuer)
+        .ok_or_else(|| Error::string("the user has no authenticator secret"))?;
+
+    format::json(setup)
+}
 
+/// Turns two-factor authentication on with a first code from the
+/// authenticator app, and returns recovery codes.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/enable",
+    tags = ["Authentication"],
+    summary = "Enable two-factor authentication",
+    responses(
+        (status = OK, description = "Two-factor authentication enabled", body = RecoveryCodesResponse),
+        (status = BAD_REQUEST, description = "Not set up or wrong code", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = CONFLICT, description = "Two-factor authentication is already enabled", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong codes", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_enable(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<TwoFactorCodeParams>,
+) -> Result<Response> {
+    if auth.user.totp_enabled_at.is_some() {
+        return conflict("Two-factor authentication is already enabled.");
+    }
+    if auth.user.totp_secret.is_none() {
+        return bad_request("Set up two-factor authentication first.");
+    }
+    match verify_second_factor(&ctx, &auth.user, &params.code, false).await? {
+        Verification::Valid => {}
+        Verification::Invalid => return bad_request("The code is not correct."),
+        Verification::Throttled(wait) => return too_many_requests(wait),
+    }
+
+    let user = auth.user.into_active_model().enable_totp(&ctx.db).await?;
+    let recovery_codes = user_recovery_codes::Model::regenerate(&ctx.db, user.id).await?;
+    AuthMailer::send_two_factor_enabled(&ctx, &user).await?;
+    tracing::info!(
+        pid = user.pid.to_string(),
+        "two-factor authentication enabled"
+    );
+
+    format::json(RecoveryCodesResponse { recovery_codes })
+}
+
+/// Turns two-factor authentication off. Staff can't when it is required for
+/// them.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/disable",
+    tags = ["Authentication"],
+    summary = "Disable two-factor authentication",
+    responses(
+        (status = OK, description = "Two-factor authentication disabled"),
+        (status = BAD_REQUEST, description = "Not enabled, or wrong password or code", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Required for staff", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong passwords or codes", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_disable(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<TwoFactorDisableParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    if auth.user.totp_enabled_at.is_none() {
+        return bad_request("Two-factor authentication is not enabled.");
+    }
+    if auth.user.is_staff() && settings.two_factor.required_for_staff {
+        return forbidden("Two-factor authentication is required for staff accounts.");
+    }
+    match verify_current_password(&ctx, &auth.user, &params.password).await? {
+        Verification::Valid => {}
+        Verification::Invalid => return bad_request("The password is not correct."),
+        Verification::Throttled(wait) => return too_many_requests(wait),
+    }
+    match verify_second_factor(&ctx, &auth.user, &params.code, true).await? {
+        Verification::Valid => {}
+        Verification::Invalid => return bad_request("The code is not correct."),
+        Verification::Throttled(wait) => return too_many_requests(wait),
+    }
+
+    let user = auth.user.into_active_model().disable_totp(&ctx.db).await?;
+    user_recovery_codes::Model::delete_all(&ctx.db, user.id).await?;
+    tracing::info!(
+        pid = user.pid.to_string(),
+        "two-factor authentication disabled"
+    );
+
+    format::json(())
+}
+
+/// Replaces the recovery codes with new ones, e.g. after some were used up.
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/recovery-codes",
+    tags = ["Authentication"],
+    summary = "Regenerate recovery codes",
+    responses(
+        (status = OK, description = "New recovery codes", body = RecoveryCodesResponse),
+        (status = BAD_REQUEST, description = "Not enabled or wrong code", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong codes", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn two_factor_recovery_codes(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<TwoFactorCodeParams>,
+) -> Result<Response> {
+    if auth.user.totp_enabled_at.is_none() {
+        return bad_request("Two-factor authentication is not enabled.");
+    }
+    match verify_second_factor(&ctx, &auth.user, &params.code, false).await? {
+        Verification::Valid => {}
+        Verification::Invalid => return bad_request("The code is not correct."),
+        Verification::Throttled(wait) => return too_many_requests(wait),
+    }
+
+    let recovery_codes = user_recovery_codes::Model::regenerate(&ctx.db, auth.user.id).await?;
+
+    format::json(RecoveryCodesResponse { recovery_codes })
+}
+
+/// Exchanges a refresh token for a new access token and refresh token. A
+/// refresh token that was already used signs out every session that descends
+/// from the same sign in, as it may have been stolen.
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    tags = ["Authentication"],
+    summary = "Refresh access token",
+    responses(
+        (status = OK, description = "Tokens refreshed", body = LoginResponse),
+        (status = UNAUTHORIZED, description = "Invalid, expired or reused refresh token", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn refresh(
+    State(ctx): State<AppContext>,
+    Json(params): Json<RefreshParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let (session, refresh_token) = match user_sessions::Model::refresh(
+        &ctx.db,
+        &params.refresh_token,
+        settings.sessions.refresh_token_ttl_days,
+    )
+    .await?
+    {
+        Refresh::Rotated(session, refresh_token) => (session, refresh_token),
+        Refresh::Reused(session) => {
+            tracing::warn!(
+                user_id = session.user_id,
+                family = session.family.to_string(),
+                "refresh token reused, revoked its session family"
+            );
+            return unauthorized("unauthorized!");
+        }
+        Refresh::Invalid => return unauthorized("unauthorized!"),
+    };
+
+    let user = users::Entity::find_by_id(session.user_id)
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(|| Error::Unauthorized("unauthorized!".to_string()))?;
+
+    let jwt_secret = ctx.config.get_jwt_config()?;
     let token = user
         .generate_jwt(&jwt_secret.secret, jwt_secret.expiration)
-        .or_else(|e| {
-            tracing::debug!(err = ?e, "could not create JWT token");
-            unauthorized("unauthorized!")
-        })?;
+        .or_else(|_| unauthorized("unauthorized!"))?;
+
+    format::json(LoginResponse::new(&user, &token, &refresh_token))
+}
+
+/// Signs out the session the refresh token belongs to. The access token
+/// stays valid until it expires.
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tags = ["Authentication"],
+    summary = "Log out",
+    responses(
+        (status = OK, description = "Logged out"),
+    )
+)]
+#[debug_handler]
+async fn logout(
+    State(ctx): State<AppContext>,
+    Json(params): Json<RefreshParams>,
+) -> Result<Response> {
+    user_sessions::Model::revoke_by_token(&ctx.db, &params.refresh_token).await?;
+
+    format::json(())
+}
+
+/// Unlocks an account that was locked after repeated failed logins, from the
+/// link emailed to its owner.
+#[utoipa::path(
+    get,
+    path = "/api/auth/unlock/{token}",
+    tags = ["Authentication"],
+    summary = "Unlock account",
+    responses(
+        (status = OK, description = "Account unlocked"),
+        (status = UNAUTHORIZED, description = "Invalid unlock token", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many attempts", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn unlock(
+    State(ctx): State<AppContext>,
+    ip: RemoteIP,
+    Path(token): Path<String>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let limits = &settings.rate_limits;
+    let key = format!("tokens:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(&ctx, limits, &key, limits.token_attempts_per_ip).await? {
+        return too_many_requests(wait);
+    }
 
-    format::json(LoginResponse::new(&user, &token))
+    let Ok(user) =
+        users::Model::find_by_unlock_token(&ctx.db, &token, settings.login.lockout_minutes).await
+    else {
+        return unauthorized("invalid token");
+    };
+
+    let user = user.into_active_model().unlock(&ctx.db).await?;
+    tracing::info!(pid = user.pid.to_string(), "account unlocked");
+
+    format::json(())
 }
 
 #[utoipa::path(
@@ -236,10 +1084,9 @@ async fn login(State(ctx): State<AppContext>, Json(params): Json<LoginParams>) -
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
     )
 )]
-#[debug_handler]
-async fn current(auth: auth::JWT, State(ctx): State<AppContext>) -> Result<Response> {
-    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
-    format::json(CurrentResponse::new(&user))
+#[debug_handler(state = AppContext)]
+async fn current(auth: auth::JWTWithUser<Model>) -> Result<Response> {
+    format::json(CurrentResponse::new(&auth.user))
 }
 
 #[utoipa::path(
@@ -258,12 +1105,190 @@ async fn update(
     Json(params): Json<UserUpdateParams>,
 ) -> Result<Response> {
     let mut user = auth.user.into_active_model();
-    user.name = Set(params.name);
+    if let Some(name) = params.name {
+        user.name = Set(name);
+    }
+    if let Some(price_alerts_enabled) = params.price_alerts_enabled {
+        user.price_alerts_enabled = Set(price_alerts_enabled);
+    }
     let user = user.update(&ctx.db).await?;
 
     format::json(CurrentResponse::new(&user))
 }
 
+async fn collect_export(db: &DatabaseConnection, user: &users::Model) -> Result<AccountExport> {
+    let orders = orders::Entity::find()
+        .filter(orders::Column::UserId.eq(user.id))
+        .order_by_asc(orders::Column::CreatedAt)
+        .find_with_related(order_items::Entity)
+        .all(db)
+        .await?;
+    // Collected first, a borrowing iterator held across the query keeps the
+    // handler from being usable as an axum handler
+    let variant_ids = orders
+        .iter()
+        .flat_map(|(_, items)| items.iter().map(|item| item.product_variant_id))
+        .collect::<Vec<_>>();
+    let variants = product_variants::Model::find_many_with_product(db, variant_ids).await?;
+
+    let wishlists = wishlist_lists::Entity::find()
+        .filter(wishlist_lists::Column::UserId.eq(user.id))
+        .order_by_asc(wishlist_lists::Column::Id)
+        .find_with_related(wishlists::Entity)
+        .all(db)
+        .await?;
+
+    Ok(AccountExport {
+        exported_at: chrono::Utc::now().into(),
+        profile: ProfileExport::new(user),
+        identities: user_identities::Model::list_for_user(db, user.id)
+            .await?
+            .iter()
+            .map(IdentityResponse::new)
+            .collect(),
+        orders: orders
+            .into_iter()
+            .map(|(order, items)| Order::create(order, items, &variants))
+            .collect(),
+        reviews: reviews::Entity::find()
+            .filter(reviews::Column::UserId.eq(user.id))
+            .order_by_asc(reviews::Column::Id)
+            .all(db)
+            .await?,
+        wishlists: wishlists
+            .into_iter()
+            .map(|(list, items)| WishlistExport { list, items })
+            .collect(),
+        cart: cart_items::Entity::find()
+            .filter(cart_items::Column::UserId.eq(user.id))
+            .order_by_asc(cart_items::Column::Id)
+            .all(db)
+            .await?,
+    })
+}
+
+/// Downloads everything stored about the current user as a JSON file: their
+/// profile, linked sign-in providers, orders, reviews, wishlists and cart.
+#[utoipa::path(
+    get,
+    path = "/api/auth/current/export",
+    tags = ["Authentication"],
+    summary = "Export account data",
+    responses(
+        (status = OK, description = "Account data", body = AccountExport),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn export(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let export = collect_export(&ctx.db, &auth.user).await?;
+
+    format::render()
+        .header(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"account-export.json\"",
+        )
+        .json(export)
+}
+
+/// Emails a link to confirm deleting the current user's account with,
+/// instead of their password. Accounts registered through an identity
+/// provider have no password their owner knows.
+#[utoipa::path(
+    post,
+    path = "/api/auth/current/deletion-link",
+    tags = ["Authentication"],
+    summary = "Send account deletion link",
+    responses(
+        (status = OK, description = "Deletion link sent"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many emails requested", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn deletion_link(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    ip: RemoteIP,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    if let Some(wait) = throttle_emails(&ctx, &settings.rate_limits, ip, &auth.user.email).await? {
+        return too_many_requests(wait);
+    }
+
+    let (user, token) = auth
+        .user
+        .into_active_model()
+        .set_deletion_sent(&ctx.db)
+        .await?;
+    AuthMailer::send_deletion_link(
+        &ctx,
+        &user,
+        &token,
+        settings.account_deletion.link_ttl_minutes,
+    )
+    .await?;
+
+    format::json(())
+}
+
+/// Deletes the current user's account, confirmed with their password or the
+/// token from a link sent by `/api/auth/current/deletion-link` and, when
+/// two-factor authentication is on, a code. Orders stay on record without the
+/// customer and their shipping address; everything else about the user is
+/// deleted and all their sessions end.
+#[utoipa::path(
+    delete,
+    path = "/api/auth/current",
+    tags = ["Authentication"],
+    summary = "Delete account",
+    responses(
+        (status = OK, description = "Account deleted"),
+        (status = BAD_REQUEST, description = "Wrong password, link or code", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong passwords or codes", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn delete_account(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<DeleteAccountParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    match (&params.password, &params.token) {
+        (Some(password), _) => match verify_current_password(&ctx, &auth.user, password).await? {
+            Verification::Valid => {}
+            Verification::Invalid => return bad_request("The password is not correct."),
+            Verification::Throttled(wait) => return too_many_requests(wait),
+        },
+        (None, Some(token)) => {
+            let ttl_minutes = settings.account_deletion.link_ttl_minutes;
+            if !auth.user.deletion_link_valid(token, ttl_minutes) {
+                return bad_request("The link is not valid or expired, request a new one.");
+            }
+        }
+        (None, None) => {
+            return bad_request("Confirm with your password or the emailed link.");
+        }
+    }
+    if auth.user.totp_enabled_at.is_some() {
+        let code = params.code.unwrap_or_default();
+        match verify_second_factor(&ctx, &auth.user, &code, true).await? {
+            Verification::Valid => {}
+            Verification::Invalid => return bad_request("The code is not correct."),
+            Verification::Throttled(wait) => return too_many_requests(wait),
+        }
+    }
+
+    auth.user.delete_account(&ctx.db).await?;
+
+    format::json(())
+}
+
 /// Magic link authentication provides a secure and passwordless way to log in to the application.
 ///
 /// # Flow
@@ -285,21 +1310,27 @@ async fn update(
     summary = "Get magic login link",
     responses(
         (status = OK, description = "Magic link sent"),
-        (status = BAD_REQUEST, description = "Invalid request"),
+        (status = BAD_REQUEST, description = "Email domain not allowed", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many emails requested", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 async fn magic_link(
     State(ctx): State<AppContext>,
+    ip: RemoteIP,
     Json(params): Json<MagicLinkParams>,
 ) -> Result<Response> {
-    let email_regex = get_allow_email_domain_re();
-    if !email_regex.is_match(&params.email) {
+    let settings = Settings::from_context(&ctx)?;
+    if let Err(err) = registration::check_email(&settings.registration, &params.email) {
         tracing::debug!(
             email = params.email,
-            "The provided email is invalid or does not match the allowed domains"
+            "The provided email does not match the allowed domains"
         );
-        return bad_request("invalid request");
+        return Err(err);
+    }
+
+    if let Some(wait) = throttle_emails(&ctx, &settings.rate_limits, ip, &params.email).await? {
+        return too_many_requests(wait);
     }
 
     let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
@@ -309,8 +1340,8 @@ async fn magic_link(
         return format::empty_json();
     };
 
-    let user = user.into_active_model().create_magic_link(&ctx.db).await?;
-    AuthMailer::send_magic_link(&ctx, &user).await?;
+    let (user, token) = user.into_active_model().create_magic_link(&ctx.db).await?;
+    AuthMailer::send_magic_link(&ctx, &user, &token).await?;
 
     format::empty_json()
 }
@@ -323,12 +1354,15 @@ async fn magic_link(
     summary = "Log in with magic link",
     responses(
         (status = OK, description = "Login successful", body = LoginResponse),
+        (status = ACCEPTED, description = "A code from the authenticator app is needed", body = TwoFactorChallengeResponse),
+        (status = FORBIDDEN, description = "Email not verified, or two-factor authentication has to be set up first", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Invalid magic link")
     )
 )]
 async fn magic_link_verify(
     Path(token): Path<String>,
     State(ctx): State<AppContext>,
+    guest: GuestCart,
 ) -> Result<Response> {
     let Ok(user) = users::Model::find_by_magic_token(&ctx.db, &token).await else {
         // we don't want to expose our users email. if the email is invalid we still
@@ -337,30 +1371,40 @@ async fn magic_link_verify(
     };
 
     let user = user.into_active_model().clear_magic_link(&ctx.db).await?;
+    // Following the emailed link proves the user owns the address
+    let user = if user.email_verified_at.is_none() {
+        user.into_active_model().verified(&ctx.db).await?
+    } else {
+        user
+    };
 
-    let jwt_secret = ctx.config.get_jwt_config()?;
-
-    let token = user
-        .generate_jwt(&jwt_secret.secret, jwt_secret.expiration)
-        .or_else(|_| unauthorized("unauthorized!"))?;
-
-    format::json(LoginResponse::new(&user, &token))
+    complete_login(&ctx, user, guest).await
 }
 
+/// Sends a new verification link to a user who hasn't verified their email
+/// yet, at most once per cooldown. It succeeds either way, so it doesn't reveal
+/// which emails have an account.
 #[utoipa::path(
     post,
     path = "/api/auth/resend-verification-mail",
     tags = ["Authentication"],
     summary = "Resend verification email",
     responses(
-        (status = OK, description = "Email sent")
+        (status = OK, description = "Email sent"),
+        (status = TOO_MANY_REQUESTS, description = "Too many emails requested", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 async fn resend_verification_email(
     State(ctx): State<AppContext>,
+    ip: RemoteIP,
     Json(params): Json<ResendVerificationParams>,
 ) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    if let Some(wait) = throttle_emails(&ctx, &settings.rate_limits, ip, &params.email).await? {
+        return too_many_requests(wait);
+    }
+
     let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
         tracing::info!(
             email = params.email,
@@ -377,12 +1421,20 @@ async fn resend_verification_email(
         return format::json(());
     }
 
-    let user = user
+    if !user.verification_resend_allowed(settings.email_verification.resend_cooldown_minutes) {
+        tracing::info!(
+            pid = user.pid.to_string(),
+            "Verification email sent recently, skipping resend"
+        );
+        return format::json(());
+    }
+
+    let (user, verify_token) = user
         .into_active_model()
         .set_email_verification_sent(&ctx.db)
         .await?;
 
-    AuthMailer::send_welcome(&ctx, &user).await?;
+    AuthMailer::send_welcome(&ctx, &user, &verify_token).await?;
     tracing::info!(pid = user.pid.to_string(), "Verification email re-sent");
 
     format::json(())
@@ -395,8 +1447,9 @@ async fn resend_verification_email(
     summary = "Change password",
     responses(
         (status = OK, description = "Password changed"),
-        (status = BAD_REQUEST, description = "Old password is not correct", body = ErrorDetail),
+        (status = BAD_REQUEST, description = "Old password is not correct or new password too weak", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = TOO_MANY_REQUESTS, description = "Too many wrong passwords", body = ErrorDetail),
     )
 )]
 #[debug_handler]
@@ -405,32 +1458,291 @@ async fn change_password(
     State(ctx): State<AppContext>,
     Json(params): Json<ChangePasswordParams>,
 ) -> Result<Response> {
-    if !auth.user.verify_password(&params.old_password) {
-        return bad_request("Old password is not correct.");
+    match verify_current_password(&ctx, &auth.user, &params.old_password).await? {
+        Verification::Valid => {}
+        Verification::Invalid => return bad_request("Old password is not correct."),
+        Verification::Throttled(wait) => return too_many_requests(wait),
     }
+    let settings = Settings::from_context(&ctx)?;
+    registration::check_password(&settings.registration, "new_password", &params.new_password)?;
 
-    auth.user
+    let user = auth
+        .user
         .into_active_model()
         .reset_password(&ctx.db, &params.new_password)
         .await?;
+    user_sessions::Model::revoke_all(&ctx.db, user.id).await?;
+    audit_account(&ctx, &user, "password_change", true).await?;
 
     format::empty()
 }
 
+/// Starts signing in with an identity provider such as Google. Send the user
+/// to the returned URL; the provider sends them back to the configured
+/// redirect URI, which posts the `code` and `state` it receives to
+/// `/api/auth/oauth/{provider}/callback`.
+#[utoipa::path(
+    post,
+    path = "/api/auth/oauth/{provider}/authorize",
+    tags = ["Authentication"],
+    summary = "Start signing in with an identity provider",
+    responses(
+        (status = OK, description = "Where to send the user", body = OAuthAuthorizationResponse),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ErrorDetail),
+        (status = BAD_GATEWAY, description = "The identity provider could not be reached", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn oauth_authorize(
+    State(ctx): State<AppContext>,
+    Path(provider): Path<String>,
+) -> Result<Response> {
+    start_oauth(&ctx, &provider, None).await
+}
+
+/// Signs in with the account an identity provider vouched for. A provider
+/// account seen before signs in its user. Otherwise it is linked to the user
+/// with the same email, provided both the provider and the user verified it,
+/// or a new verified user is registered, if the email domain passes the
+/// registration policy.
+#[utoipa::path(
+    post,
+    path = "/api/auth/oauth/{provider}/callback",
+    tags = ["Authentication"],
+    summary = "Sign in with an identity provider",
+    responses(
+        (status = OK, description = "Login successful", body = LoginResponse),
+        (status = ACCEPTED, description = "A code from the authenticator app is needed", body = TwoFactorChallengeResponse),
+        (status = FORBIDDEN, description = "Email not verified, or two-factor authentication has to be set up first", body = ErrorDetail),
+        (status = BAD_REQUEST, description = "Email domain not allowed", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Invalid state or code, or no verified email", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ErrorDetail),
+        (status = CONFLICT, description = "An unverified account uses the email", body = ErrorDetail),
+        (status = BAD_GATEWAY, description = "The identity provider could not be reached", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn oauth_callback(
+    State(ctx): State<AppContext>,
+    Path(provider): Path<String>,
+    guest: GuestCart,
+    Json(params): Json<OAuthCallbackParams>,
+) -> Result<Response> {
+    let Some(identity) = finish_oauth(&ctx, &provider, None, &params).await? else {
+        return unauthorized("unauthorized!");
+    };
+
+    if let Some(linked) =
+        user_identities::Model::find_by_subject(&ctx.db, &provider, &identity.subject).await?
+    {
+        let user = users::Entity::find_by_id(linked.user_id)
+            .one(&ctx.db)
+            .await?
+            .ok_or_else(|| Error::Unauthorized("unauthorized!".to_string()))?;
+        return complete_login(&ctx, user, guest).await;
+    }
+
+    let Some(email) = identity
+        .email
+        .as_deref()
+        .filter(|_| identity.email_verified)
+    else {
+        return unauthorized("The identity provider did not confirm your email address.");
+    };
+    let user = match users::Model::find_by_email(&ctx.db, email).await {
+        // Anyone can register with an email they don't own, so only a
+        // verified account is taken to be the same person
+        Ok(user) if user.email_verified_at.is_none() => {
+            return conflict(
+                "An account with this email exists. Verify it or sign in with your password to link this provider.",
+            );
+        }
+        Ok(user) => user,
+        Err(ModelError::EntityNotFound) => {
+            let settings = Settings::from_context(&ctx)?;
+            registration::check_email(&settings.registration, email)?;
+
+            let name = identity
+                .name
+                .as_deref()
+                .filter(|name| name.trim().chars().count() >= 2)
+                .unwrap_or(email);
+            let user = users::Model::create_from_identity(&ctx.db, email, name).await?;
+            tracing::info!(
+                pid = user.pid.to_string(),
+                provider,
+                "user registered with identity provider"
+            );
+            user
+        }
+        Err(err) => return Err(err.into()),
+    };
+    user_identities::Model::link(&ctx.db, user.id, &provider, &identity.subject, Some(email))
+        .await?;
+
+    complete_login(&ctx, user, guest).await
+}
+
+/// Lists the identity provider accounts linked to the current user.
+#[utoipa::path(
+    get,
+    path = "/api/auth/identities",
+    tags = ["Authentication"],
+    summary = "List linked identities",
+    responses(
+        (status = OK, description = "Linked identities", body = [IdentityResponse]),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn identities(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let identities = user_identities::Model::list_for_user(&ctx.db, auth.user.id).await?;
+
+    format::json(
+        identities
+            .iter()
+            .map(IdentityResponse::new)
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Starts linking an identity provider account to the current user. Works
+/// like signing in, except the redirect URI posts the `code` and `state` to
+/// `/api/auth/oauth/{provider}/link/callback` with the user's token.
+#[utoipa::path(
+    post,
+    path = "/api/auth/oauth/{provider}/link",
+    tags = ["Authentication"],
+    summary = "Start linking an identity provider",
+    responses(
+        (status = OK, description = "Where to send the user", body = OAuthAuthorizationResponse),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ErrorDetail),
+        (status = BAD_GATEWAY, description = "The identity provider could not be reached", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn identity_link(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Path(provider): Path<String>,
+) -> Result<Response> {
+    start_oauth(&ctx, &provider, Some(auth.user.id)).await
+}
+
+/// Links the identity provider account the user signed in to. The state must
+/// have been started by the same user, so nobody can link their account to
+/// someone else's.
+#[utoipa::path(
+    post,
+    path = "/api/auth/oauth/{provider}/link/callback",
+    tags = ["Authentication"],
+    summary = "Link an identity provider",
+    responses(
+        (status = OK, description = "Identity linked", body = IdentityResponse),
+        (status = UNAUTHORIZED, description = "Not authenticated, or invalid state or code", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Unknown identity provider", body = ErrorDetail),
+        (status = CONFLICT, description = "The account is linked to another user", body = ErrorDetail),
+        (status = BAD_GATEWAY, description = "The identity provider could not be reached", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn identity_link_callback(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Path(provider): Path<String>,
+    Json(params): Json<OAuthCallbackParams>,
+) -> Result<Response> {
+    let Some(identity) = finish_oauth(&ctx, &provider, Some(auth.user.id), &params).await? else {
+        return unauthorized("unauthorized!");
+    };
+
+    let linked =
+        match user_identities::Model::find_by_subject(&ctx.db, &provider, &identity.subject).await?
+        {
+            Some(linked) if linked.user_id != auth.user.id => {
+                return conflict("This account is linked to another user.");
+            }
+            Some(linked) => linked,
+            None => {
+                user_identities::Model::link(
+                    &ctx.db,
+                    auth.user.id,
+                    &provider,
+                    &identity.subject,
+                    identity.email.as_deref(),
+                )
+                .await?
+            }
+        };
+
+    format::json(IdentityResponse::new(&linked))
+}
+
+/// Unlinks an identity provider account from the current user, who can still
+/// sign in with their password or a magic link.
+#[utoipa::path(
+    delete,
+    path = "/api/auth/identities/{id}",
+    tags = ["Authentication"],
+    summary = "Unlink an identity provider",
+    responses(
+        (status = OK, description = "Identity unlinked"),
+        (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = NOT_FOUND, description = "No such identity", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+async fn identity_unlink(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+    Path(id): Path<i32>,
+) -> Result<Response> {
+    if !user_identities::Model::unlink(&ctx.db, auth.user.id, id).await? {
+        return Err(Error::NotFound);
+    }
+
+    format::json(())
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("/api/auth")
         .add("/register", post(register))
         .add("/verify/{token}", get(verify))
         .add("/login", post(login))
+        .add("/2fa/verify", post(two_factor_verify))
+        .add("/2fa/enroll/{token}", get(two_factor_enroll))
+        .add("/2fa/setup", post(two_factor_setup))
+        .add("/2fa/enable", post(two_factor_enable))
+        .add("/2fa/disable", post(two_factor_disable))
+        .add("/2fa/recovery-codes", post(two_factor_recovery_codes))
+        .add("/refresh", post(refresh))
+        .add("/logout", post(logout))
+        .add("/unlock/{token}", get(unlock))
         .add("/forgot", post(forgot))
         .add("/reset", post(reset))
         .add("/current", get(current))
         .add("/current", patch(update))
+        .add("/current", delete(delete_account))
+        .add("/current/deletion-link", post(deletion_link))
+        .add("/current/export", get(export))
         .add("/magic-link", post(magic_link))
         .add("/magic-link/{token}", get(magic_link_verify))
         .add("/resend-verification-mail", post(resend_verification_email))
         .add("/change-password", post(change_password))
+        .add("/oauth/{provider}/authorize", post(oauth_authorize))
+        .add("/oauth/{provider}/callback", post(oauth_callback))
+        .add("/oauth/{provider}/link", post(identity_link))
+        .add(
+            "/oauth/{provider}/link/callback",
+            post(identity_link_callback),
+        )
+        .add("/identities", get(identities))
+        .add("/identities/{id}", delete(identity_unlink))
 }
 
 pub fn api_routes() -> OpenApiRouter<AppContext> {
@@ -438,11 +1750,28 @@ pub fn api_routes() -> OpenApiRouter<AppContext> {
         .routes(routes!(register))
         .routes(routes!(verify))
         .routes(routes!(login))
+        .routes(routes!(two_factor_verify))
+        .routes(routes!(two_factor_enroll))
+        .routes(routes!(two_factor_setup))
+        .routes(routes!(two_factor_enable))
+        .routes(routes!(two_factor_disable))
+        .routes(routes!(two_factor_recovery_codes))
+        .routes(routes!(refresh))
+        .routes(routes!(logout))
+        .routes(routes!(unlock))
         .routes(routes!(forgot))
         .routes(routes!(reset))
-        .routes(routes!(current, update))
+        .routes(routes!(current, update, delete_account))
+        .routes(routes!(deletion_link))
+        .routes(routes!(export))
         .routes(routes!(magic_link))
         .routes(routes!(magic_link_verify))
         .routes(routes!(resend_verification_email))
         .routes(routes!(change_password))
+        .routes(routes!(oauth_authorize))
+        .routes(routes!(oauth_callback))
+        .routes(routes!(identity_link))
+        .routes(routes!(identity_link_callback))
+        .routes(routes!(identities))
+        .routes(routes!(identity_unlink))
 }
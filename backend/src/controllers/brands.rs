@@ -2,27 +2,39 @@
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
 use loco_openapi::prelude::{routes, OpenApiRouter};
-use loco_rs::prelude::*;
+use loco_rs::{model::query::PaginationQuery, prelude::*};
+use sea_orm::{PaginatorTrait, QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::{forbidden, ErrorDetail},
+    controllers::{api_error, conflict, ErrorDetail},
     models::{
-        _entities::brands::{ActiveModel, Entity, Model},
-        users,
+        _entities::{
+            brands::{ActiveModel, Entity, Model},
+            products,
+        },
+        audit_events, categories,
     },
+    permissions::{require, Authorized},
+    views::{pagination::PageResponse, products::Product},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct CreateParams {
     pub name: String,
+    pub slug: String,
     pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website_url: Option<String>,
 }
 
 impl CreateParams {
     fn update(&self, item: &mut ActiveModel) {
         item.name = Set(self.name.clone());
+        item.slug = Set(self.slug.clone());
         item.description = Set(self.description.clone());
+        item.logo_url = Set(self.logo_url.clone());
+        item.website_url = Set(self.website_url.clone());
     }
 }
 
@@ -31,12 +43,29 @@ pub struct UpdateParams {
     #[serde(default)]
     pub name: Option<String>,
 
+    #[serde(default)]
+    pub slug: Option<String>,
+
     #[serde(
         default,
         skip_serializing_if = "Option::is_none",
         with = "::serde_with::rust::double_option"
     )]
     pub description: Option<Option<String>>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    pub logo_url: Option<Option<String>>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    pub website_url: Option<Option<String>>,
 }
 
 impl UpdateParams {
@@ -45,14 +74,34 @@ impl UpdateParams {
             item.name = Set(name.clone());
         }
 
+        if let Some(ref slug) = self.slug {
+            item.slug = Set(slug.clone());
+        }
+
         if let Some(ref description) = self.description {
             item.description = Set(description.clone());
         }
+
+        if let Some(ref logo_url) = self.logo_url {
+            item.logo_url = Set(logo_url.clone());
+        }
+
+        if let Some(ref website_url) = self.website_url {
+            item.website_url = Set(website_url.clone());
+        }
     }
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeleteQuery {
+    /// Delete the brand even if products still reference it. Those products
+    /// are left without a brand.
+    #[serde(default)]
+    pub force: bool,
+}
+
+async fn load_item(ctx: &AppContext, id: String) -> Result<Model> {
+    let item = Model::get_by_id_or_slug(&ctx.db, id).await?;
     item.ok_or_else(|| Error::NotFound)
 }
 
@@ -79,23 +128,24 @@ pub async fn list(State(ctx): State<AppContext>) -> Result<Response> {
         (status = OK, description = "Created a brand", body = Model),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permissions", body = ErrorDetail),
+        (status = CONFLICT, description = "A brand with this slug already exists", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn add(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::BrandsWrite>,
     State(ctx): State<AppContext>,
     Json(params): Json<CreateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
     let mut item = ActiveModel {
         ..Default::default()
     };
     params.update(&mut item);
-    format::json(item.insert(&ctx.db).await?)
+    let txn = ctx.db.begin().await?;
+    let item = item.insert(&txn).await.map_err(api_error)?;
+    audit_events::Model::created(&txn, &auth.user, "brand", item.id, &item).await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 #[utoipa::path(
@@ -108,24 +158,26 @@ pub async fn add(
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permissions", body = ErrorDetail),
         (status = NOT_FOUND, description = "Brand not found", body = ErrorDetail),
+        (status = CONFLICT, description = "A brand with this slug already exists", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
-    Path(id): Path<i32>,
+    auth: Authorized<require::BrandsWrite>,
+    Path(id): Path<String>,
     State(ctx): State<AppContext>,
     Json(params): Json<UpdateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
-    let item = load_item(&ctx, id).await?;
-    let mut item = item.into_active_model();
+    let before = load_item(&ctx, id).await?;
+    let mut item = before.clone().into_active_model();
 
     params.update(&mut item);
-    format::json(item.update(&ctx.db).await?)
+    let txn = ctx.db.begin().await?;
+    let item = item.update(&txn).await.map_err(api_error)?;
+    audit_events::Model::changed(&txn, &auth.user, "update", "brand", item.id, &before, &item)
+        .await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 #[utoipa::path(
@@ -138,19 +190,42 @@ pub async fn update(
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permissions", body = ErrorDetail),
         (status = NOT_FOUND, description = "Brand not found", body = ErrorDetail),
+        (status = CONFLICT, description = "Brand still has products", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn remove(
-    auth: auth::JWTWithUser<users::Model>,
-    Path(id): Path<i32>,
+    auth: Authorized<require::BrandsWrite>,
+    Path(id): Path<String>,
     State(ctx): State<AppContext>,
+    Query(params): Query<DeleteQuery>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
+    let item = load_item(&ctx, id).await?;
+
+    let txn = ctx.db.begin().await?;
+    // Locking the brand holds off products being added to it until the
+    // delete is done
+    let item = Entity::find_by_id(item.id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    if !params.force {
+        let product_count = products::Entity::find()
+            .filter(products::Column::BrandId.eq(item.id))
+            .count(&txn)
+            .await?;
+
+        if product_count > 0 {
+            return conflict(format!(
+                "Brand still has {product_count} products. Pass `force=true` to delete it anyway."
+            ));
+        }
     }
 
-    load_item(&ctx, id).await?.delete(&ctx.db).await?;
+    audit_events::Model::deleted(&txn, &auth.user, "brand", item.id, &item).await?;
+    item.delete(&txn).await?;
+    txn.commit().await?;
     format::empty()
 }
 
@@ -165,10 +240,52 @@ pub async fn remove(
     )
 )]
 #[debug_handler]
-pub async fn get_one(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+pub async fn get_one(Path(id): Path<String>, State(ctx): State<AppContext>) -> Result<Response> {
     format::json(load_item(&ctx, id).await?)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/brands/{id}/products",
+    tags = ["Brands"],
+    summary = "List products of a brand",
+    responses(
+        (status = OK, description = "Products", body = PageResponse<Product>),
+        (status = NOT_FOUND, description = "Brand not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn list_products(
+    Path(id): Path<String>,
+    State(ctx): State<AppContext>,
+    Query(pagination): Query<PaginationQuery>,
+) -> Result<Response> {
+    let brand = load_item(&ctx, id).await?;
+    let paginator = products::Entity::find()
+        .filter(products::Column::BrandId.eq(brand.id))
+        .find_also_related(categories::Entity)
+        .order_by_desc(products::Column::UpdatedAt)
+        .order_by_asc(products::Column::Id)
+        .paginate(&ctx.db, pagination.page_size);
+    let counts = paginator.num_items_and_pages().await?;
+    let products = paginator
+        .fetch_page(pagination.page - 1)
+        .await?
+        .into_iter()
+        .map(|(product, category)| Product {
+            product,
+            brand: Some(brand.clone()),
+            category,
+            variants: None,
+        })
+        .collect::<Vec<_>>();
+
+    format::json(PageResponse {
+        items: products,
+        counts: counts.into(),
+    })
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("api/brands/")
@@ -177,6 +294,7 @@ pub fn routes() -> Routes {
         .add("{id}", get(get_one))
         .add("{id}", delete(remove))
         .add("{id}", patch(update))
+        .add("{id}/products", get(list_products))
 }
 
 pub fn api_routes() -> OpenApiRouter<AppContext> {
@@ -186,4 +304,5 @@ pub fn api_routes() -> OpenApiRouter<AppContext> {
         .routes(routes!(get_one))
         .routes(routes!(remove))
         .routes(routes!(update))
+        .routes(routes!(list_products))
 }
@@ -1,20 +1,145 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
 use loco_openapi::prelude::{routes, OpenApiRouter};
 use loco_rs::prelude::*;
+use sea_orm::{sea_query::SimpleExpr, QueryOrder};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::ErrorDetail,
+    controllers::{
+        api_error,
+        auth::{client_ip, throttle},
+        bad_request, conflict, too_many_requests, ErrorDetail,
+    },
     models::{
         _entities::cart_items::{Column, Entity},
-        cart_items::ActiveModel,
-        product_variants, products, users,
+        cart_items::{self, ActiveModel},
+        guest_carts, product_variants, products, users,
     },
-    views::cart_items::CartItem,
+    settings::{CartSettings, Settings},
+    views::cart_items::{CartItem, CartLine, CartNotice, CartResponse, GuestCartResponse},
 };
 
+/// Checks that a cart line may hold `quantity` units of the variant.
+fn check_quantity(
+    quantity: i32,
+    variant: &product_variants::Model,
+    settings: &CartSettings,
+) -> Result<()> {
+    if quantity < 1 {
+        return bad_request("Quantity must be at least 1");
+    }
+    if quantity > settings.max_quantity_per_line {
+        return bad_request(format!(
+            "At most {} of an item can be in the cart",
+            settings.max_quantity_per_line
+        ));
+    }
+    if quantity > variant.stock {
+        return conflict(if variant.stock > 0 {
+            format!("Only {} left in stock", variant.stock)
+        } else {
+            "This item is out of stock".to_string()
+        });
+    }
+
+    Ok(())
+}
+
+/// Smallest difference between two prices that is reported to the shopper.
+const PRICE_TOLERANCE: f64 = 0.005;
+
+/// Header carrying the cart token of an anonymous shopper.
+pub const CART_TOKEN_HEADER: &str = "x-cart-token";
+/// Cookie carrying the cart token of an anonymous shopper.
+pub const CART_TOKEN_COOKIE: &str = "cart_token";
+
+/// The guest cart identified by the request's cart token, if it sent a valid
+/// one. Never rejects the request.
+pub struct GuestCart(pub Option<guest_carts::Model>);
+
+impl<S> FromRequestParts<S> for GuestCart
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let ctx = AppContext::from_ref(state);
+        let token = parts
+            .headers
+            .get(CART_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+            .or_else(|| auth::extract_token_from_cookie(CART_TOKEN_COOKIE, parts).ok());
+        let Some(token) = token else {
+            return Ok(Self(None));
+        };
+
+        let jwt_secret = ctx.config.get_jwt_config()?;
+        let cart = guest_carts::Model::find_by_token(&ctx.db, &jwt_secret.secret, &token).await?;
+
+        Ok(Self(cart))
+    }
+}
+
+/// Whose cart a request works on: the logged in user's, or else the guest
+/// cart of an anonymous shopper.
+pub enum CartOwner {
+    User(Box<users::Model>),
+    Guest(guest_carts::Model),
+}
+
+impl CartOwner {
+    fn filter(&self) -> SimpleExpr {
+        match self {
+            Self::User(user) => Column::UserId.eq(user.id),
+            Self::Guest(cart) => Column::GuestCartId.eq(cart.id),
+        }
+    }
+
+    /// The column of cart lines naming their owner.
+    const fn column(&self) -> Column {
+        match self {
+            Self::User(_) => Column::UserId,
+            Self::Guest(_) => Column::GuestCartId,
+        }
+    }
+
+    const fn assign(&self, item: &mut ActiveModel) {
+        match self {
+            Self::User(user) => item.user_id = Set(Some(user.id)),
+            Self::Guest(cart) => item.guest_cart_id = Set(Some(cart.id)),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for CartOwner
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let err = match auth::JWTWithUser::<users::Model>::from_request_parts(parts, state).await {
+            Ok(auth) => return Ok(Self::User(Box::new(auth.user))),
+            Err(err) => err,
+        };
+
+        match GuestCart::from_request_parts(parts, state).await? {
+            GuestCart(Some(cart)) => Ok(Self::Guest(cart)),
+            GuestCart(None) => Err(err),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct CartItemCreateParams {
     product_variant_id: i32,
@@ -39,38 +164,117 @@ impl CartItemUpdateParams {
     }
 }
 
+/// Revalidates a cart line against the current product and stock, lowering
+/// its quantity when less is in stock or allowed per line than the shopper
+/// asked for.
+async fn check_line(
+    ctx: &AppContext,
+    settings: &CartSettings,
+    cart_item: cart_items::Model,
+    product_variant: product_variants::Model,
+    product: products::Model,
+) -> Result<CartLine> {
+    let mut cart_item = cart_item;
+    let mut notices = Vec::new();
+    let quantity = cart_item.quantity.unwrap_or(0);
+
+    if !product.is_active {
+        notices.push(CartNotice::ProductInactive);
+    } else if product_variant.stock < 1 {
+        notices.push(CartNotice::OutOfStock);
+    } else if quantity > cart_items::max_quantity(&product_variant, settings) {
+        let available = cart_items::max_quantity(&product_variant, settings);
+        let updated_at = cart_item.updated_at;
+        let mut item = cart_item.into_active_model();
+        item.quantity = Set(Some(available));
+        // Reading the cart isn't activity, so keep the time the shopper last
+        // changed it; cart reminders are keyed on it
+        item.updated_at = Set(updated_at);
+        cart_item = item.update(&ctx.db).await?;
+        notices.push(CartNotice::QuantityReduced {
+            requested: quantity,
+            available,
+        });
+    }
+
+    let current_price = product.effective_price();
+    if let Some(unit_price) = cart_item.unit_price {
+        if (unit_price - current_price).abs() >= PRICE_TOLERANCE {
+            notices.push(CartNotice::PriceChanged {
+                old_price: unit_price,
+                new_price: current_price,
+            });
+        }
+    }
+
+    let orderable = product.is_active && product_variant.stock > 0;
+    let line_total = if orderable {
+        current_price * f64::from(cart_item.quantity.unwrap_or(0))
+    } else {
+        0.0
+    };
+
+    Ok(CartLine {
+        item: CartItem {
+            cart_item,
+            product: Some(product),
+            product_variant: Some(product_variant),
+        },
+        current_price,
+        line_total,
+        notices,
+    })
+}
+
+/// Returns the cart with notices about anything that changed since items were
+/// added, like prices or stock, and the subtotal at current prices.
 #[utoipa::path(
     get,
     path = "/api/cart",
     tags = ["Cart"],
     summary = "Get cart items",
     responses(
-        (status = OK, description = "Retrieved cart items", body = Vec<CartItem>),
+        (status = OK, description = "Retrieved cart items", body = CartResponse),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
     )
 )]
 #[debug_handler]
-pub async fn list(
-    auth: auth::JWTWithUser<users::Model>,
-    State(ctx): State<AppContext>,
-) -> Result<Response> {
-    let result = Entity::find()
-        .filter(Column::UserId.eq(auth.user.id))
+pub async fn list(owner: CartOwner, State(ctx): State<AppContext>) -> Result<Response> {
+    let rows = Entity::find()
+        .filter(owner.filter())
         .find_also_related(product_variants::Entity)
         .and_also_related(products::Entity)
+        .order_by_asc(Column::CreatedAt)
+        .order_by_asc(Column::Id)
         .all(&ctx.db)
-        .await?
-        .into_iter()
-        .map(|(cart_item, product_variant, product)| CartItem {
-            cart_item,
-            product,
-            product_variant,
-        })
-        .collect::<Vec<_>>();
+        .await?;
 
-    format::json(result)
+    let settings = Settings::from_context(&ctx)?;
+    let mut items = Vec::with_capacity(rows.len());
+    for (cart_item, product_variant, product) in rows {
+        if let (Some(product_variant), Some(product)) = (product_variant, product) {
+            items.push(
+                check_line(&ctx, &settings.carts, cart_item, product_variant, product).await?,
+            );
+        }
+    }
+
+    let orderable = items.iter().filter(|line| line.line_total > 0.0);
+    let item_count = orderable
+        .clone()
+        .map(|line| line.item.cart_item.quantity.unwrap_or(0))
+        .sum();
+    let subtotal = orderable.map(|line| line.line_total).sum();
+
+    format::json(CartResponse {
+        items,
+        item_count,
+        subtotal,
+    })
 }
 
+/// Adds a variant to the cart. Adding a variant that is already in the cart
+/// increases its quantity.
 #[utoipa::path(
     post,
     path = "/api/cart",
@@ -78,13 +282,15 @@ pub async fn list(
     summary = "Add item to cart",
     responses(
         (status = OK, description = "Added item to cart", body = CartItem),
+        (status = BAD_REQUEST, description = "Invalid quantity", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = NOT_FOUND, description = "Product not found", body = ErrorDetail),
+        (status = CONFLICT, description = "Not enough stock", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn add(
-    auth: auth::JWTWithUser<users::Model>,
+    owner: CartOwner,
     State(ctx): State<AppContext>,
     Json(params): Json<CartItemCreateParams>,
 ) -> Result<Response> {
@@ -97,14 +303,28 @@ pub async fn add(
         .await?
         .ok_or_else(|| Error::NotFound)?;
 
+    let settings = Settings::from_context(&ctx)?;
+    check_quantity(params.quantity, &product_variant, &settings.carts)?;
+
     let mut item = ActiveModel {
-        user_id: Set(auth.user.id),
         ..Default::default()
     };
-
+    owner.assign(&mut item);
     params.update(&mut item);
+    item.unit_price = Set(Some(product.effective_price()));
 
-    let cart_item = item.insert(&ctx.db).await?;
+    // The line is added to and checked in one transaction, so an add that
+    // takes it past the stock or the per-line limit is undone
+    let txn = ctx.db.begin().await?;
+    let cart_item = cart_items::Model::add(&txn, item, owner.column())
+        .await
+        .map_err(api_error)?;
+    check_quantity(
+        cart_item.quantity.unwrap_or(0),
+        &product_variant,
+        &settings.carts,
+    )?;
+    txn.commit().await?;
 
     format::json(CartItem {
         cart_item,
@@ -126,16 +346,13 @@ pub async fn add(
 )]
 #[debug_handler]
 pub async fn get_one(
-    auth: auth::JWTWithUser<users::Model>,
+    owner: CartOwner,
     Path(product_variant_id): Path<i32>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     let (cart_item, product_variant, product) = Entity::find()
-        .filter(
-            Column::UserId
-                .eq(auth.user.id)
-                .and(Column::ProductVariantId.eq(product_variant_id)),
-        )
+        .filter(owner.filter())
+        .filter(Column::ProductVariantId.eq(product_variant_id))
         .find_also_related(product_variants::Entity)
         .and_also_related(products::Entity)
         .one(&ctx.db)
@@ -161,12 +378,12 @@ pub async fn get_one(
 )]
 #[debug_handler]
 pub async fn remove(
-    auth: auth::JWTWithUser<users::Model>,
+    owner: CartOwner,
     Path(product_variant_id): Path<i32>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     Entity::delete_many()
-        .filter(Column::UserId.eq(auth.user.id))
+        .filter(owner.filter())
         .filter(Column::ProductVariantId.eq(product_variant_id))
         .exec(&ctx.db)
         .await?;
@@ -181,46 +398,98 @@ pub async fn remove(
     summary = "Update cart item",
     responses(
         (status = OK, description = "Updated cart item", body = CartItem),
+        (status = BAD_REQUEST, description = "Invalid quantity", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = NOT_FOUND, description = "Cart item not found", body = ErrorDetail),
+        (status = CONFLICT, description = "Not enough stock", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
+    owner: CartOwner,
     Path(product_variant_id): Path<i32>,
     State(ctx): State<AppContext>,
     Json(params): Json<CartItemUpdateParams>,
 ) -> Result<Response> {
     let item = Entity::find()
-        .filter(
-            Column::UserId
-                .eq(auth.user.id)
-                .and(Column::ProductVariantId.eq(product_variant_id)),
-        )
+        .filter(owner.filter())
+        .filter(Column::ProductVariantId.eq(product_variant_id))
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let (product_variant, product) = product_variants::Entity::find_by_id(product_variant_id)
+        .find_also_related(products::Entity)
         .one(&ctx.db)
         .await?
         .ok_or_else(|| Error::NotFound)?;
-    let mut item = item.into_active_model();
 
-    params.update(&mut item);
+    let settings = Settings::from_context(&ctx)?;
+    check_quantity(params.quantity, &product_variant, &settings.carts)?;
 
-    let cart_item = item.update(&ctx.db).await?;
-    let (product_variant, product) = if let Some((product_variant, product)) =
-        product_variants::Entity::find_by_id(product_variant_id)
-            .find_also_related(products::Entity)
-            .one(&ctx.db)
-            .await?
-    {
-        (Some(product_variant), product)
-    } else {
-        (None, None)
-    };
+    // Changing the quantity confirms the line at today's price
+    let mut item = item.into_active_model();
+    params.update(&mut item);
+    if let Some(ref product) = product {
+        item.unit_price = Set(Some(product.effective_price()));
+    }
+    let cart_item = item.update(&ctx.db).await.map_err(api_error)?;
 
     format::json(CartItem {
         cart_item,
         product,
-        product_variant,
+        product_variant: Some(product_variant),
+    })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/cart",
+    tags = ["Cart"],
+    summary = "Empty the cart",
+    responses(
+        (status = OK, description = "Emptied the cart"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn clear(owner: CartOwner, State(ctx): State<AppContext>) -> Result<Response> {
+    Entity::delete_many()
+        .filter(owner.filter())
+        .exec(&ctx.db)
+        .await?;
+
+    format::empty()
+}
+
+/// Starts a cart for a visitor who is not logged in. The returned token is
+/// sent back in the `X-Cart-Token` header or the `cart_token` cookie, and the
+/// cart is merged into the user's own cart when they log in or register.
+/// Carts started are rate limited per client IP.
+#[utoipa::path(
+    post,
+    path = "/api/cart/guest",
+    tags = ["Cart"],
+    summary = "Start an anonymous cart",
+    responses(
+        (status = OK, description = "Created a guest cart", body = GuestCartResponse),
+        (status = TOO_MANY_REQUESTS, description = "Too many carts started", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn create_guest(State(ctx): State<AppContext>, ip: RemoteIP) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    let limits = &settings.rate_limits;
+    let key = format!("guest_carts:ip:{}", client_ip(ip));
+    if let Some(wait) = throttle(&ctx, limits, &key, limits.guest_carts_per_ip).await? {
+        return too_many_requests(wait);
+    }
+    let cart = guest_carts::Model::create(&ctx.db, settings.carts.guest_cart_ttl_days).await?;
+    let jwt_secret = ctx.config.get_jwt_config()?;
+    let token = cart.generate_token(&jwt_secret.secret)?;
+
+    format::json(GuestCartResponse {
+        token,
+        expires_at: cart.expires_at,
     })
 }
 
@@ -229,6 +498,8 @@ pub fn routes() -> Routes {
         .prefix("api/cart/")
         .add("/", get(list))
         .add("/", post(add))
+        .add("/", delete(clear))
+        .add("guest", post(create_guest))
         .add("{product_variant_id}", get(get_one))
         .add("{product_variant_id}", delete(remove))
         .add("{product_variant_id}", patch(update))
@@ -238,6 +509,8 @@ pub fn api_routes() -> OpenApiRouter<AppContext> {
     OpenApiRouter::new()
         .routes(routes!(list))
         .routes(routes!(add))
+        .routes(routes!(clear))
+        .routes(routes!(create_guest))
         .routes(routes!(get_one))
         .routes(routes!(remove))
         .routes(routes!(update))
@@ -3,14 +3,17 @@
 #![allow(clippy::unused_async)]
 use loco_openapi::prelude::{routes, OpenApiRouter};
 use loco_rs::prelude::*;
+use sea_orm::{sea_query::Expr, QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::{forbidden, ErrorDetail},
+    controllers::{api_error, bad_request, conflict, ErrorDetail},
     models::{
-        _entities::categories::{ActiveModel, Entity, Model},
-        users,
+        _entities::categories::{ActiveModel, Column, Entity, Model},
+        audit_events,
     },
+    permissions::{require, Authorized},
+    views::categories::CategoryNode,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
@@ -73,6 +76,25 @@ impl CategoryUpdateParams {
     }
 }
 
+/// What to do with the subcategories of a category that is being deleted.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildrenAction {
+    /// Refuse to delete a category that still has subcategories.
+    #[default]
+    Reject,
+    /// Move the subcategories up to the deleted category's parent.
+    Reparent,
+    /// Delete the subcategories along with the category.
+    Cascade,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CategoryDeleteQuery {
+    #[serde(default)]
+    pub children: ChildrenAction,
+}
+
 async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
     let item = Entity::find_by_id(id).one(&ctx.db).await?;
 
@@ -93,6 +115,42 @@ pub async fn list(State(ctx): State<AppContext>) -> Result<Response> {
     format::json(Entity::find().all(&ctx.db).await?)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/categories/tree",
+    tags = ["Categories"],
+    summary = "Get category tree",
+    responses(
+        (status = OK, description = "Category tree", body = Vec<CategoryNode>),
+    )
+)]
+#[debug_handler]
+pub async fn tree(State(ctx): State<AppContext>) -> Result<Response> {
+    let categories = Entity::find().order_by_asc(Column::Id).all(&ctx.db).await?;
+    let counts = Model::direct_product_counts(&ctx.db).await?;
+
+    format::json(CategoryNode::build_forest(categories, &counts))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/categories/{id}/ancestors",
+    tags = ["Categories"],
+    summary = "Get category breadcrumb",
+    responses(
+        (status = OK, description = "Categories from the root down to this one", body = Vec<Model>),
+        (status = NOT_FOUND, description = "Category not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn ancestors(Path(id): Path<String>, State(ctx): State<AppContext>) -> Result<Response> {
+    let item = Model::get_by_id_or_slug(&ctx.db, id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+
+    format::json(Model::ancestors(&ctx.db, item.id).await?)
+}
+
 #[utoipa::path(
     post,
     path = "/api/categories",
@@ -102,18 +160,15 @@ pub async fn list(State(ctx): State<AppContext>) -> Result<Response> {
         (status = OK, description = "Created category", body = Model),
         (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permission", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The parent category does not exist", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn add(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::CategoriesWrite>,
     State(ctx): State<AppContext>,
     Json(params): Json<CategoryCreateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
     // Check if the parent category exists
     if let Some(parent_id) = params.parent_id {
         let _ = load_item(&ctx, parent_id).await?;
@@ -124,7 +179,11 @@ pub async fn add(
     };
 
     params.update(&mut item);
-    format::json(item.insert(&ctx.db).await?)
+    let txn = ctx.db.begin().await?;
+    let item = item.insert(&txn).await.map_err(api_error)?;
+    audit_events::Model::created(&txn, &auth.user, "category", item.id, &item).await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 #[utoipa::path(
@@ -135,33 +194,49 @@ pub async fn add(
     responses(
         (status = OK, description = "Updated category", body = Model),
         (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
+        (status = BAD_REQUEST, description = "Parent would create a cycle", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permission", body = ErrorDetail),
         (status = NOT_FOUND, description = "Category not found", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The parent category does not exist", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::CategoriesWrite>,
     Path(id): Path<String>,
     State(ctx): State<AppContext>,
     Json(params): Json<CategoryUpdateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
+    let item = Model::get_by_id_or_slug(&ctx.db, id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
 
-    // Check if the parent category exists
+    let txn = ctx.db.begin().await?;
+    // Check if the parent category exists and is not a descendant. Two moves
+    // could each pass the check and together make a cycle, so moves lock the
+    // whole tree and are checked one after another
     if let Some(Some(parent_id)) = params.parent_id {
-        let _ = load_item(&ctx, parent_id).await?;
+        let tree = Entity::find().lock_exclusive().all(&txn).await?;
+        if !tree.iter().any(|category| category.id == parent_id) {
+            return Err(Error::NotFound);
+        }
+
+        if Model::would_create_cycle(&txn, item.id, parent_id).await? {
+            return bad_request("A category cannot be moved under itself or its descendants.");
+        }
     }
 
-    let item = Model::get_by_id_or_slug(&ctx.db, id)
-        .await?
-        .ok_or_else(|| Error::NotFound)?;
+    let before = item.clone();
     let mut item = item.into_active_model();
 
     params.update(&mut item);
-    format::json(item.update(&ctx.db).await?)
+    let item = item.update(&txn).await.map_err(api_error)?;
+    audit_events::Model::changed(
+        &txn, &auth.user, "update", "category", item.id, &before, &item,
+    )
+    .await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 #[utoipa::path(
@@ -174,23 +249,42 @@ pub async fn update(
         (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permission", body = ErrorDetail),
         (status = NOT_FOUND, description = "Category not found", body = ErrorDetail),
+        (status = CONFLICT, description = "Category has subcategories", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn remove(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::CategoriesWrite>,
     Path(id): Path<String>,
     State(ctx): State<AppContext>,
+    Query(params): Query<CategoryDeleteQuery>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
+    let item = Model::get_by_id_or_slug(&ctx.db, id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let txn = ctx.db.begin().await?;
+    let has_children = !item.children(&txn).await?.is_empty();
+
+    match params.children {
+        ChildrenAction::Reject if has_children => {
+            return conflict(
+                "Category has subcategories. Pass `children=reparent` or `children=cascade`.",
+            );
+        }
+        ChildrenAction::Reparent if has_children => {
+            Entity::update_many()
+                .col_expr(Column::ParentId, Expr::value(item.parent_id))
+                .filter(Column::ParentId.eq(item.id))
+                .exec(&txn)
+                .await?;
+        }
+        // Subcategories are removed by the `ON DELETE CASCADE` on `parent_id`
+        ChildrenAction::Reject | ChildrenAction::Reparent | ChildrenAction::Cascade => {}
     }
 
-    Model::get_by_id_or_slug(&ctx.db, id)
-        .await?
-        .ok_or_else(|| Error::NotFound)?
-        .delete(&ctx.db)
-        .await?;
+    audit_events::Model::deleted(&txn, &auth.user, "category", item.id, &item).await?;
+    item.delete(&txn).await?;
+    txn.commit().await?;
 
     format::empty()
 }
@@ -221,7 +315,9 @@ pub fn routes() -> Routes {
         .prefix("api/categories/")
         .add("/", get(list))
         .add("/", post(add))
+        .add("tree", get(tree))
         .add("{id}", get(get_one))
+        .add("{id}/ancestors", get(ancestors))
         .add("{id}", delete(remove))
         .add("{id}", patch(update))
 }
@@ -229,5 +325,7 @@ pub fn routes() -> Routes {
 pub fn api_routes() -> OpenApiRouter<AppContext> {
     OpenApiRouter::new()
         .routes(routes!(list, add))
+        .routes(routes!(tree))
         .routes(routes!(get_one, remove, update))
+        .routes(routes!(ancestors))
 }
@@ -1,36 +1,342 @@
-use axum::http::StatusCode;
-use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 
+use axum::{
+    http::{header, StatusCode},
+    response::Response,
+};
+use loco_rs::{controller::format, model::ModelError, validation::ModelValidationErrors, Error};
+use sea_orm::{
+    sqlx::{self, postgres::PgDatabaseError},
+    DbErr, RuntimeErr, SqlErr,
+};
+use serde::{Deserialize, Serialize};
+
+pub mod audit_events;
 pub mod auth;
 pub mod brands;
 pub mod cart_items;
 pub mod categories;
+pub mod moderation;
 pub mod orders;
 pub mod product_variants;
 pub mod products;
+pub mod reports;
 pub mod reviews;
+pub mod roles;
 pub mod wishlists;
 
+/// The stable, machine-readable code in the `error` field of every error
+/// response. Clients should branch on it rather than on the description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request can't be carried out as sent.
+    BadRequest,
+    /// Some fields of the request are invalid; `errors` says which and why.
+    ValidationFailed,
+    /// Sign in is needed, or the credentials are wrong.
+    Unauthorized,
+    /// The signed in user may not do this.
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    /// The request clashes with the current state of what it acts on.
+    Conflict,
+    /// Another record already has a value that must be unique; `errors`
+    /// names the fields.
+    AlreadyExists,
+    /// What the request refers to existed once but is no longer usable.
+    Gone,
+    UnsupportedMediaType,
+    /// The request is understood but can't be processed.
+    UnprocessableEntity,
+    /// A field refers to a record that doesn't exist; `errors` names it.
+    InvalidReference,
+    TooManyRequests,
+    InternalServerError,
+    /// A service the API relies on failed.
+    BadGateway,
+}
+
+impl ErrorCode {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::BadRequest => "bad_request",
+            Self::ValidationFailed => "validation_failed",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::NotFound => "not_found",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::Conflict => "conflict",
+            Self::AlreadyExists => "already_exists",
+            Self::Gone => "gone",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::UnprocessableEntity => "unprocessable_entity",
+            Self::InvalidReference => "invalid_reference",
+            Self::TooManyRequests => "too_many_requests",
+            Self::InternalServerError => "internal_server_error",
+            Self::BadGateway => "bad_gateway",
+        }
+    }
+
+    /// The general code for an error status, for errors that don't come
+    /// with a more specific one.
+    #[must_use]
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => Self::MethodNotAllowed,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::GONE => Self::Gone,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::UnprocessableEntity,
+            StatusCode::TOO_MANY_REQUESTS => Self::TooManyRequests,
+            StatusCode::BAD_GATEWAY => Self::BadGateway,
+            status if status.is_server_error() => Self::InternalServerError,
+            _ => Self::BadRequest,
+        }
+    }
+}
+
+/// What is wrong with one field of a request.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct FieldError {
+    /// What kind of problem it is, e.g. `length`, `range` or
+    /// `already_exists`.
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The limits the field broke, e.g. `min` for a `length` error.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[schema(value_type = Object)]
+    pub params: HashMap<String, serde_json::Value>,
+}
+
+/// Field errors by field name.
+pub type FieldErrors = BTreeMap<String, Vec<FieldError>>;
+
 #[derive(Debug, Serialize, utoipa::ToSchema)]
-/// Structure representing details about an error.
+/// The body of every error response.
 pub struct ErrorDetail {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub error: ErrorCode,
+    /// What went wrong, for people to read.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// What is wrong with each field, for `validation_failed`,
+    /// `already_exists` and `invalid_reference` errors.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, Vec<FieldError>>>)]
     pub errors: Option<serde_json::Value>,
 }
 
-/// # Errors
-/// Always return an error.
-pub fn forbidden<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
-    Err(loco_rs::errors::Error::CustomError(
-        StatusCode::FORBIDDEN,
+/// An error response with the given status, code and description.
+#[must_use]
+pub fn error<T: Into<String>>(status: StatusCode, code: ErrorCode, msg: T) -> Error {
+    with_field_errors(status, code, msg, None)
+}
+
+fn with_field_errors<T: Into<String>>(
+    status: StatusCode,
+    code: ErrorCode,
+    msg: T,
+    errors: Option<FieldErrors>,
+) -> Error {
+    Error::CustomError(
+        status,
         loco_rs::controller::ErrorDetail {
-            error: Some("Forbidden".to_string()),
+            error: Some(code.as_str().to_string()),
             description: Some(msg.into()),
-            errors: None,
+            errors: errors.and_then(|errors| serde_json::to_value(errors).ok()),
         },
+    )
+}
+
+/// A `400 Bad Request` listing what is wrong with each field.
+#[must_use]
+pub fn validation_failed(errors: FieldErrors) -> Error {
+    with_field_errors(
+        StatusCode::BAD_REQUEST,
+        ErrorCode::ValidationFailed,
+        "Some fields are invalid.",
+        Some(errors),
+    )
+}
+
+/// A `400 Bad Request` for one invalid field.
+#[must_use]
+pub fn invalid_field<T: Into<String>>(field: &str, code: &str, msg: T) -> Error {
+    validation_failed(BTreeMap::from([(
+        field.to_string(),
+        vec![FieldError {
+            code: code.to_string(),
+            message: Some(msg.into()),
+            params: HashMap::new(),
+        }],
+    )]))
+}
+
+/// # Errors
+/// Always return an error.
+pub fn bad_request<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, msg))
+}
+
+/// # Errors
+/// Always return an error.
+pub fn forbidden<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(StatusCode::FORBIDDEN, ErrorCode::Forbidden, msg))
+}
+
+/// # Errors
+/// Always return an error.
+pub fn conflict<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(StatusCode::CONFLICT, ErrorCode::Conflict, msg))
+}
+
+/// # Errors
+/// Always return an error.
+pub fn unprocessable<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::UnprocessableEntity,
+        msg,
     ))
 }
+
+/// # Errors
+/// Always return an error.
+pub fn bad_gateway<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(StatusCode::BAD_GATEWAY, ErrorCode::BadGateway, msg))
+}
+
+/// # Errors
+/// Always return an error.
+pub fn gone<T: Into<String>, U>(msg: T) -> loco_rs::Result<U> {
+    Err(error(StatusCode::GONE, ErrorCode::Gone, msg))
+}
+
+/// Turns errors caused by what the client sent, which would otherwise be
+/// reported as `500 Internal Server Error`, into responses that say what was
+/// wrong: unique violations become `409 already_exists`, foreign key
+/// violations `422 invalid_reference` (or `409 conflict` when deleting a
+/// record that is still referenced) and failed model validations
+/// `400 validation_failed`. Other errors are returned unchanged.
+///
+/// Use it on database writes: `item.insert(&txn).await.map_err(api_error)?`.
+#[must_use]
+pub fn api_error(err: impl Into<Error>) -> Error {
+    match err.into() {
+        Error::DB(err) | Error::Model(ModelError::DbErr(err)) => db_error(err),
+        Error::Validation(errors) | Error::Model(ModelError::Validation(errors)) => {
+            validation_failed(model_field_errors(errors))
+        }
+        Error::Model(ModelError::EntityAlreadyExists) => error(
+            StatusCode::CONFLICT,
+            ErrorCode::AlreadyExists,
+            "It already exists.",
+        ),
+        Error::Model(ModelError::EntityNotFound) => Error::NotFound,
+        err => err,
+    }
+}
+
+fn db_error(err: DbErr) -> Error {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => with_field_errors(
+            StatusCode::CONFLICT,
+            ErrorCode::AlreadyExists,
+            "A record with the same value already exists.",
+            key_field_errors(&err, "already_exists"),
+        ),
+        // Postgres words the message this way when the violation comes from
+        // deleting or renumbering the referenced record.
+        Some(SqlErr::ForeignKeyConstraintViolation(msg)) if msg.starts_with("update or delete") => {
+            error(
+                StatusCode::CONFLICT,
+                ErrorCode::Conflict,
+                "It is still referenced by other records.",
+            )
+        }
+        Some(SqlErr::ForeignKeyConstraintViolation(_)) => with_field_errors(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InvalidReference,
+            "A referenced record does not exist.",
+            key_field_errors(&err, "invalid_reference"),
+        ),
+        _ => match err {
+            DbErr::RecordNotFound(_) | DbErr::RecordNotUpdated => Error::NotFound,
+            // `Validatable` models fail `before_save` with their field
+            // errors as JSON
+            DbErr::Custom(msg) => match serde_json::from_str::<FieldErrors>(&msg) {
+                Ok(errors) => validation_failed(errors),
+                Err(_) => Error::DB(DbErr::Custom(msg)),
+            },
+            err => Error::DB(err),
+        },
+    }
+}
+
+fn model_field_errors(errors: ModelValidationErrors) -> FieldErrors {
+    errors
+        .errors
+        .into_iter()
+        .map(|(field, errors)| {
+            let errors = errors
+                .into_iter()
+                .map(|err| FieldError {
+                    code: err.code,
+                    message: err.message,
+                    params: err.params,
+                })
+                .collect();
+            (field, errors)
+        })
+        .collect()
+}
+
+/// Field errors for the columns a Postgres constraint violation names in its
+/// detail, e.g. `Key (sku)=(CV-38) already exists.`
+fn key_field_errors(err: &DbErr, code: &str) -> Option<FieldErrors> {
+    let (DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))
+    | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))) = err
+    else {
+        return None;
+    };
+    let detail = db_err.try_downcast_ref::<PgDatabaseError>()?.detail()?;
+    let (columns, _) = detail.strip_prefix("Key (")?.split_once(")=(")?;
+
+    Some(
+        columns
+            .split(", ")
+            .map(|column| {
+                let error = FieldError {
+                    code: code.to_string(),
+                    message: Some(detail.to_string()),
+                    params: HashMap::new(),
+                };
+                (column.to_string(), vec![error])
+            })
+            .collect(),
+    )
+}
+
+/// Responds with `429 Too Many Requests` and a `Retry-After` header telling
+/// the client how many seconds to wait.
+///
+/// # Errors
+/// When the response could not be built.
+pub fn too_many_requests(retry_after: u64) -> loco_rs::Result<Response> {
+    format::render()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header(header::RETRY_AFTER, retry_after)
+        .json(ErrorDetail {
+            error: ErrorCode::TooManyRequests,
+            description: Some(format!(
+                "Too many attempts, try again in {retry_after} seconds."
+            )),
+            errors: None,
+        })
+}
@@ -0,0 +1,204 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use loco_openapi::prelude::{routes, OpenApiRouter};
+use loco_rs::{model::query::PaginationQuery, prelude::*};
+use sea_orm::{sea_query, PaginatorTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::ErrorDetail,
+    models::{
+        _entities::{reviews::Column, sea_orm_active_enums::ReviewStatus},
+        audit_events, review_reports,
+        reviews::Entity,
+        users,
+    },
+    permissions::{require, Authorized},
+    views::{
+        pagination::PageResponse,
+        reviews::{ModerationReview, Review},
+        users::User,
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ModerationQuery {
+    /// Only list reviews with this status.
+    #[serde(default)]
+    pub status: Option<ReviewStatus>,
+    /// Only list reviews that were reported by customers.
+    #[serde(default)]
+    pub flagged: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    Approve,
+    Reject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ModerateParams {
+    pub review_ids: Vec<i32>,
+    pub action: ModerationAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ModerateResponse {
+    pub updated: u64,
+}
+
+fn reported_reviews() -> sea_query::SelectStatement {
+    sea_query::Query::select()
+        .column(review_reports::Column::ReviewId)
+        .from(review_reports::Entity)
+        .to_owned()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reviews",
+    tags = ["Moderation"],
+    summary = "List reviews awaiting moderation",
+    responses(
+        (status = OK, description = "Reviews listed", body = PageResponse<ModerationReview>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn list(
+    _: Authorized<require::ReviewsModerate>,
+    State(ctx): State<AppContext>,
+    Query(pagination): Query<PaginationQuery>,
+    Query(params): Query<ModerationQuery>,
+) -> Result<Response> {
+    let mut query = Entity::find();
+
+    // Without filters, the queue is everything that is pending or reported
+    query = match (params.status, params.flagged) {
+        (None, None) => query.filter(
+            Column::Status
+                .eq(ReviewStatus::Pending)
+                .or(Column::Id.in_subquery(reported_reviews())),
+        ),
+        (status, flagged) => {
+            if let Some(status) = status {
+                query = query.filter(Column::Status.eq(status));
+            }
+            match flagged {
+                Some(true) => query.filter(Column::Id.in_subquery(reported_reviews())),
+                Some(false) => query.filter(Column::Id.not_in_subquery(reported_reviews())),
+                None => query,
+            }
+        }
+    };
+
+    let paginator = query
+        .find_also_related(users::Entity)
+        .order_by_asc(Column::CreatedAt)
+        .order_by_asc(Column::Id)
+        .paginate(&ctx.db, pagination.page_size);
+    let counts = paginator.num_items_and_pages().await?;
+    let reviews = paginator.fetch_page(pagination.page - 1).await?;
+    let reports = review_reports::Model::count_by_review(
+        &ctx.db,
+        reviews.iter().map(|(review, _)| review.id),
+    )
+    .await?;
+
+    let items = reviews
+        .into_iter()
+        .map(|(review, user)| ModerationReview {
+            report_count: reports.get(&review.id).copied().unwrap_or_default(),
+            review: Review {
+                review,
+                user: user.map(|u| User {
+                    id: u.id,
+                    name: u.name,
+                }),
+                product: None,
+            },
+        })
+        .collect::<Vec<_>>();
+
+    format::json(PageResponse {
+        items,
+        counts: counts.into(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/reviews/moderate",
+    tags = ["Moderation"],
+    summary = "Approve or reject reviews in bulk",
+    responses(
+        (status = OK, description = "Reviews moderated", body = ModerateResponse),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn moderate(
+    auth: Authorized<require::ReviewsModerate>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<ModerateParams>,
+) -> Result<Response> {
+    let status = match params.action {
+        ModerationAction::Approve => ReviewStatus::Published,
+        ModerationAction::Reject => ReviewStatus::Rejected,
+    };
+
+    let txn = ctx.db.begin().await?;
+    let reviews = || {
+        Entity::find()
+            .filter(Column::Id.is_in(params.review_ids.clone()))
+            .order_by_asc(Column::Id)
+    };
+    let before = reviews().all(&txn).await?;
+    let result = Entity::update_many()
+        .col_expr(
+            Column::Status,
+            Column::Status.save_as(sea_query::Expr::val(status)),
+        )
+        .col_expr(
+            Column::UpdatedAt,
+            sea_query::Expr::value(chrono::Utc::now()),
+        )
+        .filter(Column::Id.is_in(params.review_ids.clone()))
+        .exec(&txn)
+        .await?;
+    for (before, after) in before.iter().zip(reviews().all(&txn).await?) {
+        audit_events::Model::changed(
+            &txn, &auth.user, "moderate", "review", after.id, before, &after,
+        )
+        .await?;
+    }
+
+    // A moderation decision resolves the reports filed against the review
+    review_reports::Entity::delete_many()
+        .filter(review_reports::Column::ReviewId.is_in(params.review_ids))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+
+    format::json(ModerateResponse {
+        updated: result.rows_affected,
+    })
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/admin/reviews/")
+        .add("/", get(list))
+        .add("moderate", post(moderate))
+}
+
+pub fn api_routes() -> OpenApiRouter<AppContext> {
+    OpenApiRouter::new()
+        .routes(routes!(list))
+        .routes(routes!(moderate))
+}
@@ -2,24 +2,34 @@
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
 
+use std::collections::HashMap;
+
+use axum::Extension;
+use chrono::{DateTime, Utc};
 use loco_openapi::prelude::{routes, OpenApiRouter};
-use loco_rs::prelude::*;
+use loco_rs::{model::query::PaginationQuery, prelude::*};
 use rust_decimal::{dec, prelude::FromPrimitive};
+use sea_orm::{sea_query, PaginatorTrait, QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::{forbidden, ErrorDetail},
+    controllers::{api_error, bad_request, conflict, forbidden, invalid_field, ErrorDetail},
+    documents::{self, DocumentFormat},
+    initializers::idempotency::IdempotencyClaim,
     models::{
         _entities::{
             order_items,
             orders::Column,
             product_variants,
             sea_orm_active_enums::{OrderStatus, PaymentMethod},
+            users,
         },
+        audit_events, invoice_sequences,
         orders::{ActiveModel, Entity},
-        users,
     },
-    views::orders::Order,
+    permissions::{require, Authorized, Permission},
+    settings::{Settings, VerificationGate},
+    views::{orders::Order, pagination::PageResponse},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
@@ -35,6 +45,51 @@ pub struct OrderCreateParams {
     pub items: Vec<OrderItemCreateParams>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSort {
+    #[default]
+    Newest,
+    Oldest,
+    AmountHigh,
+    AmountLow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct OrderListQuery {
+    #[serde(default)]
+    pub sort: OrderSort,
+    #[serde(default)]
+    pub status: Option<OrderStatus>,
+    #[serde(default)]
+    pub payment_method: Option<PaymentMethod>,
+    /// Only list orders placed at or after this time.
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only list orders placed before this time.
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub min_amount: Option<Decimal>,
+    #[serde(default)]
+    pub max_amount: Option<Decimal>,
+    /// Staff with `orders.read` only: orders of customers whose email
+    /// contains this text.
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Staff with `orders.read` only: orders containing a variant whose SKU
+    /// contains this text.
+    #[serde(default)]
+    pub sku: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DocumentQuery {
+    /// Whether to render the document as a web page or a PDF.
+    #[serde(default)]
+    pub format: DocumentFormat,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct OrderUpdateParams {
     #[serde(default)]
@@ -76,37 +131,132 @@ impl OrderUpdateParams {
     tags = ["Orders"],
     summary = "List orders",
     responses(
-        (status = OK, description = "Orders listed", body = Vec<Order>),
+        (status = OK, description = "Orders listed", body = PageResponse<Order>),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Filter is only available to staff", body = ErrorDetail),
     )
 )]
 #[debug_handler]
-#[allow(clippy::needless_collect)] // seemingly does not work without collect
 pub async fn list(
     auth: auth::JWTWithUser<users::Model>,
     State(ctx): State<AppContext>,
+    Query(pagination): Query<PaginationQuery>,
+    Query(params): Query<OrderListQuery>,
 ) -> Result<Response> {
-    let mut query = Entity::find().find_with_related(order_items::Entity);
+    let mut query = Entity::find();
+
+    if auth.user.can(Permission::OrdersRead) {
+        if let Some(email) = params.email {
+            query = query.filter(
+                Column::UserId.in_subquery(
+                    sea_query::Query::select()
+                        .column(users::Column::Id)
+                        .from(users::Entity)
+                        .and_where(
+                            sea_query::Expr::expr(sea_query::Func::lower(sea_query::Expr::col(
+                                users::Column::Email,
+                            )))
+                            .like(contains_pattern(&email.to_lowercase())),
+                        )
+                        .to_owned(),
+                ),
+            );
+        }
+        if let Some(sku) = params.sku {
+            query = query.filter(
+                Column::Id.in_subquery(
+                    sea_query::Query::select()
+                        .column(order_items::Column::OrderId)
+                        .from(order_items::Entity)
+                        .inner_join(
+                            product_variants::Entity,
+                            sea_query::Expr::col((
+                                product_variants::Entity,
+                                product_variants::Column::Id,
+                            ))
+                            .equals((order_items::Entity, order_items::Column::ProductVariantId)),
+                        )
+                        .and_where(product_variants::Column::Sku.like(contains_pattern(&sku)))
+                        .to_owned(),
+                ),
+            );
+        }
+    } else {
+        if params.email.is_some() || params.sku.is_some() {
+            return forbidden("Only staff can filter orders by customer or SKU.");
+        }
+        query = query.filter(Column::UserId.eq(auth.user.id));
+    }
 
-    if !auth.user.is_staff {
-        query = query.filter(Column::UserId.eq(auth.user.id))
+    if let Some(status) = params.status {
+        query = query.filter(Column::Status.eq(status));
+    }
+    if let Some(payment_method) = params.payment_method {
+        query = query.filter(Column::PaymentMethod.eq(payment_method));
+    }
+    if let Some(from) = params.from {
+        query = query.filter(Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = params.to {
+        query = query.filter(Column::CreatedAt.lt(to));
+    }
+    if let Some(min_amount) = params.min_amount {
+        query = query.filter(Column::Amount.gte(min_amount));
+    }
+    if let Some(max_amount) = params.max_amount {
+        query = query.filter(Column::Amount.lte(max_amount));
     }
 
-    let result = query.all(&ctx.db).await?;
+    query = match params.sort {
+        OrderSort::Newest => query.order_by_desc(Column::CreatedAt),
+        OrderSort::Oldest => query.order_by_asc(Column::CreatedAt),
+        OrderSort::AmountHigh => query
+            .order_by_desc(Column::Amount)
+            .order_by_desc(Column::CreatedAt),
+        OrderSort::AmountLow => query
+            .order_by_asc(Column::Amount)
+            .order_by_desc(Column::CreatedAt),
+    };
+
+    let paginator = query
+        .order_by_asc(Column::Id)
+        .paginate(&ctx.db, pagination.page_size);
+    let counts = paginator.num_items_and_pages().await?;
+    let orders = paginator.fetch_page(pagination.page - 1).await?;
+
+    // Items and variants are loaded for the whole page at once
+    let mut items = order_items::Entity::find()
+        .filter(order_items::Column::OrderId.is_in(orders.iter().map(|order| order.id)))
+        .order_by_asc(order_items::Column::Id)
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .fold(HashMap::<i32, Vec<_>>::new(), |mut items, item| {
+            items.entry(item.order_id).or_default().push(item);
+            items
+        });
     let variants = product_variants::Model::find_many_with_product(
         &ctx.db,
-        result
-            .iter()
-            .flat_map(|(_, items)| items.iter().map(|item| item.product_variant_id))
+        items
+            .values()
+            .flatten()
+            .map(|item| item.product_variant_id)
             .collect::<Vec<i32>>(),
     )
     .await?;
-    let result = result
+
+    let items = orders
         .into_iter()
-        .map(|(order, items)| Order::create(order, items, &variants))
+        .map(|order| {
+            let order_items = items.remove(&order.id).unwrap_or_default();
+            Order::create(order, order_items, &variants)
+        })
         .collect::<Vec<_>>();
 
-    format::json(result)
+    format::json(PageResponse {
+        items,
+        counts: counts.into(),
+    })
 }
 
 #[utoipa::path(
@@ -114,10 +264,17 @@ pub async fn list(
     path = "/api/orders",
     tags = ["Orders"],
     summary = "Create order",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the original response instead of creating another order"),
+    ),
     responses(
         (status = OK, description = "Order created", body = Order),
+        (status = BAD_REQUEST, description = "Invalid quantity or Idempotency-Key", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
-        (status = NOT_FOUND, description = "Product not found", body = ErrorDetail)
+        (status = FORBIDDEN, description = "The email address must be verified first", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Product not found", body = ErrorDetail),
+        (status = CONFLICT, description = "A request with the same key is still being processed or took over", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The key was used for a different request", body = ErrorDetail),
     )
 )]
 #[debug_handler]
@@ -125,11 +282,21 @@ pub async fn list(
 pub async fn add(
     auth: auth::JWTWithUser<users::Model>,
     State(ctx): State<AppContext>,
+    claim: Option<Extension<IdempotencyClaim>>,
     Json(params): Json<OrderCreateParams>,
 ) -> Result<Response> {
+    let settings = Settings::from_context(&ctx)?;
+    if auth.user.email_verified_at.is_none()
+        && settings.email_verification.required_for != VerificationGate::None
+    {
+        return forbidden("Verify your email address before placing an order.");
+    }
+
     if params.items.iter().any(|item| item.quantity <= 0) {
-        return Err(Error::BadRequest(
-            "Quantity must be greater than zero".to_string(),
+        return Err(invalid_field(
+            "items",
+            "range",
+            "Quantity must be greater than zero",
         ));
     }
 
@@ -168,14 +335,14 @@ pub async fn add(
         .fold(dec!(0), |acc, item| acc + item);
 
     let order = ActiveModel {
-        user_id: Set(auth.user.id),
+        user_id: Set(Some(auth.user.id)),
         status: Set(OrderStatus::Pending),
         amount: Set(order_total),
         payment_method: Set(params.payment_method),
         shipping_address: Set(Some(params.shipping_address.clone())),
         ..Default::default()
     };
-    let order = order.insert(&txn).await?;
+    let order = order.insert(&txn).await.map_err(api_error)?;
     let order_id = order.id;
 
     order_items::Entity::insert_many(params.items.iter().map(|item| {
@@ -195,17 +362,37 @@ pub async fn add(
         }
     }))
     .exec(&txn)
-    .await?;
-    txn.commit().await?;
+    .await
+    .map_err(api_error)?;
 
-    format::json(Order::create(
+    let order = Order::create(
         order,
         order_items::Entity::find()
             .filter(order_items::Column::OrderId.eq(order_id))
-            .all(&ctx.db)
+            .all(&txn)
             .await?,
         &variants,
-    ))
+    );
+
+    // The response is stored with the order, so a retry replays it even if
+    // this request fails after committing. A retry that took over the key
+    // while this one was slow places the order instead.
+    if let Some(Extension(IdempotencyClaim(record))) = claim {
+        let stored = record
+            .complete(
+                &txn,
+                200,
+                Some("application/json".to_string()),
+                serde_json::to_string(&order)?,
+            )
+            .await?;
+        if !stored {
+            return conflict("A retry with this Idempotency-Key took over the request");
+        }
+    }
+    txn.commit().await.map_err(api_error)?;
+
+    format::json(order)
 }
 
 #[utoipa::path(
@@ -231,7 +418,7 @@ pub async fn get_one(
         .await?
         .ok_or_else(|| Error::NotFound)?;
 
-    if order.user_id != auth.user.id && !auth.user.is_staff {
+    if order.user_id != Some(auth.user.id) && !auth.user.can(Permission::OrdersRead) {
         return forbidden("You are not authorized to view this item.");
     }
 
@@ -245,6 +432,8 @@ pub async fn get_one(
     format::json(Order::create(order, order_items, &variants))
 }
 
+/// Staff with `orders.update_status` can change the status; changing the
+/// other details also takes `orders.write`.
 #[utoipa::path(
     patch,
     path = "/api/orders/{id}",
@@ -258,24 +447,45 @@ pub async fn get_one(
     )
 )]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::OrdersUpdateStatus>,
     Path(id): Path<i32>,
     State(ctx): State<AppContext>,
     Json(params): Json<OrderUpdateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
+    let changes_details = params.amount.is_some()
+        || params.payment_method.is_some()
+        || params.shipping_address.is_some();
+    if changes_details && !auth.user.can(Permission::OrdersWrite) {
+        return forbidden("You are only authorized to change the status of orders.");
     }
 
-    let mut order = Entity::find_by_id(id)
-        .one(&ctx.db)
+    let txn = ctx.db.begin().await?;
+    // The order stays locked until the transaction ends, so concurrent
+    // updates can't both see it uninvoiced and each take a number
+    let order = Entity::find_by_id(id)
+        .lock_exclusive()
+        .one(&txn)
         .await?
-        .ok_or_else(|| Error::NotFound)?
-        .into_active_model();
+        .ok_or_else(|| Error::NotFound)?;
+    let invoiced = order.invoice_number.is_some();
+    let before = order.clone();
+    let mut order = order.into_active_model();
 
     params.update(&mut order);
 
-    let order = order.update(&ctx.db).await?;
+    // The number is taken in the same transaction, so it is only used up
+    // when the order is actually saved as paid
+    if !invoiced && params.status == Some(OrderStatus::Paid) {
+        order.invoice_number = Set(Some(invoice_sequences::Model::next_number(&txn).await?));
+        order.invoiced_at = Set(Some(chrono::Utc::now().into()));
+    }
+    let order = order.update(&txn).await.map_err(api_error)?;
+    audit_events::Model::changed(
+        &txn, &auth.user, "update", "order", order.id, &before, &order,
+    )
+    .await?;
+    txn.commit().await?;
+
     let order_items = order.find_related(order_items::Entity).all(&ctx.db).await?;
     let variants = product_variants::Model::find_many_with_product(
         &ctx.db,
@@ -293,6 +503,7 @@ pub async fn update(
     summary = "Cancel order",
     responses(
         (status = OK, description = "Order cancelled"),
+        (status = BAD_REQUEST, description = "The order is no longer pending", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
         (status = NOT_FOUND, description = "Order not found", body = ErrorDetail)
@@ -303,29 +514,125 @@ pub async fn cancel(
     Path(id): Path<i32>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
+    let txn = ctx.db.begin().await?;
+    // The order stays locked until the transaction ends, so a concurrent
+    // status change can't land between the check and the cancellation
     let order = Entity::find_by_id(id)
-        .one(&ctx.db)
+        .lock_exclusive()
+        .one(&txn)
         .await?
         .ok_or_else(|| Error::NotFound)?;
 
-    if auth.user.id != order.user_id && !auth.user.is_staff {
+    if order.user_id != Some(auth.user.id) && !auth.user.can(Permission::OrdersUpdateStatus) {
         return forbidden("You are not authorized to perform this action.");
     }
 
     if order.status != OrderStatus::Pending {
-        return Err(Error::BadRequest(
-            "Cannot cancel an ongoing order.".to_string(),
-        ));
+        return bad_request("Cannot cancel an ongoing order.");
     }
 
+    let before = order.clone();
     let mut order = order.into_active_model();
 
     order.status = Set(OrderStatus::Cancelled);
-    order.update(&ctx.db).await?;
+    let order = order.update(&txn).await?;
+    // Customers cancelling their own orders are not audited
+    if order.user_id != Some(auth.user.id) {
+        audit_events::Model::changed(
+            &txn, &auth.user, "cancel", "order", order.id, &before, &order,
+        )
+        .await?;
+    }
+    txn.commit().await?;
 
     format::empty()
 }
 
+/// A `LIKE` pattern matching values that contain `text`, with the wildcards
+/// in `text` matched literally.
+fn contains_pattern(text: &str) -> sea_query::LikeExpr {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    sea_query::LikeExpr::new(format!("%{escaped}%")).escape('\\')
+}
+
+/// The order with its customer, unless their account was deleted.
+async fn load_document(db: &DatabaseConnection, id: i32) -> Result<(Order, Option<users::Model>)> {
+    let (order, customer) = Entity::find_by_id(id)
+        .find_also_related(users::Entity)
+        .one(db)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+
+    let order_items = order.find_related(order_items::Entity).all(db).await?;
+    let variants = product_variants::Model::find_many_with_product(
+        db,
+        order_items.iter().map(|item| item.product_variant_id),
+    )
+    .await?;
+
+    Ok((Order::create(order, order_items, &variants), customer))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/invoice",
+    tags = ["Orders"],
+    summary = "Get order invoice",
+    responses(
+        (status = OK, description = "Invoice rendered as HTML or PDF"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Order not found", body = ErrorDetail),
+        (status = CONFLICT, description = "Order has not been paid yet", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn invoice(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Query(params): Query<DocumentQuery>,
+) -> Result<Response> {
+    let (order, customer) = load_document(&ctx.db, id).await?;
+
+    if order.order.user_id != Some(auth.user.id) && !auth.user.can(Permission::OrdersRead) {
+        return forbidden("You are not authorized to view this item.");
+    }
+    if order.order.invoice_number.is_none() {
+        return conflict("The order has not been invoiced yet.");
+    }
+
+    documents::render_invoice(&order, customer.as_ref(), params.format)
+}
+
+/// Staff with the `orders.read` permission only.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/packing-slip",
+    tags = ["Orders"],
+    summary = "Get order packing slip",
+    responses(
+        (status = OK, description = "Packing slip rendered as HTML or PDF"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Order not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn packing_slip(
+    _: Authorized<require::OrdersRead>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Query(params): Query<DocumentQuery>,
+) -> Result<Response> {
+    let (order, customer) = load_document(&ctx.db, id).await?;
+
+    documents::render_packing_slip(&order, customer.as_ref(), params.format)
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("api/orders/")
@@ -334,6 +641,8 @@ pub fn routes() -> Routes {
         .add("{id}", get(get_one))
         .add("{id}", patch(update))
         .add("{id}/cancel", post(cancel))
+        .add("{id}/invoice", get(invoice))
+        .add("{id}/packing-slip", get(packing_slip))
 }
 
 pub fn api_routes() -> OpenApiRouter<AppContext> {
@@ -341,4 +650,6 @@ pub fn api_routes() -> OpenApiRouter<AppContext> {
         .routes(routes!(list, add))
         .routes(routes!(get_one, update))
         .routes(routes!(cancel))
+        .routes(routes!(invoice))
+        .routes(routes!(packing_slip))
 }
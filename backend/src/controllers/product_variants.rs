@@ -7,12 +7,14 @@ use loco_rs::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::{forbidden, ErrorDetail},
+    controllers::{api_error, invalid_field, ErrorDetail},
     models::{
         _entities::product_variants::{Column, Entity},
+        audit_events,
         product_variants::{ActiveModel, Model},
-        users,
+        wishlists,
     },
+    permissions::{require, Authorized},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
@@ -30,7 +32,7 @@ impl ProductVariantCreateParams {
         item.stock = Set(self
             .stock
             .try_into()
-            .map_err(|_| Error::BadRequest("Product stock too large".to_string()))?);
+            .map_err(|_| invalid_field("stock", "range", "Product stock too large"))?);
         item.sku = Set(self.sku.clone());
 
         Ok(())
@@ -73,7 +75,7 @@ impl ProductVariantUpdateParams {
         if let Some(stock) = self.stock {
             item.stock = Set(stock
                 .try_into()
-                .map_err(|_| Error::BadRequest("Product stock too large".to_string()))?);
+                .map_err(|_| invalid_field("stock", "range", "Product stock too large"))?);
         }
 
         if let Some(ref sku) = self.sku {
@@ -118,28 +120,31 @@ pub async fn list(Path(product_id): Path<i32>, State(ctx): State<AppContext>) ->
     summary = "Create product variant",
     responses(
         (status = OK, description = "Created product variant", body = Model),
+        (status = BAD_REQUEST, description = "Invalid stock", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Not authenticated", body = ErrorDetail),
         (status = FORBIDDEN, description = "Insufficient permissions", body = ErrorDetail),
+        (status = CONFLICT, description = "A variant with this SKU already exists", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The product does not exist", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn add(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     Path(product_id): Path<i32>,
     State(ctx): State<AppContext>,
     Json(params): Json<ProductVariantCreateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
     let mut item = ActiveModel {
         product_id: Set(product_id),
         ..Default::default()
     };
 
     params.update(&mut item)?;
-    format::json(item.insert(&ctx.db).await?)
+    let txn = ctx.db.begin().await?;
+    let item = item.insert(&txn).await.map_err(api_error)?;
+    audit_events::Model::created(&txn, &auth.user, "product_variant", item.id, &item).await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 #[utoipa::path(
@@ -172,18 +177,16 @@ pub async fn get_one(
 )]
 #[debug_handler]
 pub async fn remove(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     Path((product_id, id)): Path<(i32, i32)>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
-    load_item(&ctx, product_id, id)
-        .await?
-        .delete(&ctx.db)
-        .await?;
+    let item = load_item(&ctx, product_id, id).await?;
+    let txn = ctx.db.begin().await?;
+    audit_events::Model::deleted(&txn, &auth.user, "product_variant", item.id, &item).await?;
+    wishlists::Model::delete_pinned_duplicates(&txn, item.product_id, item.id).await?;
+    item.delete(&txn).await?;
+    txn.commit().await?;
 
     format::empty()
 }
@@ -195,27 +198,38 @@ pub async fn remove(
     summary = "Update product variant",
     responses(
         (status = OK, description = "Product variant", body = Model),
+        (status = BAD_REQUEST, description = "Invalid stock", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+        (status = CONFLICT, description = "A variant with this SKU already exists", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     Path((product_id, id)): Path<(i32, i32)>,
     State(ctx): State<AppContext>,
     Json(params): Json<ProductVariantUpdateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
-    let item = load_item(&ctx, product_id, id).await?;
-    let mut item = item.into_active_model();
+    let before = load_item(&ctx, product_id, id).await?;
+    let mut item = before.clone().into_active_model();
 
     params.update(&mut item)?;
 
-    format::json(item.update(&ctx.db).await?)
+    let txn = ctx.db.begin().await?;
+    let item = item.update(&txn).await.map_err(api_error)?;
+    audit_events::Model::changed(
+        &txn,
+        &auth.user,
+        "update",
+        "product_variant",
+        item.id,
+        &before,
+        &item,
+    )
+    .await?;
+    txn.commit().await?;
+    format::json(item)
 }
 
 pub fn routes() -> Routes {
@@ -7,19 +7,19 @@ use loco_rs::{model::query::PaginationQuery, prelude::*};
 use sea_orm::{PaginatorTrait, QueryOrder};
 use serde::{Deserialize, Serialize};
 
-use super::forbidden;
 use crate::{
-    controllers::ErrorDetail,
+    controllers::{api_error, ErrorDetail},
     models::{
         _entities::{
             product_variants,
             products::{ActiveModel, Column, Entity},
         },
-        brands, categories,
+        audit_events, brands, categories,
         products::Model,
-        users,
     },
+    permissions::{require, Authorized},
     views::{pagination::PageResponse, products::Product},
+    workers::price_drop::{PriceDropWorker, PriceDropWorkerArgs},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
@@ -179,23 +179,23 @@ pub async fn list(
         (status = OK, description = "Product created", body = Model),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The brand or category does not exist", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn add(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     State(ctx): State<AppContext>,
     Json(params): Json<ProductCreateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
     let mut item = ActiveModel {
         ..Default::default()
     };
     params.update(&mut item);
-    let item = item.insert(&ctx.db).await?;
+    let txn = ctx.db.begin().await?;
+    let item = item.insert(&txn).await.map_err(api_error)?;
+    audit_events::Model::created(&txn, &auth.user, "product", item.id, &item).await?;
+    txn.commit().await?;
     format::json(item)
 }
 
@@ -209,25 +209,46 @@ pub async fn add(
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
         (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
         (status = NOT_FOUND, description = "Product not found", body = ErrorDetail),
+        (status = UNPROCESSABLE_ENTITY, description = "The brand or category does not exist", body = ErrorDetail),
     )
 )]
 #[debug_handler]
 pub async fn update(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     Path(id): Path<String>,
     State(ctx): State<AppContext>,
     Json(params): Json<ProductUpdateParams>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
     let item = Model::get_by_id_or_slug(&ctx.db, id)
         .await?
         .ok_or_else(|| Error::NotFound)?;
+    let old_price = item.effective_price();
+    let before = item.clone();
     let mut item = item.into_active_model();
     params.update(&mut item);
-    let item = item.update(&ctx.db).await?;
+    let txn = ctx.db.begin().await?;
+    let item = item.update(&txn).await.map_err(api_error)?;
+    audit_events::Model::changed(
+        &txn, &auth.user, "update", "product", item.id, &before, &item,
+    )
+    .await?;
+    txn.commit().await?;
+
+    // The update is committed, so a failed enqueue only costs the alerts
+    if item.effective_price() < old_price {
+        let args = PriceDropWorkerArgs {
+            product_id: item.id,
+            old_price,
+        };
+        if let Err(err) = PriceDropWorker::perform_later(&ctx, args).await {
+            tracing::error!(
+                err = err.to_string(),
+                product_id = item.id,
+                "could not enqueue price drop alerts"
+            );
+        }
+    }
+
     format::json(item)
 }
 
@@ -245,19 +266,17 @@ pub async fn update(
 )]
 #[debug_handler]
 pub async fn remove(
-    auth: auth::JWTWithUser<users::Model>,
+    auth: Authorized<require::ProductsWrite>,
     Path(id): Path<String>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    if !auth.user.is_staff {
-        return forbidden("You are not authorized to perform this action.");
-    }
-
-    Model::get_by_id_or_slug(&ctx.db, id)
+    let item = Model::get_by_id_or_slug(&ctx.db, id)
         .await?
-        .ok_or_else(|| Error::NotFound)?
-        .delete(&ctx.db)
-        .await?;
+        .ok_or_else(|| Error::NotFound)?;
+    let txn = ctx.db.begin().await?;
+    audit_events::Model::deleted(&txn, &auth.user, "product", item.id, &item).await?;
+    item.delete(&txn).await?;
+    txn.commit().await?;
     format::empty()
 }
 
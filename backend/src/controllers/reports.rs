@@ -0,0 +1,510 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+use chrono::{DateTime, Utc};
+use loco_openapi::prelude::{routes, OpenApiRouter};
+use loco_rs::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::{
+    sea_query::{Expr, SimpleExpr},
+    Condition, DbBackend, JoinType, Order, QueryOrder, QuerySelect, RelationTrait, Select,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::ErrorDetail,
+    models::_entities::{
+        brands, categories, order_items, orders, product_variants, products,
+        sea_orm_active_enums::OrderStatus,
+    },
+    permissions::{require, Authorized},
+    views::reports::{
+        to_csv, CsvRecord, GroupSales, ProductSales, SalesPeriod, SalesSummary, SellThrough,
+        VariantSales,
+    },
+};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReportQuery {
+    /// Only count orders placed at or after this time.
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only count orders placed before this time.
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Interval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SalesQuery {
+    #[serde(default)]
+    pub interval: Interval,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TopBy {
+    #[default]
+    Units,
+    Revenue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct TopQuery {
+    #[serde(default)]
+    pub by: TopBy,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+const fn default_limit() -> u64 {
+    10
+}
+
+/// Orders that count as sales: placed in the range and not cancelled.
+fn sales(query: &ReportQuery) -> Condition {
+    placed(query).add(orders::Column::Status.ne(OrderStatus::Cancelled))
+}
+
+fn placed(query: &ReportQuery) -> Condition {
+    let mut condition = Condition::all();
+    if let Some(from) = query.from {
+        condition = condition.add(orders::Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = query.to {
+        condition = condition.add(orders::Column::CreatedAt.lt(to));
+    }
+    condition
+}
+
+/// First day of the interval an order was placed in, as `YYYY-MM-DD`.
+fn period(backend: DbBackend, interval: Interval) -> SimpleExpr {
+    let sql = match (backend, interval) {
+        (DbBackend::Sqlite, Interval::Day) => "strftime('%Y-%m-%d', orders.created_at)",
+        // Weeks start on Monday, like `date_trunc` does
+        (DbBackend::Sqlite, Interval::Week) => "date(orders.created_at, 'weekday 0', '-6 days')",
+        (DbBackend::Sqlite, Interval::Month) => "strftime('%Y-%m-01', orders.created_at)",
+        (_, Interval::Day) => {
+            "to_char(date_trunc('day', orders.created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')"
+        }
+        (_, Interval::Week) => {
+            "to_char(date_trunc('week', orders.created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')"
+        }
+        (_, Interval::Month) => {
+            "to_char(date_trunc('month', orders.created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')"
+        }
+    };
+    Expr::cust(sql)
+}
+
+/// Order lines of the orders that count as sales, with their units and
+/// revenue selected for grouping.
+fn sold_items(query: &ReportQuery) -> Select<order_items::Entity> {
+    order_items::Entity::find()
+        .select_only()
+        .join(JoinType::InnerJoin, order_items::Relation::Orders.def())
+        .filter(sales(query))
+        .column_as(
+            Expr::cust("SUM(COALESCE(order_items.quantity, 0))"),
+            "units",
+        )
+        .column_as(
+            Expr::cust("SUM(order_items.price * COALESCE(order_items.quantity, 0))"),
+            "revenue",
+        )
+}
+
+fn top<E: EntityTrait>(select: Select<E>, params: &TopQuery) -> Select<E> {
+    let (first, second) = match params.by {
+        TopBy::Units => ("units", "revenue"),
+        TopBy::Revenue => ("revenue", "units"),
+    };
+    select
+        .order_by(Expr::cust(first), Order::Desc)
+        .order_by(Expr::cust(second), Order::Desc)
+        .limit(params.limit)
+}
+
+fn respond<T: CsvRecord + Serialize>(
+    name: &str,
+    rows: Vec<T>,
+    format: ReportFormat,
+) -> Result<Response> {
+    match format {
+        ReportFormat::Json => format::json(rows),
+        ReportFormat::Csv => Ok(format::render()
+            .header(CONTENT_TYPE, "text/csv; charset=utf-8")
+            .header(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}.csv\""),
+            )
+            .response()
+            .body(Body::from(to_csv(&rows)))?),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/sales",
+    tags = ["Reports"],
+    summary = "Revenue and order count by day, week or month",
+    responses(
+        (status = OK, description = "Sales per period, as JSON or CSV", body = Vec<SalesPeriod>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn sales_by_period(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+    Query(params): Query<SalesQuery>,
+) -> Result<Response> {
+    let period = period(ctx.db.get_database_backend(), params.interval);
+    let rows = orders::Entity::find()
+        .select_only()
+        .column_as(period.clone(), "period")
+        .column_as(orders::Column::Id.count(), "order_count")
+        .column_as(orders::Column::Amount.sum(), "revenue")
+        .filter(sales(&query))
+        .group_by(period.clone())
+        .order_by(period, Order::Asc)
+        .into_model::<SalesPeriod>()
+        .all(&ctx.db)
+        .await?;
+
+    respond("sales", rows, query.format)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/products",
+    tags = ["Reports"],
+    summary = "Best selling products",
+    responses(
+        (status = OK, description = "Products by units or revenue, as JSON or CSV", body = Vec<ProductSales>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn top_products(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+    Query(params): Query<TopQuery>,
+) -> Result<Response> {
+    let select = sold_items(&query)
+        .join(
+            JoinType::InnerJoin,
+            order_items::Relation::ProductVariants.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            product_variants::Relation::Products.def(),
+        )
+        .column_as(products::Column::Id, "product_id")
+        .column_as(products::Column::Name, "name")
+        .group_by(products::Column::Id)
+        .group_by(products::Column::Name);
+    let rows = top(select, &params)
+        .order_by_asc(products::Column::Id)
+        .into_model::<ProductSales>()
+        .all(&ctx.db)
+        .await?;
+
+    respond("products", rows, query.format)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/variants",
+    tags = ["Reports"],
+    summary = "Best selling variants",
+    responses(
+        (status = OK, description = "Variants by units or revenue, as JSON or CSV", body = Vec<VariantSales>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn top_variants(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+    Query(params): Query<TopQuery>,
+) -> Result<Response> {
+    let select = sold_items(&query)
+        .join(
+            JoinType::InnerJoin,
+            order_items::Relation::ProductVariants.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            product_variants::Relation::Products.def(),
+        )
+        .column_as(product_variants::Column::Id, "product_variant_id")
+        .column(product_variants::Column::Sku)
+        .column(product_variants::Column::Size)
+        .column(product_variants::Column::Color)
+        .column_as(products::Column::Name, "product_name")
+        .group_by(product_variants::Column::Id)
+        .group_by(product_variants::Column::Sku)
+        .group_by(product_variants::Column::Size)
+        .group_by(product_variants::Column::Color)
+        .group_by(products::Column::Name);
+    let rows = top(select, &params)
+        .order_by_asc(product_variants::Column::Id)
+        .into_model::<VariantSales>()
+        .all(&ctx.db)
+        .await?;
+
+    respond("variants", rows, query.format)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/brands",
+    tags = ["Reports"],
+    summary = "Sales by brand",
+    responses(
+        (status = OK, description = "Units and revenue per brand, as JSON or CSV", body = Vec<GroupSales>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn sales_by_brand(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response> {
+    let rows = sold_items(&query)
+        .join(
+            JoinType::InnerJoin,
+            order_items::Relation::ProductVariants.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            product_variants::Relation::Products.def(),
+        )
+        .join(JoinType::LeftJoin, products::Relation::Brands.def())
+        .column_as(brands::Column::Id, "id")
+        .column_as(brands::Column::Name, "name")
+        .group_by(brands::Column::Id)
+        .group_by(brands::Column::Name)
+        .order_by(Expr::cust("revenue"), Order::Desc)
+        .order_by_asc(brands::Column::Id)
+        .into_model::<GroupSales>()
+        .all(&ctx.db)
+        .await?;
+
+    respond("brands", rows, query.format)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/categories",
+    tags = ["Reports"],
+    summary = "Sales by category",
+    responses(
+        (status = OK, description = "Units and revenue per category, as JSON or CSV", body = Vec<GroupSales>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn sales_by_category(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response> {
+    let rows = sold_items(&query)
+        .join(
+            JoinType::InnerJoin,
+            order_items::Relation::ProductVariants.def(),
+        )
+        .join(
+            JoinType::InnerJoin,
+            product_variants::Relation::Products.def(),
+        )
+        .join(JoinType::LeftJoin, products::Relation::Categories.def())
+        .column_as(categories::Column::Id, "id")
+        .column_as(categories::Column::Name, "name")
+        .group_by(categories::Column::Id)
+        .group_by(categories::Column::Name)
+        .order_by(Expr::cust("revenue"), Order::Desc)
+        .order_by_asc(categories::Column::Id)
+        .into_model::<GroupSales>()
+        .all(&ctx.db)
+        .await?;
+
+    respond("categories", rows, query.format)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/summary",
+    tags = ["Reports"],
+    summary = "Average order value and cancellation rate",
+    responses(
+        (status = OK, description = "Summary of the orders in the range, as JSON or CSV", body = SalesSummary),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn summary(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response> {
+    let (order_count, cancelled_count, revenue) = orders::Entity::find()
+        .select_only()
+        .column_as(orders::Column::Id.count(), "order_count")
+        .column_as(
+            Expr::cust(
+                "COALESCE(SUM(CASE WHEN orders.status = 'CANCELLED' THEN 1 ELSE 0 END), 0)",
+            ),
+            "cancelled_count",
+        )
+        .column_as(
+            Expr::cust(
+                "COALESCE(SUM(CASE WHEN orders.status = 'CANCELLED' THEN 0 ELSE orders.amount END), 0)",
+            ),
+            "revenue",
+        )
+        .filter(placed(&query))
+        .into_tuple::<(i64, i64, Decimal)>()
+        .one(&ctx.db)
+        .await?
+        .unwrap_or_default();
+
+    let sold_count = order_count - cancelled_count;
+    let summary = SalesSummary {
+        order_count,
+        cancelled_count,
+        cancellation_rate: if order_count == 0 {
+            0.0
+        } else {
+            cancelled_count.to_f64().unwrap_or_default() / order_count.to_f64().unwrap_or(1.0)
+        },
+        revenue,
+        average_order_value: if sold_count == 0 {
+            Decimal::ZERO
+        } else {
+            (revenue / Decimal::from(sold_count)).round_dp(2)
+        },
+    };
+
+    match query.format {
+        ReportFormat::Json => format::json(summary),
+        ReportFormat::Csv => respond("summary", vec![summary], ReportFormat::Csv),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/sell-through",
+    tags = ["Reports"],
+    summary = "Units sold against current stock per variant",
+    responses(
+        (status = OK, description = "Variants by sell-through, as JSON or CSV", body = Vec<SellThrough>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn sell_through(
+    _: Authorized<require::ReportsRead>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response> {
+    let sold = sold_items(&query)
+        .column(order_items::Column::ProductVariantId)
+        .group_by(order_items::Column::ProductVariantId)
+        .into_tuple::<(i64, Decimal, i32)>()
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|(units, _, variant_id)| (variant_id, units))
+        .collect::<HashMap<_, _>>();
+
+    let mut rows = product_variants::Entity::find()
+        .find_also_related(products::Entity)
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|(variant, product)| {
+            let units_sold = sold.get(&variant.id).copied().unwrap_or_default();
+            let available = units_sold + i64::from(variant.stock.max(0));
+            SellThrough {
+                product_variant_id: variant.id,
+                sku: variant.sku,
+                product_name: product.map(|product| product.name).unwrap_or_default(),
+                units_sold,
+                stock: variant.stock,
+                sell_through: if available == 0 {
+                    0.0
+                } else {
+                    units_sold.to_f64().unwrap_or_default() / available.to_f64().unwrap_or(1.0)
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| {
+        b.sell_through
+            .total_cmp(&a.sell_through)
+            .then(a.product_variant_id.cmp(&b.product_variant_id))
+    });
+
+    respond("sell-through", rows, query.format)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/admin/reports/")
+        .add("sales", get(sales_by_period))
+        .add("products", get(top_products))
+        .add("variants", get(top_variants))
+        .add("brands", get(sales_by_brand))
+        .add("categories", get(sales_by_category))
+        .add("summary", get(summary))
+        .add("sell-through", get(sell_through))
+}
+
+pub fn api_routes() -> OpenApiRouter<AppContext> {
+    OpenApiRouter::new()
+        .routes(routes!(sales_by_period))
+        .routes(routes!(top_products))
+        .routes(routes!(top_variants))
+        .routes(routes!(sales_by_brand))
+        .routes(routes!(sales_by_category))
+        .routes(routes!(summary))
+        .routes(routes!(sell_through))
+}
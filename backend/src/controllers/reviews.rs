@@ -2,18 +2,22 @@
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
 use loco_openapi::prelude::{routes, OpenApiRouter};
-use loco_rs::prelude::*;
+use loco_rs::{model::query::PaginationQuery, prelude::*};
+use sea_orm::{sea_query::OnConflict, PaginatorTrait, QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::{forbidden, ErrorDetail},
+    controllers::{api_error, bad_request, forbidden, ErrorDetail},
     models::{
-        _entities::reviews::Column,
-        products,
+        _entities::{reviews::Column, sea_orm_active_enums::ReviewStatus},
+        audit_events, products, review_reports, review_votes,
         reviews::{ActiveModel, Entity, Model},
         users,
     },
+    permissions::Permission,
+    settings::Settings,
     views::{
+        pagination::PageResponse,
         products::Product,
         reviews::{ListReviewsResponse, Review},
         users::User,
@@ -56,6 +60,36 @@ impl ReviewUpdateParams {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSort {
+    #[default]
+    Newest,
+    Highest,
+    Lowest,
+    MostHelpful,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReviewListQuery {
+    #[serde(default)]
+    pub sort: ReviewSort,
+    /// Only list reviews with this star rating.
+    #[serde(default)]
+    pub rating: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReviewVoteParams {
+    pub helpful: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReviewReportParams {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 #[utoipa::path(
     get,
     path = "/api/products/{product_id}/reviews",
@@ -69,6 +103,8 @@ impl ReviewUpdateParams {
 pub async fn list(
     Path(product_id): Path<String>,
     State(ctx): State<AppContext>,
+    Query(pagination): Query<PaginationQuery>,
+    Query(params): Query<ReviewListQuery>,
 ) -> Result<Response> {
     let product = products::Model::get_by_id_or_slug(&ctx.db, product_id)
         .await?
@@ -77,10 +113,34 @@ pub async fn list(
         products::Model::find_by_id_with_brand_category(&ctx.db, product.id)
             .await?
             .ok_or_else(|| Error::NotFound)?;
-    let reviews = Entity::find()
+
+    let mut query = Entity::find()
         .filter(Column::ProductId.eq(product.id))
+        .filter(Column::Status.eq(ReviewStatus::Published));
+    if let Some(rating) = params.rating {
+        query = query.filter(Column::Rating.eq(rating));
+    }
+    query = match params.sort {
+        ReviewSort::Newest => query.order_by_desc(Column::CreatedAt),
+        ReviewSort::Highest => query
+            .order_by_desc(Column::Rating)
+            .order_by_desc(Column::CreatedAt),
+        ReviewSort::Lowest => query
+            .order_by_asc(Column::Rating)
+            .order_by_desc(Column::CreatedAt),
+        ReviewSort::MostHelpful => query
+            .order_by_desc(Column::HelpfulCount)
+            .order_by_asc(Column::UnhelpfulCount)
+            .order_by_desc(Column::CreatedAt),
+    };
+
+    let paginator = query
+        .order_by_asc(Column::Id)
         .find_also_related(users::Entity)
-        .all(&ctx.db)
+        .paginate(&ctx.db, pagination.page_size);
+    let counts = paginator.num_items_and_pages().await?;
+    let reviews = paginator
+        .fetch_page(pagination.page - 1)
         .await?
         .into_iter()
         .map(|(review, user)| Review {
@@ -100,7 +160,10 @@ pub async fn list(
             category,
             variants: None,
         },
-        reviews,
+        reviews: PageResponse {
+            items: reviews,
+            counts: counts.into(),
+        },
     })
 }
 
@@ -129,16 +192,21 @@ pub async fn add(
         products::Model::find_by_id_with_brand_category(&ctx.db, product.id)
             .await?
             .ok_or_else(|| Error::NotFound)?;
+    let settings = Settings::from_context(&ctx)?;
     let user = users::Entity::find_by_id(auth.user.id).one(&ctx.db).await?;
     let mut item = ActiveModel {
         user_id: Set(auth.user.id),
         product_id: Set(product.id),
+        status: Set(Model::initial_status(
+            &settings.reviews,
+            params.content.as_deref(),
+        )),
         ..Default::default()
     };
 
     params.update(&mut item);
 
-    let item = item.insert(&ctx.db).await?;
+    let item = item.insert(&ctx.db).await.map_err(api_error)?;
 
     format::json(Review {
         review: item,
@@ -179,6 +247,7 @@ pub async fn get_one(
             .ok_or_else(|| Error::NotFound)?;
     let (review, user) = Model::find_by_product_and_user(&ctx.db, product.id, user_id)
         .await?
+        .filter(|(review, _)| review.status == ReviewStatus::Published)
         .ok_or_else(|| Error::NotFound)?;
 
     format::json(Review {
@@ -215,7 +284,7 @@ pub async fn update(
     State(ctx): State<AppContext>,
     Json(params): Json<ReviewUpdateParams>,
 ) -> Result<Response> {
-    if auth.user.id != user_id && !auth.user.is_staff {
+    if auth.user.id != user_id && !auth.user.can(Permission::ReviewsModerate) {
         return forbidden("You are not authorized to perform this action.");
     }
 
@@ -229,11 +298,31 @@ pub async fn update(
     let (review, user) = Model::find_by_product_and_user(&ctx.db, product.id, user_id)
         .await?
         .ok_or_else(|| Error::NotFound)?;
+    let before = review.clone();
     let mut review = review.into_active_model();
 
     params.update(&mut review);
 
-    let review = review.update(&ctx.db).await?;
+    // Edited content goes through the same screening as a new review, unless
+    // staff are editing someone else's review. An edit can only hold a
+    // published review back, never undo a rejection or a hold for reports.
+    if let Some(ref content) = params.content {
+        if auth.user.id == user_id && before.status == ReviewStatus::Published {
+            let settings = Settings::from_context(&ctx)?;
+            review.status = Set(Model::initial_status(&settings.reviews, content.as_deref()));
+        }
+    }
+
+    let txn = ctx.db.begin().await?;
+    let review = review.update(&txn).await.map_err(api_error)?;
+    // Only staff edits of someone else's review are audited
+    if auth.user.id != user_id {
+        audit_events::Model::changed(
+            &txn, &auth.user, "update", "review", review.id, &before, &review,
+        )
+        .await?;
+    }
+    txn.commit().await?;
 
     format::json(Review {
         review,
@@ -268,14 +357,172 @@ pub async fn remove(
     Path((product_id, user_id)): Path<(String, i32)>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    if auth.user.id != user_id && !auth.user.is_staff {
+    if auth.user.id != user_id && !auth.user.can(Permission::ReviewsModerate) {
         return forbidden("You are not authorized to perform this action.");
     }
 
     let product = products::Model::get_by_id_or_slug(&ctx.db, product_id)
         .await?
         .ok_or_else(|| Error::NotFound)?;
-    let _ = Model::delete_by_product_and_user(&ctx.db, product.id, user_id).await?;
+    // Only staff deleting someone else's review is audited
+    let audited = if auth.user.id == user_id {
+        None
+    } else {
+        Model::find_by_product_and_user(&ctx.db, product.id, user_id).await?
+    };
+
+    let txn = ctx.db.begin().await?;
+    if let Some((review, _)) = audited {
+        audit_events::Model::deleted(&txn, &auth.user, "review", review.id, &review).await?;
+    }
+    let _ = Model::delete_by_product_and_user(&txn, product.id, user_id).await?;
+    txn.commit().await?;
+
+    format::empty()
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/products/{product_id}/reviews/{user_id}/vote",
+    tags = ["Reviews"],
+    summary = "Vote review helpful or unhelpful",
+    responses(
+        (status = OK, description = "Vote recorded", body = Model),
+        (status = BAD_REQUEST, description = "Cannot vote on own review", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Product or review not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn vote(
+    auth: auth::JWTWithUser<users::Model>,
+    Path((product_id, user_id)): Path<(String, i32)>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<ReviewVoteParams>,
+) -> Result<Response> {
+    cast_vote(&ctx, &auth.user, product_id, user_id, Some(params.helpful)).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/products/{product_id}/reviews/{user_id}/vote",
+    tags = ["Reviews"],
+    summary = "Withdraw vote on review",
+    responses(
+        (status = OK, description = "Vote withdrawn", body = Model),
+        (status = BAD_REQUEST, description = "Cannot vote on own review", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Product or review not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn unvote(
+    auth: auth::JWTWithUser<users::Model>,
+    Path((product_id, user_id)): Path<(String, i32)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    cast_vote(&ctx, &auth.user, product_id, user_id, None).await
+}
+
+async fn cast_vote(
+    ctx: &AppContext,
+    voter: &users::Model,
+    product_id: String,
+    user_id: i32,
+    helpful: Option<bool>,
+) -> Result<Response> {
+    if voter.id == user_id {
+        return bad_request("You cannot vote on your own review.");
+    }
+
+    let product = products::Model::get_by_id_or_slug(&ctx.db, product_id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let (review, _) = Model::find_by_product_and_user(&ctx.db, product.id, user_id)
+        .await?
+        .filter(|(review, _)| review.status == ReviewStatus::Published)
+        .ok_or_else(|| Error::NotFound)?;
+
+    format::json(review_votes::Model::cast(&ctx.db, review.id, voter.id, helpful).await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/products/{product_id}/reviews/{user_id}/report",
+    tags = ["Reviews"],
+    summary = "Report review",
+    responses(
+        (status = OK, description = "Review reported"),
+        (status = BAD_REQUEST, description = "Cannot report own review", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Product or review not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn report(
+    auth: auth::JWTWithUser<users::Model>,
+    Path((product_id, user_id)): Path<(String, i32)>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<ReviewReportParams>,
+) -> Result<Response> {
+    if auth.user.id == user_id {
+        return bad_request("You cannot report your own review.");
+    }
+
+    let product = products::Model::get_by_id_or_slug(&ctx.db, product_id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let (review, _) = Model::find_by_product_and_user(&ctx.db, product.id, user_id)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+
+    let txn = ctx.db.begin().await?;
+    // The review stays locked until the transaction ends, so concurrent
+    // reports are counted one after the other
+    let Some(review) = Entity::find_by_id(review.id)
+        .filter(Column::Status.eq(ReviewStatus::Published))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Err(Error::NotFound);
+    };
+
+    // Reporting the same review twice is a no-op
+    let inserted = review_reports::Entity::insert(review_reports::ActiveModel {
+        review_id: Set(review.id),
+        user_id: Set(auth.user.id),
+        reason: Set(params.reason),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([
+            review_reports::Column::ReviewId,
+            review_reports::Column::UserId,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(&txn)
+    .await
+    .map_err(api_error)?;
+    if inserted == 0 {
+        return format::empty();
+    }
+
+    let settings = Settings::from_context(&ctx)?;
+    let reports = review_reports::Model::count_by_review(&txn, [review.id])
+        .await?
+        .get(&review.id)
+        .copied()
+        .unwrap_or_default();
+
+    if u64::try_from(reports).unwrap_or_default() >= settings.reviews.report_threshold {
+        let mut review = review.into_active_model();
+        review.status = Set(ReviewStatus::Pending);
+        review.update(&txn).await?;
+    }
+    txn.commit().await?;
 
     format::empty()
 }
@@ -288,10 +535,15 @@ pub fn routes() -> Routes {
         .add("{user_id}", get(get_one))
         .add("{user_id}", patch(update))
         .add("{user_id}", delete(remove))
+        .add("{user_id}/vote", put(vote))
+        .add("{user_id}/vote", delete(unvote))
+        .add("{user_id}/report", post(report))
 }
 
 pub fn api_routes() -> OpenApiRouter<AppContext> {
     OpenApiRouter::new()
         .routes(routes!(list, add))
         .routes(routes!(get_one, update, remove))
+        .routes(routes!(vote, unvote))
+        .routes(routes!(report))
 }
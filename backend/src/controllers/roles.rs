@@ -0,0 +1,99 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use loco_openapi::prelude::{routes, OpenApiRouter};
+use loco_rs::prelude::*;
+use sea_orm::Iterable;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::{forbidden, ErrorDetail},
+    models::{_entities::sea_orm_active_enums::UserRole, audit_events, users},
+    permissions::{require, Authorized},
+    views::roles::{RoleResponse, StaffMember},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AssignRoleParams {
+    /// The role to give the user, or null to make them a customer.
+    pub role: Option<UserRole>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/roles",
+    tags = ["Roles"],
+    summary = "List staff roles and their permissions",
+    responses(
+        (status = OK, description = "Roles listed", body = Vec<RoleResponse>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden", body = ErrorDetail),
+    )
+)]
+#[debug_handler(state = AppContext)]
+pub async fn list(_: Authorized<require::RolesAssign>) -> Result<Response> {
+    format::json(UserRole::iter().map(RoleResponse::new).collect::<Vec<_>>())
+}
+
+/// Gives a user a staff role, or takes it away. The change applies to the
+/// user's next request. Staff can't change their own role, so the last admin
+/// can't lock everyone out.
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{pid}/role",
+    tags = ["Roles"],
+    summary = "Assign a role to a user",
+    responses(
+        (status = OK, description = "Role assigned", body = StaffMember),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = FORBIDDEN, description = "Forbidden, or the user is yourself", body = ErrorDetail),
+        (status = NOT_FOUND, description = "User not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn assign(
+    auth: Authorized<require::RolesAssign>,
+    Path(pid): Path<String>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<AssignRoleParams>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &pid)
+        .await
+        .map_err(|_| Error::NotFound)?;
+    if user.id == auth.user.id {
+        return forbidden("You can't change your own role.");
+    }
+
+    let before = serde_json::json!({ "role": user.role });
+    let mut user = user.into_active_model();
+    user.role = Set(params.role);
+
+    let txn = ctx.db.begin().await?;
+    let user = user.update(&txn).await?;
+    audit_events::Model::changed(
+        &txn,
+        &auth.user,
+        "role_change",
+        "user",
+        user.pid,
+        &before,
+        &serde_json::json!({ "role": user.role }),
+    )
+    .await?;
+    txn.commit().await?;
+
+    format::json(StaffMember::new(&user))
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/admin/")
+        .add("roles", get(list))
+        .add("users/{pid}/role", put(assign))
+}
+
+pub fn api_routes() -> OpenApiRouter<AppContext> {
+    OpenApiRouter::new()
+        .routes(routes!(list))
+        .routes(routes!(assign))
+}
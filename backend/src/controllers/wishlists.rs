@@ -1,27 +1,155 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
+use std::collections::HashMap;
+
 use loco_openapi::prelude::{routes, OpenApiRouter};
 use loco_rs::prelude::*;
-use sea_orm::{sea_query, QueryOrder};
+use sea_orm::{sea_query::OnConflict, QueryOrder};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    controllers::ErrorDetail,
+    controllers::{api_error, bad_request, invalid_field, ErrorDetail},
     models::{
-        _entities::{brands, categories, products, wishlists::Column},
-        users,
-        wishlists::{ActiveModel, Entity},
+        _entities::{brands, categories, product_variants, products, wishlists::Column},
+        users, wishlist_lists,
+        wishlists::{self, ActiveModel, Entity},
+    },
+    views::{
+        products::Product,
+        wishlists::{
+            SharedWishlist, SharedWishlistOwner, WishlistDetail, WishlistItem, WishlistList,
+        },
     },
-    views::products::Product,
 };
 
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ListParams {
+    pub name: String,
+}
+
+impl ListParams {
+    fn update(&self, item: &mut wishlist_lists::ActiveModel) {
+        item.name = Set(self.name.trim().to_string());
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AddItemParams {
+    pub product_id: i32,
+    /// Pin a specific size or color of the product.
+    #[serde(default)]
+    pub variant_id: Option<i32>,
+}
+
+async fn load_list(ctx: &AppContext, id: i32, user_id: i32) -> Result<wishlist_lists::Model> {
+    let item = wishlist_lists::Model::find_for_user(&ctx.db, id, user_id).await?;
+    item.ok_or_else(|| Error::NotFound)
+}
+
+async fn load_items(ctx: &AppContext, list_id: i32) -> Result<Vec<WishlistItem>> {
+    let entries = Entity::find()
+        .filter(Column::WishlistListId.eq(list_id))
+        .order_by_desc(Column::CreatedAt)
+        .order_by_desc(Column::Id)
+        .all(&ctx.db)
+        .await?;
+
+    let products = products::Entity::find()
+        .filter(products::Column::Id.is_in(entries.iter().map(|entry| entry.product_id)))
+        .find_also_related(brands::Entity)
+        .find_also_related(categories::Entity)
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|(product, brand, category)| {
+            (
+                product.id,
+                Product {
+                    product,
+                    brand,
+                    category,
+                    variants: None,
+                },
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let variants = product_variants::Entity::find()
+        .filter(
+            product_variants::Column::Id
+                .is_in(entries.iter().filter_map(|entry| entry.product_variant_id)),
+        )
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|variant| (variant.id, variant))
+        .collect::<HashMap<_, _>>();
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let product = products.get(&entry.product_id)?.clone();
+            Some(WishlistItem {
+                item_id: entry.id,
+                variant: entry
+                    .product_variant_id
+                    .and_then(|id| variants.get(&id).cloned()),
+                saved_price: entry.saved_price,
+                current_price: product.product.effective_price(),
+                product,
+            })
+        })
+        .collect())
+}
+
+async fn add_to_list(
+    ctx: &AppContext,
+    list: &wishlist_lists::Model,
+    product_id: i32,
+    variant_id: Option<i32>,
+) -> Result<()> {
+    let product = products::Entity::find_by_id(product_id)
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    if let Some(variant_id) = variant_id {
+        let variant = product_variants::Entity::find_by_id(variant_id)
+            .filter(product_variants::Column::ProductId.eq(product_id))
+            .one(&ctx.db)
+            .await?;
+        if variant.is_none() {
+            return Err(invalid_field(
+                "variant_id",
+                "invalid_reference",
+                "Variant does not belong to this product",
+            ));
+        }
+    }
+
+    // Saving an item twice keeps the first one, also when two requests race
+    Entity::insert(ActiveModel {
+        user_id: Set(list.user_id),
+        wishlist_list_id: Set(list.id),
+        product_id: Set(product_id),
+        product_variant_id: Set(variant_id),
+        saved_price: Set(Some(product.effective_price())),
+        ..Default::default()
+    })
+    .on_conflict(OnConflict::new().do_nothing().to_owned())
+    .exec_without_returning(&ctx.db)
+    .await
+    .map_err(api_error)?;
+
+    Ok(())
+}
+
 #[utoipa::path(
     get,
     path = "/api/wishlist",
     tags = ["Wishlist"],
-    summary = "List products in wishlist",
+    summary = "List products in the default wishlist",
     responses(
-        (status = OK, description = "Retrieved wishlist", body = Vec<Product>),
+        (status = OK, description = "Retrieved wishlist", body = Vec<WishlistItem>),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
     )
 )]
@@ -30,28 +158,90 @@ pub async fn list(
     auth: auth::JWTWithUser<users::Model>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let results = products::Entity::find()
+    let list = wishlist_lists::Model::find_or_create_default(&ctx.db, auth.user.id).await?;
+    format::json(load_items(&ctx, list.id).await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/products/{product_id}/wishlist",
+    tags = ["Wishlist"],
+    summary = "Add product to the default wishlist",
+    responses(
+        (status = OK, description = "Added to wishlist"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+    )
+)]
+pub async fn add(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(product_id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = wishlist_lists::Model::find_or_create_default(&ctx.db, auth.user.id).await?;
+    add_to_list(&ctx, &list, product_id, None).await?;
+
+    format::empty()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/products/{product_id}/wishlist",
+    tags = ["Wishlist"],
+    summary = "Remove product from the default wishlist",
+    responses(
+        (status = OK, description = "Removed from wishlist"),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+    )
+)]
+pub async fn remove(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(product_id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = wishlist_lists::Model::find_or_create_default(&ctx.db, auth.user.id).await?;
+    Entity::delete_many()
         .filter(
-            products::Column::Id.in_subquery(
-                sea_query::Query::select()
-                    .column(Column::ProductId)
-                    .from(Entity)
-                    .and_where(Column::UserId.eq(auth.user.id))
-                    .to_owned(),
-            ),
+            Column::WishlistListId
+                .eq(list.id)
+                .and(Column::ProductId.eq(product_id)),
         )
-        .find_also_related(brands::Entity)
-        .find_also_related(categories::Entity)
-        .order_by_desc(products::Column::UpdatedAt)
-        .order_by_asc(products::Column::Id)
+        .exec(&ctx.db)
+        .await?;
+
+    format::empty()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/wishlists",
+    tags = ["Wishlist"],
+    summary = "List the user's wishlists",
+    responses(
+        (status = OK, description = "Listed wishlists", body = Vec<WishlistList>),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn list_lists(
+    auth: auth::JWTWithUser<users::Model>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let lists = wishlist_lists::Entity::find()
+        .filter(wishlist_lists::Column::UserId.eq(auth.user.id))
+        .order_by_desc(wishlist_lists::Column::IsDefault)
+        .order_by_asc(wishlist_lists::Column::Name)
+        .order_by_asc(wishlist_lists::Column::Id)
         .all(&ctx.db)
-        .await?
+        .await?;
+
+    let counts = wishlists::Model::count_by_list(&ctx.db, lists.iter().map(|list| list.id)).await?;
+    let results = lists
         .into_iter()
-        .map(|(product, brand, category)| Product {
-            product,
-            brand,
-            category,
-            variants: None,
+        .map(|list| {
+            let item_count = counts
+                .get(&list.id)
+                .map_or(0, |count| u64::try_from(*count).unwrap_or_default());
+            WishlistList { list, item_count }
         })
         .collect::<Vec<_>>();
 
@@ -60,83 +250,266 @@ pub async fn list(
 
 #[utoipa::path(
     post,
-    path = "/api/products/{product_id}/wishlist",
+    path = "/api/wishlists",
     tags = ["Wishlist"],
-    summary = "Add product to wishlist",
+    summary = "Create a wishlist",
     responses(
-        (status = OK, description = "Added to wishlist"),
+        (status = OK, description = "Created a wishlist", body = wishlist_lists::Model),
+        (status = BAD_REQUEST, description = "Invalid name", body = ErrorDetail),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
     )
 )]
-pub async fn add(
+#[debug_handler]
+pub async fn add_list(
     auth: auth::JWTWithUser<users::Model>,
-    Path(product_id): Path<i32>,
     State(ctx): State<AppContext>,
+    Json(params): Json<ListParams>,
 ) -> Result<Response> {
-    let _ = products::Entity::find_by_id(product_id)
-        .one(&ctx.db)
-        .await?
-        .ok_or_else(|| Error::NotFound)?;
-    if (Entity::find()
-        .filter(
-            Column::UserId
-                .eq(auth.user.id)
-                .and(Column::ProductId.eq(product_id)),
-        )
-        .one(&ctx.db)
-        .await?)
-        .is_some()
-    {
-        return format::empty();
+    if params.name.trim().is_empty() {
+        return bad_request("Wishlist name must not be empty");
     }
 
-    let item = ActiveModel {
+    let mut item = wishlist_lists::ActiveModel {
         user_id: Set(auth.user.id),
-        product_id: Set(product_id),
+        is_default: Set(false),
         ..Default::default()
     };
+    params.update(&mut item);
+    let item = item.insert(&ctx.db).await.map_err(api_error)?;
+
+    format::json(item)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/wishlists/{id}",
+    tags = ["Wishlist"],
+    summary = "Get a wishlist with its products",
+    responses(
+        (status = OK, description = "Retrieved wishlist", body = WishlistDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn get_list(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
+    let items = load_items(&ctx, list.id).await?;
+
+    format::json(WishlistDetail { list, items })
+}
 
-    item.insert(&ctx.db).await?;
+#[utoipa::path(
+    patch,
+    path = "/api/wishlists/{id}",
+    tags = ["Wishlist"],
+    summary = "Rename a wishlist",
+    responses(
+        (status = OK, description = "Renamed wishlist", body = wishlist_lists::Model),
+        (status = BAD_REQUEST, description = "Invalid name", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn update_list(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<ListParams>,
+) -> Result<Response> {
+    if params.name.trim().is_empty() {
+        return bad_request("Wishlist name must not be empty");
+    }
+
+    let mut item = load_list(&ctx, id, auth.user.id).await?.into_active_model();
+    params.update(&mut item);
+    let item = item.update(&ctx.db).await.map_err(api_error)?;
+
+    format::json(item)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/wishlists/{id}",
+    tags = ["Wishlist"],
+    summary = "Delete a wishlist and its items",
+    responses(
+        (status = OK, description = "Deleted wishlist"),
+        (status = BAD_REQUEST, description = "The default wishlist cannot be deleted", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn remove_list(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
+    if list.is_default {
+        return bad_request("The default wishlist cannot be deleted");
+    }
+
+    list.delete(&ctx.db).await?;
+    format::empty()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wishlists/{id}/items",
+    tags = ["Wishlist"],
+    summary = "Add a product to a wishlist",
+    responses(
+        (status = OK, description = "Added to wishlist"),
+        (status = BAD_REQUEST, description = "Variant does not belong to the product", body = ErrorDetail),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist or product not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn add_item(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<AddItemParams>,
+) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
+    add_to_list(&ctx, &list, params.product_id, params.variant_id).await?;
 
     format::empty()
 }
 
 #[utoipa::path(
     delete,
-    path = "/api/products/{product_id}/wishlist",
+    path = "/api/wishlists/{id}/items/{item_id}",
     tags = ["Wishlist"],
-    summary = "Remove product from wishlist",
+    summary = "Remove an item from a wishlist",
     responses(
         (status = OK, description = "Removed from wishlist"),
         (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
     )
 )]
-pub async fn remove(
+#[debug_handler]
+pub async fn remove_item(
     auth: auth::JWTWithUser<users::Model>,
-    Path(product_id): Path<i32>,
+    Path((id, item_id)): Path<(i32, i32)>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
     Entity::delete_many()
-        .filter(
-            Column::UserId
-                .eq(auth.user.id)
-                .and(Column::ProductId.eq(product_id)),
-        )
+        .filter(Column::WishlistListId.eq(list.id))
+        .filter(Column::Id.eq(item_id))
         .exec(&ctx.db)
         .await?;
 
     format::empty()
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/wishlists/{id}/share",
+    tags = ["Wishlist"],
+    summary = "Create a new share link for a wishlist",
+    responses(
+        (status = OK, description = "Shared wishlist", body = wishlist_lists::Model),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn share(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
+    format::json(list.into_active_model().share(&ctx.db).await?)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/wishlists/{id}/share",
+    tags = ["Wishlist"],
+    summary = "Revoke the share link of a wishlist",
+    responses(
+        (status = OK, description = "Revoked share link", body = wishlist_lists::Model),
+        (status = UNAUTHORIZED, description = "Unauthorized", body = ErrorDetail),
+        (status = NOT_FOUND, description = "Wishlist not found", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn unshare(
+    auth: auth::JWTWithUser<users::Model>,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = load_list(&ctx, id, auth.user.id).await?;
+    format::json(list.into_active_model().unshare(&ctx.db).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/shared-wishlists/{token}",
+    tags = ["Wishlist"],
+    summary = "View a shared wishlist",
+    responses(
+        (status = OK, description = "Retrieved wishlist", body = SharedWishlist),
+        (status = NOT_FOUND, description = "Wishlist not found or no longer shared", body = ErrorDetail),
+    )
+)]
+#[debug_handler]
+pub async fn get_shared(
+    Path(token): Path<String>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let list = wishlist_lists::Model::find_by_share_token(&ctx.db, &token)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let owner = users::Entity::find_by_id(list.user_id)
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let items = load_items(&ctx, list.id).await?;
+
+    format::json(SharedWishlist {
+        name: list.name,
+        owner: SharedWishlistOwner { name: owner.name },
+        items,
+    })
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .add("/api/wishlist", get(list))
         .add("/api/products/{product_id}/wishlist", post(add))
         .add("/api/products/{product_id}/wishlist", delete(remove))
+        .add("/api/wishlists", get(list_lists))
+        .add("/api/wishlists", post(add_list))
+        .add("/api/wishlists/{id}", get(get_list))
+        .add("/api/wishlists/{id}", patch(update_list))
+        .add("/api/wishlists/{id}", delete(remove_list))
+        .add("/api/wishlists/{id}/items", post(add_item))
+        .add("/api/wishlists/{id}/items/{item_id}", delete(remove_item))
+        .add("/api/wishlists/{id}/share", post(share))
+        .add("/api/wishlists/{id}/share", delete(unshare))
+        .add("/api/shared-wishlists/{token}", get(get_shared))
 }
 
 pub fn api_routes() -> OpenApiRouter<AppContext> {
     OpenApiRouter::new()
         .routes(routes!(list))
         .routes(routes!(add, remove))
+        .routes(routes!(list_lists, add_list))
+        .routes(routes!(get_list, update_list, remove_list))
+        .routes(routes!(add_item))
+        .routes(routes!(remove_item))
+        .routes(routes!(share, unshare))
+        .routes(routes!(get_shared))
 }
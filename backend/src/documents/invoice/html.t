@@ -0,0 +1,57 @@
+<!DOCTYPE html>
+<html>
+
+<head>
+  <meta charset="utf-8">
+  <title>Invoice {{invoice.number}}</title>
+</head>
+
+<body>
+  <h1>Invoice {{invoice.number}}</h1>
+  <p>
+    Invoice date: {{invoice.date}}<br>
+    Order #{{order.id}}, placed {{order.date}}<br>
+    Payment method: {{order.paymentMethod}}
+  </p>
+  <p>
+    <strong>Billed to</strong><br>
+    {{customer.name}}<br>
+    {{customer.email}}{% if order.shippingAddress %}<br>
+    {{order.shippingAddress}}{% endif %}
+  </p>
+  <table>
+    <thead>
+      <tr>
+        <th>Item</th>
+        <th>SKU</th>
+        <th>Size</th>
+        <th>Color</th>
+        <th>Quantity</th>
+        <th>Unit price</th>
+        <th>Total</th>
+      </tr>
+    </thead>
+    <tbody>
+      {% for item in items %}
+      <tr>
+        <td>{{item.name}}</td>
+        <td>{{item.sku}}</td>
+        <td>{{item.size}}</td>
+        <td>{{item.color}}</td>
+        <td>{{item.quantity}}</td>
+        <td>{{item.unitPrice}}</td>
+        <td>{{item.lineTotal}}</td>
+      </tr>
+      {% endfor %}
+    </tbody>
+    <tfoot>
+      <tr>
+        <th colspan="6">Total</th>
+        <th>{{total}}</th>
+      </tr>
+    </tfoot>
+  </table>
+  <p>Thank you for shopping with the Shoes Store.</p>
+</body>
+
+</html>
@@ -0,0 +1,24 @@
+INVOICE {{invoice.number}}
+
+Invoice date:   {{invoice.date}}
+Order:          #{{order.id}}, placed {{order.date}}
+Payment method: {{order.paymentMethod}}
+
+Billed to:
+  {{customer.name}}
+  {{customer.email}}
+{%- if order.shippingAddress %}
+  {{order.shippingAddress}}
+{%- endif %}
+
+Items
+-----
+{%- for item in items %}
+{{item.quantity}} x {{item.name}}
+    SKU {{item.sku}}{% if item.size %}, size {{item.size}}{% endif %}{% if item.color %}, color {{item.color}}{% endif %}
+    {{item.unitPrice}} each, {{item.lineTotal}}
+{%- endfor %}
+-----
+Total: {{total}}
+
+Thank you for shopping with the Shoes Store.
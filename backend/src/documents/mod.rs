@@ -0,0 +1,160 @@
+//! Printable order documents. Each document is a directory of Tera
+//! templates, like the mailer ones: `html.t` for the browser and `text.t`,
+//! which is laid out as a PDF.
+#![allow(non_upper_case_globals)]
+
+pub mod pdf;
+
+use axum::{
+    body::Body,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tera::{Context, Tera};
+
+use crate::{models::users, views::orders::Order};
+
+static invoice: Dir<'_> = include_dir!("src/documents/invoice");
+static packing_slip: Dir<'_> = include_dir!("src/documents/packing_slip");
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+/// Formats an invoice number the way it is printed.
+#[must_use]
+pub fn invoice_label(number: i32) -> String {
+    format!("INV-{number:06}")
+}
+
+/// Renders the invoice of a paid order.
+///
+/// # Errors
+///
+/// When the order has no invoice number yet or rendering fails
+pub fn render_invoice(
+    order: &Order,
+    customer: Option<&users::Model>,
+    format: DocumentFormat,
+) -> Result<Response> {
+    let number = order
+        .order
+        .invoice_number
+        .ok_or_else(|| Error::Message("order has not been invoiced".to_string()))?;
+    let label = invoice_label(number);
+
+    render(
+        &invoice,
+        &format!("invoice-{label}"),
+        &format!("Invoice {label}"),
+        &locals(order, customer),
+        format,
+    )
+}
+
+/// Renders the packing slip the warehouse ships an order with.
+///
+/// # Errors
+///
+/// When rendering fails
+pub fn render_packing_slip(
+    order: &Order,
+    customer: Option<&users::Model>,
+    format: DocumentFormat,
+) -> Result<Response> {
+    let id = order.order.id;
+
+    render(
+        &packing_slip,
+        &format!("packing-slip-{id}"),
+        &format!("Packing slip for order #{id}"),
+        &locals(order, customer),
+        format,
+    )
+}
+
+/// `customer` is `None` for orders of deleted accounts, which are printed
+/// without name and email.
+fn locals(order: &Order, customer: Option<&users::Model>) -> serde_json::Value {
+    let items = order
+        .items
+        .iter()
+        .map(|item| {
+            let quantity = item.order_item.quantity.unwrap_or_default();
+            json!({
+              "name": item.product.as_ref().map(|product| product.name.clone()),
+              "sku": item.product_variant.sku,
+              "size": item.product_variant.size,
+              "color": item.product_variant.color,
+              "quantity": quantity,
+              "unitPrice": format!("{:.2}", item.order_item.price),
+              "lineTotal": format!("{:.2}", item.order_item.price * Decimal::from(quantity)),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+      "order": {
+        "id": order.order.id,
+        "date": order.order.created_at.format("%Y-%m-%d").to_string(),
+        "status": order.order.status,
+        "paymentMethod": order.order.payment_method,
+        "shippingAddress": order.order.shipping_address,
+      },
+      "invoice": order.order.invoice_number.map(|number| json!({
+        "number": invoice_label(number),
+        "date": order
+            .order
+            .invoiced_at
+            .map(|invoiced_at| invoiced_at.format("%Y-%m-%d").to_string()),
+      })),
+      "customer": {
+        "name": customer.map(|customer| customer.name.clone()),
+        "email": customer.map(|customer| customer.email.clone()),
+      },
+      "items": items,
+      "itemCount": order
+          .items
+          .iter()
+          .map(|item| item.order_item.quantity.unwrap_or_default())
+          .sum::<i32>(),
+      "total": format!("{:.2}", order.order.amount),
+    })
+}
+
+fn render(
+    dir: &Dir<'_>,
+    filename: &str,
+    title: &str,
+    locals: &serde_json::Value,
+    format: DocumentFormat,
+) -> Result<Response> {
+    let template = |name: &str| {
+        dir.get_file(name)
+            .and_then(|file| file.contents_utf8())
+            .ok_or_else(|| Error::Message(format!("no document template file found {name}")))
+    };
+
+    let context = Context::from_serialize(locals)?;
+
+    match format {
+        DocumentFormat::Html => format::html(&Tera::one_off(template("html.t")?, &context, true)?),
+        DocumentFormat::Pdf => {
+            let text = Tera::one_off(template("text.t")?, &context, false)?;
+            Ok(format::render()
+                .header(CONTENT_TYPE, "application/pdf")
+                .header(
+                    CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{filename}.pdf\""),
+                )
+                .response()
+                .body(Body::from(pdf::from_text(title, &text)))?)
+        }
+    }
+}
@@ -0,0 +1,52 @@
+<!DOCTYPE html>
+<html>
+
+<head>
+  <meta charset="utf-8">
+  <title>Packing slip for order #{{order.id}}</title>
+</head>
+
+<body>
+  <h1>Packing slip for order #{{order.id}}</h1>
+  <p>
+    Placed {{order.date}}{% if invoice %}<br>
+    Invoice {{invoice.number}}{% endif %}
+  </p>
+  <p>
+    <strong>Ship to</strong><br>
+    {{customer.name}}{% if order.shippingAddress %}<br>
+    {{order.shippingAddress}}{% endif %}
+  </p>
+  <table>
+    <thead>
+      <tr>
+        <th>Packed</th>
+        <th>SKU</th>
+        <th>Item</th>
+        <th>Size</th>
+        <th>Color</th>
+        <th>Quantity</th>
+      </tr>
+    </thead>
+    <tbody>
+      {% for item in items %}
+      <tr>
+        <td>&#9744;</td>
+        <td>{{item.sku}}</td>
+        <td>{{item.name}}</td>
+        <td>{{item.size}}</td>
+        <td>{{item.color}}</td>
+        <td>{{item.quantity}}</td>
+      </tr>
+      {% endfor %}
+    </tbody>
+    <tfoot>
+      <tr>
+        <th colspan="5">Items</th>
+        <th>{{itemCount}}</th>
+      </tr>
+    </tfoot>
+  </table>
+</body>
+
+</html>
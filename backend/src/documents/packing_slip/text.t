@@ -0,0 +1,19 @@
+PACKING SLIP FOR ORDER #{{order.id}}
+
+Placed: {{order.date}}
+{%- if invoice %}
+Invoice: {{invoice.number}}
+{%- endif %}
+
+Ship to:
+  {{customer.name}}
+{%- if order.shippingAddress %}
+  {{order.shippingAddress}}
+{%- endif %}
+
+[ ] Qty  SKU / item
+{%- for item in items %}
+[ ] {{item.quantity}}  {{item.sku}} {{item.name}}{% if item.size %}, size {{item.size}}{% endif %}{% if item.color %}, color {{item.color}}{% endif %}
+{%- endfor %}
+
+Items: {{itemCount}}
@@ -0,0 +1,125 @@
+//! A minimal PDF writer that lays plain text out on A4 pages in a monospaced
+//! font. Documents are written as text templates with aligned columns, which
+//! is all a printed invoice or packing slip needs.
+
+use std::fmt::Write as _;
+
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 10;
+const LINE_HEIGHT: u32 = 12;
+/// Courier glyphs are 0.6em wide
+const LINE_CHARS: usize = ((PAGE_WIDTH - 2 * MARGIN) * 10 / (FONT_SIZE * 6)) as usize;
+const PAGE_LINES: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+
+/// Renders `text` into a PDF document titled `title`. Long lines are wrapped
+/// and a form feed (`\x0c`) starts a new page.
+#[must_use]
+pub fn from_text(title: &str, text: &str) -> Vec<u8> {
+    let pages = paginate(text);
+
+    // Objects 1-3 are the catalog, the page tree and the font, followed by a
+    // page and its content stream for every page
+    let page_ids = (0..pages.len())
+        .map(|page| 4 + 2 * page)
+        .collect::<Vec<_>>();
+    let info_id = 4 + 2 * pages.len();
+
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{id} 0 R"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (lines, page_id) in pages.iter().zip(&page_ids) {
+        let mut content = format!(
+            "BT /F1 {FONT_SIZE} Tf {LINE_HEIGHT} TL {MARGIN} {} Td",
+            PAGE_HEIGHT - MARGIN
+        );
+        for line in lines {
+            let _ = write!(content, " ({}) '", escape(line));
+        }
+        content.push_str(" ET");
+
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            encode(&content).len()
+        ));
+    }
+    objects.push(format!("<< /Title ({}) >>", escape(title)));
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(encode(&format!("{} 0 obj\n{object}\nendobj\n", index + 1)));
+    }
+
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {info_id} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    );
+    pdf.extend(trailer.into_bytes());
+    pdf
+}
+
+fn paginate(text: &str) -> Vec<Vec<String>> {
+    let mut pages = Vec::new();
+    for page in text.split('\x0c') {
+        let lines = page
+            .trim_matches('\n')
+            .lines()
+            .flat_map(wrap)
+            .collect::<Vec<_>>();
+        pages.extend(lines.chunks(PAGE_LINES).map(<[String]>::to_vec));
+    }
+    if pages.is_empty() {
+        pages.push(Vec::new());
+    }
+    pages
+}
+
+fn wrap(line: &str) -> Vec<String> {
+    let chars = line.trim_end().chars().collect::<Vec<_>>();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(LINE_CHARS)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Escapes the characters that delimit PDF string literals.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// The standard fonts only cover Latin-1; anything else prints as `?`.
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
@@ -0,0 +1,83 @@
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderValue, StatusCode},
+    middleware, Router as AxumRouter,
+};
+use loco_rs::prelude::*;
+use serde_json::Value;
+
+use crate::controllers::{ErrorCode, ErrorDetail};
+
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Gives every error response an [`ErrorDetail`] body. Handlers already
+/// answer with one, but the errors loco and axum produce themselves, such as
+/// rejected paths, queries and JSON bodies, carry free text or reason phrases
+/// instead of an error code.
+pub struct ErrorEnvelopeInitializer;
+
+#[async_trait]
+impl Initializer for ErrorEnvelopeInitializer {
+    fn name(&self) -> String {
+        "error-envelope".to_string()
+    }
+
+    async fn after_routes(&self, router: AxumRouter, _ctx: &AppContext) -> Result<AxumRouter> {
+        Ok(router.layer(middleware::map_response(envelope)))
+    }
+}
+
+async fn envelope(response: Response) -> Response {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = to_bytes(body, MAX_BODY_BYTES).await.unwrap_or_default();
+    let body = serde_json::to_vec(&detail(status, &body)).unwrap_or_default();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// The envelope for an error response, keeping what its body already says.
+/// Bodies that are not JSON become the description.
+fn detail(status: StatusCode, body: &[u8]) -> ErrorDetail {
+    let (error, description, errors) = match serde_json::from_slice(body) {
+        Ok(Value::Object(mut body)) => (
+            body.remove("error"),
+            match body.remove("description") {
+                Some(Value::String(description)) => Some(description),
+                _ => None,
+            },
+            body.remove("errors").filter(|errors| !errors.is_null()),
+        ),
+        _ => {
+            let text = String::from_utf8_lossy(body).trim().to_string();
+            (None, (!text.is_empty()).then_some(text), None)
+        }
+    };
+
+    // Codes set by our handlers are kept, loco's reason phrases like
+    // "Bad Request" are replaced
+    let error = error
+        .and_then(|error| serde_json::from_value(error).ok())
+        .unwrap_or_else(|| {
+            if status == StatusCode::BAD_REQUEST && errors.is_some() {
+                ErrorCode::ValidationFailed
+            } else {
+                ErrorCode::from_status(status)
+            }
+        });
+
+    ErrorDetail {
+        error,
+        description,
+        errors,
+    }
+}
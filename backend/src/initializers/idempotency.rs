@@ -0,0 +1,206 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{FromRequestParts, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::IntoResponse,
+    Router as AxumRouter,
+};
+use chrono::{Duration, Utc};
+use loco_rs::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    controllers::{bad_request, conflict, unprocessable},
+    models::{
+        idempotency_keys::{self, Claim},
+        users,
+    },
+    settings::Settings,
+};
+
+/// Request header carrying the client's idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Response header set when a stored response is replayed.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Routes whose `POST` requests honor `Idempotency-Key`, with `{…}` matching
+/// any path segment. Responses are stored as they are, so routes returning
+/// secrets or tokens, such as the auth routes and wishlist share links, must
+/// not be listed.
+const IDEMPOTENT_ROUTES: &[&str] = &[
+    "/api/cart",
+    "/api/orders",
+    "/api/orders/{id}/cancel",
+    "/api/products/{product_id}/reviews",
+    "/api/products/{product_id}/reviews/{user_id}/report",
+    "/api/products/{product_id}/wishlist",
+    "/api/wishlists",
+    "/api/wishlists/{id}/items",
+];
+
+/// Makes authenticated `POST` requests to [`IDEMPOTENT_ROUTES`] that carry an
+/// `Idempotency-Key` header safe to retry: the first request with a key is
+/// processed and its response stored, and later requests with the same key
+/// get that response back instead of being processed again.
+pub struct IdempotencyInitializer;
+
+/// The claim of the request being processed, available to handlers as a
+/// request extension. Handlers store their response with
+/// [`idempotency_keys::Model::complete`] in the transaction of their writes,
+/// so a crash after committing can't lead to the request being processed
+/// twice.
+#[derive(Clone)]
+pub struct IdempotencyClaim(pub idempotency_keys::Model);
+
+#[async_trait]
+impl Initializer for IdempotencyInitializer {
+    fn name(&self) -> String {
+        "idempotency".to_string()
+    }
+
+    async fn after_routes(&self, router: AxumRouter, ctx: &AppContext) -> Result<AxumRouter> {
+        Ok(router.layer(middleware::from_fn_with_state(ctx.clone(), idempotency)))
+    }
+}
+
+async fn idempotency(State(ctx): State<AppContext>, request: Request, next: Next) -> Response {
+    handle(&ctx, request, next)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+fn is_idempotent_route(path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    IDEMPOTENT_ROUTES.iter().any(|route| {
+        let mut segments = path.split('/');
+        route.split('/').all(|expected| match segments.next() {
+            Some(segment) if expected.starts_with('{') => !segment.is_empty(),
+            Some(segment) => segment == expected,
+            None => false,
+        }) && segments.next().is_none()
+    })
+}
+
+/// Identifies a request by what it does, so a key reused for another request
+/// can be told apart from a retry.
+fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(path);
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+fn replay(record: &idempotency_keys::Model, status: i16) -> Result<Response> {
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .ok_or(Error::InternalServerError)?;
+
+    let mut response = Response::new(Body::from(record.response_body.clone().unwrap_or_default()));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    if let Some(content_type) = record
+        .response_content_type
+        .as_deref()
+        .and_then(|value| HeaderValue::from_str(value).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+    Ok(response)
+}
+
+async fn handle(ctx: &AppContext, request: Request, next: Next) -> Result<Response> {
+    let key = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| value.to_str().unwrap_or_default().trim().to_string());
+    let Some(key) = key
+        .filter(|_| request.method() == Method::POST && is_idempotent_route(request.uri().path()))
+    else {
+        return Ok(next.run(request).await);
+    };
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return bad_request(format!(
+            "Idempotency-Key must be between 1 and {MAX_KEY_LENGTH} characters"
+        ));
+    }
+
+    // Keys are scoped to a user, anonymous requests are processed as usual
+    let (mut parts, body) = request.into_parts();
+    let Ok(auth) = auth::JWTWithUser::<users::Model>::from_request_parts(&mut parts, ctx).await
+    else {
+        return Ok(next.run(Request::from_parts(parts, body)).await);
+    };
+
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return bad_request("Request body is too large");
+    };
+    let fingerprint = fingerprint(&parts.method, parts.uri.path(), &body);
+
+    // The key is held for a while, so a retry arriving in the meantime is
+    // turned away while one that comes after a crash can take over
+    let settings = Settings::from_context(ctx)?;
+    let now = Utc::now();
+    let expired_before = now - Duration::hours(settings.idempotency.retention_hours);
+    let locked_until = now + Duration::seconds(settings.idempotency.lease_seconds);
+    let claim = idempotency_keys::Model::claim(
+        &ctx.db,
+        auth.user.id,
+        &key,
+        &fingerprint,
+        expired_before.into(),
+        locked_until.into(),
+    )
+    .await?;
+
+    let record = match claim {
+        Claim::Existing(record) => {
+            if record.request_fingerprint != fingerprint {
+                return unprocessable("Idempotency-Key was already used for a different request");
+            }
+            return match record.response_status {
+                Some(status) => replay(&record, status),
+                None => conflict("A request with this Idempotency-Key is still being processed"),
+            };
+        }
+        Claim::New(record) => record,
+    };
+
+    parts.extensions.insert(IdempotencyClaim(record.clone()));
+    let request = Request::from_parts(parts, Body::from(body));
+    let response = next.run(request).await;
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|_| Error::InternalServerError)?;
+
+    // Failures on our side are not stored, so the client can retry them.
+    // Responses the handler already stored are kept as they are.
+    if parts.status.is_server_error() {
+        record.release(&ctx.db).await?;
+    } else {
+        record
+            .complete(
+                &ctx.db,
+                i16::try_from(parts.status.as_u16()).unwrap_or(i16::MAX),
+                parts
+                    .headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(ToString::to_string),
+                String::from_utf8_lossy(&body).into_owned(),
+            )
+            .await?;
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
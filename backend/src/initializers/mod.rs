@@ -1 +1,2 @@
-
+pub mod error_envelope;
+pub mod idempotency;
@@ -1,9 +1,15 @@
 pub mod app;
 pub mod controllers;
 pub mod data;
+pub mod documents;
 pub mod initializers;
 pub mod mailers;
 pub mod models;
+pub mod oidc;
+pub mod permissions;
+pub mod registration;
+pub mod settings;
 pub mod tasks;
+pub mod totp;
 pub mod views;
 pub mod workers;
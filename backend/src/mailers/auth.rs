@@ -9,17 +9,26 @@ use crate::models::users;
 static welcome: Dir<'_> = include_dir!("src/mailers/auth/welcome");
 static forgot: Dir<'_> = include_dir!("src/mailers/auth/forgot");
 static magic_link: Dir<'_> = include_dir!("src/mailers/auth/magic_link");
+static unlock: Dir<'_> = include_dir!("src/mailers/auth/unlock");
+static two_factor_setup: Dir<'_> = include_dir!("src/mailers/auth/two_factor_setup");
+static two_factor_enabled: Dir<'_> = include_dir!("src/mailers/auth/two_factor_enabled");
+static account_deletion: Dir<'_> = include_dir!("src/mailers/auth/account_deletion");
 
 #[allow(clippy::module_name_repetitions)]
 pub struct AuthMailer {}
 impl Mailer for AuthMailer {}
 impl AuthMailer {
-    /// Sending welcome email the the given user
+    /// Sending welcome email the the given user, with the link to verify their
+    /// email address
     ///
     /// # Errors
     ///
     /// When email sending is failed
-    pub async fn send_welcome(ctx: &AppContext, user: &users::Model) -> Result<()> {
+    pub async fn send_welcome(
+        ctx: &AppContext,
+        user: &users::Model,
+        verify_token: &str,
+    ) -> Result<()> {
         Self::mail_template(
             ctx,
             &welcome,
@@ -27,7 +36,7 @@ impl AuthMailer {
                 to: user.email.to_string(),
                 locals: json!({
                   "name": user.name,
-                  "verifyToken": user.email_verification_token,
+                  "verifyToken": verify_token,
                   "domain": ctx.config.server.full_url()
                 }),
                 ..Default::default()
@@ -43,7 +52,11 @@ impl AuthMailer {
     /// # Errors
     ///
     /// When email sending is failed
-    pub async fn forgot_password(ctx: &AppContext, user: &users::Model) -> Result<()> {
+    pub async fn forgot_password(
+        ctx: &AppContext,
+        user: &users::Model,
+        reset_token: &str,
+    ) -> Result<()> {
         Self::mail_template(
             ctx,
             &forgot,
@@ -51,7 +64,7 @@ impl AuthMailer {
                 to: user.email.to_string(),
                 locals: json!({
                   "name": user.name,
-                  "resetToken": user.reset_token,
+                  "resetToken": reset_token,
                   "domain": ctx.config.server.full_url()
                 }),
                 ..Default::default()
@@ -67,7 +80,7 @@ impl AuthMailer {
     /// # Errors
     ///
     /// When email sending is failed
-    pub async fn send_magic_link(ctx: &AppContext, user: &users::Model) -> Result<()> {
+    pub async fn send_magic_link(ctx: &AppContext, user: &users::Model, token: &str) -> Result<()> {
         Self::mail_template(
             ctx,
             &magic_link,
@@ -75,9 +88,7 @@ impl AuthMailer {
                 to: user.email.to_string(),
                 locals: json!({
                   "name": user.name,
-                  "token": user.magic_link_token.clone().ok_or_else(|| Error::string(
-                            "the user model not contains magic link token",
-                    ))?,
+                  "token": token,
                   "host": ctx.config.server.full_url()
                 }),
                 ..Default::default()
@@ -87,4 +98,121 @@ impl AuthMailer {
 
         Ok(())
     }
+
+    /// Sends the unlock link to a user whose account was locked after
+    /// repeated failed logins.
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_unlock(
+        ctx: &AppContext,
+        user: &users::Model,
+        unlock_token: &str,
+        lockout_minutes: i64,
+    ) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &unlock,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "unlockToken": unlock_token,
+                  "lockoutMinutes": lockout_minutes,
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends staff who have to use two-factor authentication the link to set
+    /// it up with.
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_two_factor_setup(
+        ctx: &AppContext,
+        user: &users::Model,
+        enrollment_token: &str,
+        ttl_minutes: i64,
+    ) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &two_factor_setup,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "enrollmentToken": enrollment_token,
+                  "ttlMinutes": ttl_minutes,
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Tells a user that two-factor authentication was turned on for their
+    /// account, in case it wasn't them.
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_two_factor_enabled(ctx: &AppContext, user: &users::Model) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &two_factor_enabled,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends the link a user confirms deleting their account with, for
+    /// accounts without a password they know, such as ones registered through
+    /// an identity provider.
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_deletion_link(
+        ctx: &AppContext,
+        user: &users::Model,
+        deletion_token: &str,
+        ttl_minutes: i64,
+    ) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &account_deletion,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "deletionToken": deletion_token,
+                  "ttlMinutes": ttl_minutes,
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
 }
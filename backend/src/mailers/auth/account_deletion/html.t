@@ -0,0 +1,11 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  You asked to delete your account. Confirm it with the link below, which is valid for {{ttlMinutes}} minutes:
+  <a href="{{domain}}/delete-account#{{deletionToken}}">Delete Your Account</a>
+  If you did not ask for this, ignore this email and change your password.
+  <p>Best regards,<br>The Loco Team</p>
+</body>
+
+</html>
@@ -0,0 +1 @@
+Confirm deleting your account
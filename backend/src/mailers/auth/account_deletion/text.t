@@ -0,0 +1,5 @@
+You asked to delete your account. Confirm it with this link, which is valid for {{ttlMinutes}} minutes:
+
+{{domain}}/delete-account#{{deletionToken}}
+
+If you did not ask for this, ignore this email and change your password.
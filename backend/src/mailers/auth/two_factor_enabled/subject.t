@@ -0,0 +1 @@
+Two-factor authentication was turned on
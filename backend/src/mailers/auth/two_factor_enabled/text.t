@@ -0,0 +1,4 @@
+Two-factor authentication was just turned on for your account. Logging in now takes a code from your
+authenticator app.
+
+If this was not you, contact us right away.
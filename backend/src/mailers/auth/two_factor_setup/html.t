@@ -0,0 +1,12 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  Your account needs two-factor authentication. Set it up with an authenticator app from the link below,
+  which is valid for {{ttlMinutes}} minutes:
+  <a href="{{domain}}/api/auth/2fa/enroll/{{enrollmentToken}}">Set Up Two-Factor Authentication</a>
+  If you did not just try to log in, change your password right away.
+  <p>Best regards,<br>The Loco Team</p>
+</body>
+
+</html>
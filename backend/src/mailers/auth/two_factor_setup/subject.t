@@ -0,0 +1 @@
+Set up two-factor authentication
@@ -0,0 +1,6 @@
+Your account needs two-factor authentication. Set it up with an authenticator app from this link,
+which is valid for {{ttlMinutes}} minutes:
+
+{{domain}}/api/auth/2fa/enroll/{{enrollmentToken}}
+
+If you did not just try to log in, change your password right away.
@@ -0,0 +1,12 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  We locked your account after several failed attempts to log in with your password.
+  It unlocks by itself in {{lockoutMinutes}} minutes, or right away with the link below:
+  <a href="{{domain}}/api/auth/unlock/{{unlockToken}}">Unlock Your Account</a>
+  If these attempts were not you, consider changing your password once you are back in.
+  <p>Best regards,<br>The Loco Team</p>
+</body>
+
+</html>
@@ -0,0 +1 @@
+Your account was locked
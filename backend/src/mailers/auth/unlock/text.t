@@ -0,0 +1,4 @@
+Your account was locked after several failed attempts to log in with your password.
+It unlocks by itself in {{lockoutMinutes}} minutes, or right away with this link:
+
+{{domain}}/api/auth/unlock/{{unlockToken}}
@@ -0,0 +1,57 @@
+// cart mailer
+#![allow(non_upper_case_globals)]
+
+use loco_rs::prelude::*;
+use serde_json::json;
+
+use crate::models::{product_variants, products, users};
+
+static reminder: Dir<'_> = include_dir!("src/mailers/cart/reminder");
+
+#[allow(clippy::module_name_repetitions)]
+pub struct CartMailer {}
+impl Mailer for CartMailer {}
+impl CartMailer {
+    /// Reminds the user of the items they left in their cart, at today's
+    /// prices
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_reminder(
+        ctx: &AppContext,
+        user: &users::Model,
+        items: &[(i32, product_variants::Model, products::Model)],
+    ) -> Result<()> {
+        let items = items
+            .iter()
+            .map(|(quantity, variant, product)| {
+                json!({
+                  "name": product.name,
+                  "slug": product.slug,
+                  "size": variant.size,
+                  "quantity": quantity,
+                  "price": format!("{:.2}", product.effective_price()),
+                  "imageUrl": product.image_url,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Self::mail_template(
+            ctx,
+            &reminder,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "items": items,
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+}
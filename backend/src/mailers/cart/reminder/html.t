@@ -0,0 +1,22 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  You still have these in your cart:
+  <table>
+    {% for item in items %}
+    <tr>
+      <td>{% if item.imageUrl %}<img src="{{item.imageUrl}}" alt="{{item.name}}" width="80">{% endif %}</td>
+      <td>
+        <a href="{{domain}}/products/{{item.slug}}">{{item.name}}</a>{% if item.size %} (size {{item.size}}){% endif %}
+        &times; {{item.quantity}}
+      </td>
+      <td><strong>{{item.price}}</strong></td>
+    </tr>
+    {% endfor %}
+  </table>
+  <a href="{{domain}}/cart">Go to your cart</a>
+  Best regards,<br>The Shoes Store Team</br>
+</body>
+
+</html>
@@ -0,0 +1 @@
+You left something in your cart
@@ -0,0 +1,8 @@
+Hey {{name}},
+
+You still have these in your cart:
+{% for item in items %}
+- {{item.name}}{% if item.size %} (size {{item.size}}){% endif %} x {{item.quantity}}: {{item.price}}
+{%- endfor %}
+
+{{domain}}/cart
@@ -1 +1,3 @@
 pub mod auth;
+pub mod cart;
+pub mod wishlist;
@@ -0,0 +1,46 @@
+// wishlist mailer
+#![allow(non_upper_case_globals)]
+
+use loco_rs::prelude::*;
+use serde_json::json;
+
+use crate::models::{products, users};
+
+static price_drop: Dir<'_> = include_dir!("src/mailers/wishlist/price_drop");
+
+#[allow(clippy::module_name_repetitions)]
+pub struct WishlistMailer {}
+impl Mailer for WishlistMailer {}
+impl WishlistMailer {
+    /// Tells the user that a product on their wishlist got cheaper
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_price_drop(
+        ctx: &AppContext,
+        user: &users::Model,
+        product: &products::Model,
+        old_price: f64,
+    ) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &price_drop,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "productName": product.name,
+                  "productSlug": product.slug,
+                  "oldPrice": format!("{old_price:.2}"),
+                  "newPrice": format!("{:.2}", product.effective_price()),
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  Good news! <strong>{{productName}}</strong> from your wishlist is now
+  <strong>{{newPrice}}</strong> (was {{oldPrice}}).
+  <a href="{{domain}}/products/{{productSlug}}">Take a look</a>
+  You can turn off price alerts in your account settings.
+  Best regards,<br>The Shoes Store Team</br>
+</body>
+
+</html>
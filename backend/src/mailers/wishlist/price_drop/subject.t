@@ -0,0 +1 @@
+{{productName}} just dropped in price
@@ -0,0 +1,7 @@
+Hey {{name}},
+
+{{productName}} from your wishlist is now {{newPrice}} (was {{oldPrice}}).
+
+{{domain}}/products/{{productSlug}}
+
+You can turn off price alerts in your account settings.
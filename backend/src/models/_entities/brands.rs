@@ -16,6 +16,10 @@ pub struct Model {
     pub name: String,
     #[sea_orm(column_type = "Text", nullable)]
     pub description: Option<String>,
+    #[sea_orm(unique)]
+    pub slug: String,
+    pub logo_url: Option<String>,
+    pub website_url: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
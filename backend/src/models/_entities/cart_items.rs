@@ -4,7 +4,7 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(
-    Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, utoipa :: ToSchema,
+    Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, utoipa :: ToSchema,
 )]
 #[schema(as = models::_entities::cart_items::Model)]
 #[sea_orm(table_name = "cart_items")]
@@ -14,12 +14,23 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub quantity: Option<i32>,
-    pub user_id: i32,
+    pub user_id: Option<i32>,
     pub product_variant_id: i32,
+    pub guest_cart_id: Option<i32>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub unit_price: Option<f64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::guest_carts::Entity",
+        from = "Column::GuestCartId",
+        to = "super::guest_carts::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    GuestCarts,
     #[sea_orm(
         belongs_to = "super::product_variants::Entity",
         from = "Column::ProductVariantId",
@@ -38,6 +49,12 @@ pub enum Relation {
     Users,
 }
 
+impl Related<super::guest_carts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GuestCarts.def()
+    }
+}
+
 impl Related<super::product_variants::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ProductVariants.def()
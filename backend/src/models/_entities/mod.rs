@@ -2,14 +2,28 @@
 
 pub mod prelude;
 
+pub mod audit_events;
+pub mod auth_throttles;
 pub mod brands;
 pub mod cart_items;
+pub mod cart_reminders;
 pub mod categories;
+pub mod guest_carts;
+pub mod idempotency_keys;
+pub mod invoice_sequences;
+pub mod oauth_states;
 pub mod order_items;
 pub mod orders;
 pub mod product_variants;
 pub mod products;
+pub mod review_reports;
+pub mod review_votes;
 pub mod reviews;
 pub mod sea_orm_active_enums;
+pub mod two_factor_challenges;
+pub mod user_identities;
+pub mod user_recovery_codes;
+pub mod user_sessions;
 pub mod users;
+pub mod wishlist_lists;
 pub mod wishlists;
@@ -13,12 +13,15 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
-    pub user_id: i32,
+    pub user_id: Option<i32>,
     pub status: OrderStatus,
     pub amount: Decimal,
     pub payment_method: PaymentMethod,
     #[sea_orm(column_type = "Text", nullable)]
     pub shipping_address: Option<String>,
+    #[sea_orm(unique)]
+    pub invoice_number: Option<i32>,
+    pub invoiced_at: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -32,7 +35,7 @@ pub enum Relation {
         from = "Column::UserId",
         to = "super::users::Column::Id",
         on_update = "Cascade",
-        on_delete = "Cascade"
+        on_delete = "SetNull"
     )]
     Users,
 }
@@ -1,12 +1,26 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
+pub use super::audit_events::Entity as AuditEvents;
+pub use super::auth_throttles::Entity as AuthThrottles;
 pub use super::brands::Entity as Brands;
 pub use super::cart_items::Entity as CartItems;
+pub use super::cart_reminders::Entity as CartReminders;
 pub use super::categories::Entity as Categories;
+pub use super::guest_carts::Entity as GuestCarts;
+pub use super::idempotency_keys::Entity as IdempotencyKeys;
+pub use super::invoice_sequences::Entity as InvoiceSequences;
+pub use super::oauth_states::Entity as OauthStates;
 pub use super::order_items::Entity as OrderItems;
 pub use super::orders::Entity as Orders;
 pub use super::product_variants::Entity as ProductVariants;
 pub use super::products::Entity as Products;
+pub use super::review_reports::Entity as ReviewReports;
+pub use super::review_votes::Entity as ReviewVotes;
 pub use super::reviews::Entity as Reviews;
+pub use super::two_factor_challenges::Entity as TwoFactorChallenges;
+pub use super::user_identities::Entity as UserIdentities;
+pub use super::user_recovery_codes::Entity as UserRecoveryCodes;
+pub use super::user_sessions::Entity as UserSessions;
 pub use super::users::Entity as Users;
+pub use super::wishlist_lists::Entity as WishlistLists;
 pub use super::wishlists::Entity as Wishlists;
@@ -35,6 +35,8 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Products,
+    #[sea_orm(has_many = "super::wishlists::Entity")]
+    Wishlists,
 }
 
 impl Related<super::cart_items::Entity> for Entity {
@@ -54,3 +56,9 @@ impl Related<super::products::Entity> for Entity {
         Relation::Products.def()
     }
 }
+
+impl Related<super::wishlists::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wishlists.def()
+    }
+}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
+use super::sea_orm_active_enums::ReviewStatus;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +17,9 @@ pub struct Model {
     pub rating: i32,
     #[sea_orm(column_type = "Text", nullable)]
     pub content: Option<String>,
+    pub status: ReviewStatus,
+    pub helpful_count: i32,
+    pub unhelpful_count: i32,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -30,6 +34,10 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Products,
+    #[sea_orm(has_many = "super::review_reports::Entity")]
+    ReviewReports,
+    #[sea_orm(has_many = "super::review_votes::Entity")]
+    ReviewVotes,
     #[sea_orm(
         belongs_to = "super::users::Entity",
         from = "Column::UserId",
@@ -46,6 +54,18 @@ impl Related<super::products::Entity> for Entity {
     }
 }
 
+impl Related<super::review_reports::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReviewReports.def()
+    }
+}
+
+impl Related<super::review_votes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReviewVotes.def()
+    }
+}
+
 impl Related<super::users::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Users.def()
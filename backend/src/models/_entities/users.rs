@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 
+use super::sea_orm_active_enums::UserRole;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -27,40 +28,133 @@ pub struct Model {
     pub email_verified_at: Option<DateTimeWithTimeZone>,
     pub magic_link_token: Option<String>,
     pub magic_link_expiration: Option<DateTimeWithTimeZone>,
-    pub is_staff: bool,
     pub is_active: bool,
+    pub price_alerts_enabled: bool,
+    pub failed_login_count: i32,
+    pub last_failed_login_at: Option<DateTimeWithTimeZone>,
+    pub locked_until: Option<DateTimeWithTimeZone>,
+    pub unlock_token: Option<String>,
+    pub unlock_sent_at: Option<DateTimeWithTimeZone>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTimeWithTimeZone>,
+    pub totp_last_step: Option<i64>,
+    pub role: Option<UserRole>,
+    pub totp_enrollment_token: Option<String>,
+    pub totp_enrollment_sent_at: Option<DateTimeWithTimeZone>,
+    pub deletion_token: Option<String>,
+    pub deletion_sent_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::audit_events::Entity")]
+    AuditEvents,
     #[sea_orm(has_many = "super::cart_items::Entity")]
     CartItems,
+    #[sea_orm(has_many = "super::cart_reminders::Entity")]
+    CartReminders,
+    #[sea_orm(has_many = "super::idempotency_keys::Entity")]
+    IdempotencyKeys,
     #[sea_orm(has_many = "super::orders::Entity")]
     Orders,
+    #[sea_orm(has_many = "super::review_reports::Entity")]
+    ReviewReports,
+    #[sea_orm(has_many = "super::review_votes::Entity")]
+    ReviewVotes,
     #[sea_orm(has_many = "super::reviews::Entity")]
     Reviews,
+    #[sea_orm(has_many = "super::two_factor_challenges::Entity")]
+    TwoFactorChallenges,
+    #[sea_orm(has_many = "super::user_identities::Entity")]
+    UserIdentities,
+    #[sea_orm(has_many = "super::user_recovery_codes::Entity")]
+    UserRecoveryCodes,
+    #[sea_orm(has_many = "super::user_sessions::Entity")]
+    UserSessions,
+    #[sea_orm(has_many = "super::wishlist_lists::Entity")]
+    WishlistLists,
     #[sea_orm(has_many = "super::wishlists::Entity")]
     Wishlists,
 }
 
+impl Related<super::audit_events::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditEvents.def()
+    }
+}
+
 impl Related<super::cart_items::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::CartItems.def()
     }
 }
 
+impl Related<super::cart_reminders::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CartReminders.def()
+    }
+}
+
+impl Related<super::idempotency_keys::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::IdempotencyKeys.def()
+    }
+}
+
 impl Related<super::orders::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Orders.def()
     }
 }
 
+impl Related<super::review_reports::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReviewReports.def()
+    }
+}
+
+impl Related<super::review_votes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReviewVotes.def()
+    }
+}
+
 impl Related<super::reviews::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Reviews.def()
     }
 }
 
+impl Related<super::two_factor_challenges::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TwoFactorChallenges.def()
+    }
+}
+
+impl Related<super::user_identities::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentities.def()
+    }
+}
+
+impl Related<super::user_recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserRecoveryCodes.def()
+    }
+}
+
+impl Related<super::user_sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSessions.def()
+    }
+}
+
+impl Related<super::wishlist_lists::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WishlistLists.def()
+    }
+}
+
 impl Related<super::wishlists::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Wishlists.def()
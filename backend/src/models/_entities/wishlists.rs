@@ -4,7 +4,7 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(
-    Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, utoipa :: ToSchema,
+    Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, utoipa :: ToSchema,
 )]
 #[schema(as = models::_entities::wishlists::Model)]
 #[sea_orm(table_name = "wishlists")]
@@ -15,10 +15,23 @@ pub struct Model {
     pub id: i32,
     pub user_id: i32,
     pub product_id: i32,
+    pub wishlist_list_id: i32,
+    pub product_variant_id: Option<i32>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub saved_price: Option<f64>,
+    pub price_alert_sent_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::product_variants::Entity",
+        from = "Column::ProductVariantId",
+        to = "super::product_variants::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
+    ProductVariants,
     #[sea_orm(
         belongs_to = "super::products::Entity",
         from = "Column::ProductId",
@@ -35,6 +48,20 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Users,
+    #[sea_orm(
+        belongs_to = "super::wishlist_lists::Entity",
+        from = "Column::WishlistListId",
+        to = "super::wishlist_lists::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    WishlistLists,
+}
+
+impl Related<super::product_variants::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ProductVariants.def()
+    }
 }
 
 impl Related<super::products::Entity> for Entity {
@@ -48,3 +75,9 @@ impl Related<super::users::Entity> for Entity {
         Relation::Users.def()
     }
 }
+
+impl Related<super::wishlist_lists::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WishlistLists.def()
+    }
+}
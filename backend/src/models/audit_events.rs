@@ -0,0 +1,177 @@
+pub use super::_entities::audit_events::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{entity::prelude::*, Set};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::_entities::users;
+
+pub type AuditEvents = Entity;
+
+/// An action to record in the audit log.
+#[derive(Debug, Default)]
+pub struct NewEvent {
+    pub actor_id: Option<i32>,
+    /// What was done, e.g. `update` or `login`.
+    pub action: &'static str,
+    /// What it was done to, e.g. `product` or `user`.
+    pub entity_type: &'static str,
+    pub entity_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> ModelResult<Value> {
+    serde_json::to_value(value).map_err(ModelError::wrap)
+}
+
+/// The fields that differ between two versions of an entity, as they were
+/// and as they became. `updated_at` changes on every save and is left out.
+fn diff(before: Value, after: Value) -> Option<(Value, Value)> {
+    let (Value::Object(before), Value::Object(mut after)) = (before, after) else {
+        return None;
+    };
+
+    let mut old = Map::new();
+    let mut new = Map::new();
+    for (field, was) in before {
+        let now = after.remove(&field).unwrap_or(Value::Null);
+        if field != "updated_at" && was != now {
+            old.insert(field.clone(), was);
+            new.insert(field, now);
+        }
+    }
+    for (field, now) in after {
+        old.insert(field.clone(), Value::Null);
+        new.insert(field, now);
+    }
+
+    (!new.is_empty()).then_some((Value::Object(old), Value::Object(new)))
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Records an action in the audit log.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn record<C: ConnectionTrait>(db: &C, event: NewEvent) -> ModelResult<Self> {
+        Ok(ActiveModel {
+            actor_id: Set(event.actor_id),
+            action: Set(event.action.to_string()),
+            entity_type: Set(event.entity_type.to_string()),
+            entity_id: Set(event.entity_id),
+            before: Set(event.before),
+            after: Set(event.after),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Records that `actor` created an entity.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn created<C: ConnectionTrait, T: Serialize>(
+        db: &C,
+        actor: &users::Model,
+        entity_type: &'static str,
+        entity_id: impl ToString,
+        after: &T,
+    ) -> ModelResult<Self> {
+        Self::record(
+            db,
+            NewEvent {
+                actor_id: Some(actor.id),
+                action: "create",
+                entity_type,
+                entity_id: entity_id.to_string(),
+                after: Some(to_json(after)?),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Records that `actor` changed an entity, with the fields that changed.
+    /// Nothing is recorded when no field did.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn changed<C: ConnectionTrait, T: Serialize>(
+        db: &C,
+        actor: &users::Model,
+        action: &'static str,
+        entity_type: &'static str,
+        entity_id: impl ToString,
+        before: &T,
+        after: &T,
+    ) -> ModelResult<Option<Self>> {
+        let Some((before, after)) = diff(to_json(before)?, to_json(after)?) else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            Self::record(
+                db,
+                NewEvent {
+                    actor_id: Some(actor.id),
+                    action,
+                    entity_type,
+                    entity_id: entity_id.to_string(),
+                    before: Some(before),
+                    after: Some(after),
+                },
+            )
+            .await?,
+        ))
+    }
+
+    /// Records that `actor` deleted an entity, with what it was.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn deleted<C: ConnectionTrait, T: Serialize>(
+        db: &C,
+        actor: &users::Model,
+        entity_type: &'static str,
+        entity_id: impl ToString,
+        before: &T,
+    ) -> ModelResult<Self> {
+        Self::record(
+            db,
+            NewEvent {
+                actor_id: Some(actor.id),
+                action: "delete",
+                entity_type,
+                entity_id: entity_id.to_string(),
+                before: Some(to_json(before)?),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,122 @@
+pub use super::_entities::auth_throttles::{ActiveModel, Column, Entity, Model};
+use chrono::{Duration, Utc};
+use loco_rs::model::ModelResult;
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Expr, OnConflict},
+    Set,
+};
+
+use crate::settings::LoginSettings;
+pub type AuthThrottles = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Counts a request against `key`, e.g. a client IP, and returns the
+    /// seconds to wait if it was made more than `limit` times in the current
+    /// window. The first request after a window has passed starts a new one.
+    ///
+    /// The count is incremented in a single upsert, so concurrent requests
+    /// can't slip past the limit.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn hit<C: ConnectionTrait>(
+        db: &C,
+        key: &str,
+        limit: i32,
+        window: Duration,
+    ) -> ModelResult<Option<u64>> {
+        let now = Utc::now();
+        let expired = Expr::col((Entity, Column::WindowStartedAt)).lt(now - window);
+
+        let throttle = Entity::insert(ActiveModel {
+            key: Set(key.to_string()),
+            hits: Set(1),
+            window_started_at: Set(now.into()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(Column::Key)
+                .value(
+                    Column::Hits,
+                    Expr::case(expired.clone(), 1)
+                        .finally(Expr::col((Entity, Column::Hits)).add(1)),
+                )
+                .value(
+                    Column::WindowStartedAt,
+                    Expr::case(expired, now).finally(Expr::col((Entity, Column::WindowStartedAt))),
+                )
+                .value(Column::UpdatedAt, Expr::value(now))
+                .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await?;
+
+        if throttle.hits <= limit {
+            return Ok(None);
+        }
+        let wait = (throttle.window_started_at + window)
+            .signed_duration_since(now)
+            .num_milliseconds();
+        Ok(Some(
+            u64::try_from(wait)
+                .unwrap_or_default()
+                .div_ceil(1000)
+                .max(1),
+        ))
+    }
+
+    /// Seconds to wait before another password may be tried when the failed
+    /// attempts are counted against `key` with [`Model::hit`], delayed and
+    /// locked out like the attempts on an account.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn login_wait<C: ConnectionTrait>(
+        db: &C,
+        key: &str,
+        settings: &LoginSettings,
+        window: Duration,
+    ) -> ModelResult<Option<u64>> {
+        let now = Utc::now();
+        let Some(throttle) = Entity::find()
+            .filter(Column::Key.eq(key))
+            .filter(Column::WindowStartedAt.gte(now - window))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(delay) = settings.failure_delay(throttle.hits) else {
+            return Ok(None);
+        };
+
+        let wait = (throttle.updated_at + delay)
+            .signed_duration_since(now)
+            .num_milliseconds();
+        Ok((wait > 0).then(|| u64::try_from(wait).unwrap_or_default().div_ceil(1000)))
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,5 +1,8 @@
-pub use super::_entities::brands::{ActiveModel, Entity, Model};
+pub use super::_entities::brands::{ActiveModel, Column, Entity, Model};
 use sea_orm::entity::prelude::*;
+
+use super::_macros::impl_find_by_slug;
+
 pub type Brands = Entity;
 
 #[async_trait::async_trait]
@@ -20,6 +23,7 @@ impl ActiveModelBehavior for ActiveModel {
 
 // implement your read-oriented logic here
 impl Model {}
+impl_find_by_slug!();
 
 // implement your write-oriented logic here
 impl ActiveModel {}
@@ -1,10 +1,23 @@
-pub use super::_entities::cart_items::{ActiveModel, Entity, Model};
-use loco_rs::prelude::Validatable;
-use sea_orm::entity::prelude::*;
+pub use super::_entities::cart_items::{ActiveModel, Column, Entity, Model};
+use loco_rs::{model::ModelResult, prelude::Validatable};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Alias, Expr, OnConflict},
+};
 use serde::Deserialize;
 use validator::Validate;
+
+use super::_entities::product_variants;
+use crate::settings::CartSettings;
 pub type CartItems = Entity;
 
+/// The most units of a variant a cart line may hold: what is in stock, up to
+/// the per-line limit.
+#[must_use]
+pub fn max_quantity(variant: &product_variants::Model, settings: &CartSettings) -> i32 {
+    variant.stock.min(settings.max_quantity_per_line)
+}
+
 #[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
@@ -36,7 +49,38 @@ impl Validatable for ActiveModel {
 }
 
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// Adds `item` to the cart of its owner, whose column is `owner`. When the
+    /// variant is already in that cart, its quantity is increased by the
+    /// item's instead and its price updated.
+    ///
+    /// This is a single upsert, so concurrent adds of the same variant each
+    /// count. The line stays locked until the transaction ends, so callers
+    /// checking the resulting quantity should run it in one.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn add<C: ConnectionTrait>(
+        db: &C,
+        item: ActiveModel,
+        owner: Column,
+    ) -> ModelResult<Self> {
+        let added = Expr::col((Alias::new("excluded"), Column::Quantity));
+        Ok(Entity::insert(item)
+            .on_conflict(
+                OnConflict::columns([owner, Column::ProductVariantId])
+                    .value(
+                        Column::Quantity,
+                        Expr::col((Entity, Column::Quantity)).if_null(0).add(added),
+                    )
+                    .update_column(Column::UnitPrice)
+                    .value(Column::UpdatedAt, Expr::current_timestamp())
+                    .to_owned(),
+            )
+            .exec_with_returning(db)
+            .await?)
+    }
+}
 
 // implement your write-oriented logic here
 impl ActiveModel {}
@@ -0,0 +1,76 @@
+pub use super::_entities::cart_reminders::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, QueryOrder, Set};
+pub type CartReminders = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Reminders already sent about a user's cart as it was last changed at
+    /// `cart_updated_at`, most recent first.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn for_cart<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        cart_updated_at: DateTimeWithTimeZone,
+    ) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::CartUpdatedAt.eq(cart_updated_at))
+            .order_by_desc(Column::Sequence)
+            .all(db)
+            .await?)
+    }
+
+    /// Records that reminder number `sequence` about the cart is being sent.
+    /// Returns `false` when it was already recorded, so the caller must not
+    /// send it again.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn claim<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        cart_updated_at: DateTimeWithTimeZone,
+        sequence: i32,
+    ) -> ModelResult<bool> {
+        let inserted = Entity::insert(ActiveModel {
+            user_id: Set(user_id),
+            cart_updated_at: Set(cart_updated_at),
+            sequence: Set(sequence),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([Column::UserId, Column::CartUpdatedAt, Column::Sequence])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+        Ok(inserted > 0)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
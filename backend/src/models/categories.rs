@@ -1,6 +1,10 @@
+use std::collections::{HashMap, HashSet};
+
 pub use super::_entities::categories::{ActiveModel, Column, Entity, Model};
 use super::_macros::impl_find_by_slug;
-use sea_orm::entity::prelude::*;
+use crate::models::_entities::products;
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, QueryOrder, QuerySelect};
 pub type Categories = Entity;
 
 #[async_trait::async_trait]
@@ -20,7 +24,82 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// Returns the chain of categories from the root down to `id`, inclusive.
+    /// Empty if the category does not exist.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn ancestors<C: ConnectionTrait>(db: &C, id: i32) -> ModelResult<Vec<Self>> {
+        let mut by_id = Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect::<HashMap<_, _>>();
+
+        let mut chain = Vec::new();
+        let mut seen = HashSet::new();
+        let mut current = Some(id);
+        // `seen` guards against cycles that predate cycle checks
+        while let Some(id) = current.filter(|id| seen.insert(*id)) {
+            let Some(category) = by_id.remove(&id) else {
+                break;
+            };
+            current = category.parent_id;
+            chain.push(category);
+        }
+        chain.reverse();
+
+        Ok(chain)
+    }
+
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn children<C: ConnectionTrait>(&self, db: &C) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(Column::ParentId.eq(self.id))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Whether moving category `id` under `parent_id` would make it its own
+    /// ancestor.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn would_create_cycle<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+        parent_id: i32,
+    ) -> ModelResult<bool> {
+        Ok(Self::ancestors(db, parent_id)
+            .await?
+            .iter()
+            .any(|category| category.id == id))
+    }
+
+    /// Counts the products filed directly under each category.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn direct_product_counts<C: ConnectionTrait>(
+        db: &C,
+    ) -> ModelResult<HashMap<i32, i64>> {
+        Ok(products::Entity::find()
+            .select_only()
+            .column(products::Column::CategoryId)
+            .column_as(products::Column::Id.count(), "count")
+            .filter(products::Column::CategoryId.is_not_null())
+            .group_by(products::Column::CategoryId)
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect())
+    }
+}
 impl_find_by_slug!();
 
 // implement your write-oriented logic here
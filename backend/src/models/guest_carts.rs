@@ -0,0 +1,183 @@
+pub use super::_entities::guest_carts::{ActiveModel, Column, Entity, Model};
+use chrono::{Duration, Utc};
+use loco_rs::{auth::jwt, model::ModelResult};
+use sea_orm::{entity::prelude::*, ActiveValue, IntoActiveModel, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map};
+
+use crate::{
+    models::{
+        _entities::{cart_items, product_variants},
+        cart_items::max_quantity,
+    },
+    settings::CartSettings,
+};
+pub type GuestCarts = Entity;
+
+/// A line whose quantity was lowered when a guest cart was merged, because
+/// the combined quantity was more than the cart may hold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct MergeReduction {
+    pub product_variant_id: i32,
+    pub requested: i32,
+    pub available: i32,
+}
+
+/// Claim marking a JWT as a guest cart token rather than a login token.
+const GUEST_CART_CLAIM: &str = "guest_cart";
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            let mut this = self;
+            this.pid = ActiveValue::Set(Uuid::new_v4());
+            Ok(this)
+        } else if self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Finds the guest cart a signed cart token was issued for, as long as
+    /// neither the token nor the cart has expired.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_by_token<C: ConnectionTrait>(
+        db: &C,
+        secret: &str,
+        token: &str,
+    ) -> ModelResult<Option<Self>> {
+        let Ok(data) = jwt::JWT::new(secret).validate(token) else {
+            return Ok(None);
+        };
+        if data.claims.claims.get(GUEST_CART_CLAIM) != Some(&json!(true)) {
+            return Ok(None);
+        }
+        let Ok(pid) = Uuid::parse_str(&data.claims.pid) else {
+            return Ok(None);
+        };
+
+        Ok(Entity::find()
+            .filter(Column::Pid.eq(pid))
+            .filter(Column::ExpiresAt.gt(Utc::now()))
+            .one(db)
+            .await?)
+    }
+
+    /// Creates a signed token identifying this guest cart, valid until the
+    /// cart expires.
+    ///
+    /// # Errors
+    /// When the token could not be signed
+    pub fn generate_token(&self, secret: &str) -> ModelResult<String> {
+        let expiration =
+            u64::try_from((self.expires_at.with_timezone(&Utc) - Utc::now()).num_seconds())
+                .unwrap_or_default();
+        let mut claims = Map::new();
+        claims.insert(GUEST_CART_CLAIM.to_string(), json!(true));
+
+        Ok(jwt::JWT::new(secret).generate_token(expiration, self.pid.to_string(), claims)?)
+    }
+
+    /// Creates an empty guest cart that expires after the given number of
+    /// days.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn create<C: ConnectionTrait>(db: &C, ttl_days: i64) -> ModelResult<Self> {
+        Ok(ActiveModel {
+            expires_at: ActiveValue::Set((Utc::now() + Duration::days(ttl_days)).into()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Moves every line of this guest cart into the user's cart and deletes
+    /// the guest cart. Lines for a variant the user already has in their cart
+    /// are combined by adding up the quantities, as far as stock and the
+    /// per-line limit allow; the lines that were lowered are returned.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn merge_into(
+        &self,
+        db: &DatabaseConnection,
+        user_id: i32,
+        settings: &CartSettings,
+    ) -> ModelResult<Vec<MergeReduction>> {
+        let txn = db.begin().await?;
+
+        let items = cart_items::Entity::find()
+            .filter(cart_items::Column::GuestCartId.eq(self.id))
+            .all(&txn)
+            .await?;
+        let mut reductions = Vec::new();
+        for item in items {
+            let available = product_variants::Entity::find_by_id(item.product_variant_id)
+                .one(&txn)
+                .await?
+                .map_or(0, |variant| max_quantity(&variant, settings));
+            let existing = cart_items::Entity::find()
+                .filter(cart_items::Column::UserId.eq(user_id))
+                .filter(cart_items::Column::ProductVariantId.eq(item.product_variant_id))
+                .one(&txn)
+                .await?;
+
+            // What the account's cart already held is left for reading the
+            // cart to revalidate, only the guest's units are capped here
+            let current = existing
+                .as_ref()
+                .and_then(|existing| existing.quantity)
+                .unwrap_or(0);
+            let requested = current.saturating_add(item.quantity.unwrap_or(0));
+            let quantity = requested.min(available.max(current));
+            if quantity < requested {
+                reductions.push(MergeReduction {
+                    product_variant_id: item.product_variant_id,
+                    requested,
+                    available: quantity,
+                });
+            }
+
+            // Guest lines that are not moved go with the guest cart
+            match existing {
+                Some(existing) if quantity > current => {
+                    let mut existing = existing.into_active_model();
+                    existing.quantity = ActiveValue::Set(Some(quantity));
+                    existing.update(&txn).await?;
+                }
+                None if quantity > 0 => {
+                    let mut item = item.into_active_model();
+                    item.user_id = ActiveValue::Set(Some(user_id));
+                    item.guest_cart_id = ActiveValue::Set(None);
+                    item.quantity = ActiveValue::Set(Some(quantity));
+                    item.update(&txn).await?;
+                }
+                Some(_) | None => {}
+            }
+        }
+
+        Entity::delete_by_id(self.id).exec(&txn).await?;
+        txn.commit().await?;
+
+        Ok(reductions)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,152 @@
+pub use super::_entities::idempotency_keys::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Alias, Expr, OnConflict},
+    Condition, Set,
+};
+pub type IdempotencyKeys = Entity;
+
+/// Outcome of claiming an idempotency key for a request.
+pub enum Claim {
+    /// The key was unused, the request should be processed.
+    New(Model),
+    /// The key was used before, by this or another request.
+    Existing(Model),
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Claims `key` for a request of the user, holding it until `locked_until`.
+    /// Records created before `expired_before` no longer guard their key and
+    /// are replaced. A claim without a stored response whose hold has lapsed
+    /// belongs to a request that is gone and is taken over by a retry.
+    ///
+    /// Claiming is a single upsert, so of two requests racing for a key only
+    /// one gets it.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn claim<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTimeWithTimeZone,
+        locked_until: DateTimeWithTimeZone,
+    ) -> ModelResult<Claim> {
+        let existing = |column: Column| Expr::col((Entity, column));
+        let abandoned = existing(Column::ResponseStatus)
+            .is_null()
+            .and(existing(Column::LockedUntil).lt(Expr::current_timestamp()))
+            .and(
+                existing(Column::RequestFingerprint)
+                    .equals((Alias::new("excluded"), Column::RequestFingerprint)),
+            );
+        let expired = existing(Column::CreatedAt).lt(expired_before);
+
+        let claimed = Entity::insert(ActiveModel {
+            user_id: Set(user_id),
+            key: Set(key.to_string()),
+            request_fingerprint: Set(fingerprint.to_string()),
+            locked_until: Set(Some(locked_until)),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([Column::UserId, Column::Key])
+                .update_columns([
+                    Column::RequestFingerprint,
+                    Column::LockedUntil,
+                    Column::ResponseStatus,
+                    Column::ResponseContentType,
+                    Column::ResponseBody,
+                    Column::CreatedAt,
+                    Column::UpdatedAt,
+                ])
+                .action_and_where(abandoned.or(expired))
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?
+            > 0;
+
+        let record = Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::Key.eq(key))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+
+        Ok(if claimed {
+            Claim::New(record)
+        } else {
+            Claim::Existing(record)
+        })
+    }
+
+    /// Stores the response of the request that claimed the key, so it can be
+    /// replayed. Returns `false` if the claim was taken over by a retry in the
+    /// meantime, in which case handlers storing it in the transaction of their
+    /// own writes roll them back.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn complete<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        status: i16,
+        content_type: Option<String>,
+        body: String,
+    ) -> ModelResult<bool> {
+        let res = Entity::update_many()
+            .col_expr(Column::ResponseStatus, Expr::value(status))
+            .col_expr(Column::ResponseContentType, Expr::value(content_type))
+            .col_expr(Column::ResponseBody, Expr::value(body))
+            .col_expr(Column::UpdatedAt, Expr::current_timestamp().into())
+            .filter(self.held())
+            .exec(db)
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Gives up the claim without storing a response, so the key can be
+    /// retried right away.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn release<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()> {
+        Entity::delete_many().filter(self.held()).exec(db).await?;
+        Ok(())
+    }
+
+    /// Matches this claim as long as no response was stored for it and it
+    /// wasn't taken over.
+    fn held(&self) -> Condition {
+        Condition::all()
+            .add(Column::Id.eq(self.id))
+            .add(Column::ResponseStatus.is_null())
+            .add(Column::LockedUntil.eq(self.locked_until))
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,49 @@
+pub use super::_entities::invoice_sequences::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{entity::prelude::*, sea_query::Expr};
+pub type InvoiceSequences = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Takes the next invoice number. Must run inside the transaction that
+    /// stores the number: the counter stays locked until it ends, and a
+    /// rollback gives the number back, so invoice numbers have no gaps.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn next_number<C: ConnectionTrait>(db: &C) -> ModelResult<i32> {
+        Entity::update_many()
+            .col_expr(Column::LastNumber, Expr::col(Column::LastNumber).add(1))
+            .col_expr(Column::UpdatedAt, Expr::value(chrono::Utc::now()))
+            .exec(db)
+            .await?;
+
+        let sequence = Entity::find()
+            .one(db)
+            .await?
+            .ok_or_else(|| ModelError::EntityNotFound)?;
+        Ok(sequence.last_number)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,12 +1,35 @@
 pub mod _entities;
 mod _macros;
+pub mod audit_events;
+pub mod auth_throttles;
 pub mod brands;
 pub mod cart_items;
+pub mod cart_reminders;
 pub mod categories;
+pub mod guest_carts;
+pub mod idempotency_keys;
+pub mod invoice_sequences;
+pub mod oauth_states;
 pub mod order_items;
 pub mod orders;
 pub mod product_variants;
 pub mod products;
+pub mod review_reports;
+pub mod review_votes;
 pub mod reviews;
+pub mod two_factor_challenges;
+pub mod user_identities;
+pub mod user_recovery_codes;
+pub mod user_sessions;
 pub mod users;
+pub mod wishlist_lists;
 pub mod wishlists;
+
+use sha2::{Digest, Sha256};
+
+/// Hashes a random token before it is stored, so a leaked table can't be
+/// used to sign in. Tokens carry enough entropy that a fast hash will do.
+#[must_use]
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
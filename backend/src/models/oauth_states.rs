@@ -0,0 +1,95 @@
+pub use super::_entities::oauth_states::{ActiveModel, Column, Entity, Model};
+use chrono::{Duration, Utc};
+use loco_rs::{hash, model::ModelResult};
+use sea_orm::{entity::prelude::*, ActiveValue};
+
+use super::hash_token;
+
+pub type OauthStates = Entity;
+
+const STATE_LENGTH: usize = 43;
+/// RFC 7636 allows 43 to 128 characters.
+const CODE_VERIFIER_LENGTH: usize = 64;
+const NONCE_LENGTH: usize = 32;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Starts an authorization with an identity provider, to sign in or, with
+    /// `link_user_id`, to link an identity to that user. Returns the pending
+    /// authorization with its `state` parameter, which is not stored in clear.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn start<C: ConnectionTrait>(
+        db: &C,
+        provider: &str,
+        link_user_id: Option<i32>,
+        ttl_minutes: i64,
+    ) -> ModelResult<(Self, String)> {
+        let state = hash::random_string(STATE_LENGTH);
+        let pending = ActiveModel {
+            state_hash: ActiveValue::Set(hash_token(&state)),
+            provider: ActiveValue::Set(provider.to_string()),
+            code_verifier: ActiveValue::Set(hash::random_string(CODE_VERIFIER_LENGTH)),
+            nonce: ActiveValue::Set(hash::random_string(NONCE_LENGTH)),
+            expires_at: ActiveValue::Set((Utc::now() + Duration::minutes(ttl_minutes)).into()),
+            link_user_id: ActiveValue::Set(link_user_id),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok((pending, state))
+    }
+
+    /// Takes the pending authorization of a `state` returned by the provider,
+    /// unless it expired or was started for another provider. Each state can
+    /// be taken once.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn take<C: ConnectionTrait>(
+        db: &C,
+        provider: &str,
+        state: &str,
+    ) -> ModelResult<Option<Self>> {
+        let Some(pending) = Entity::find()
+            .filter(Column::StateHash.eq(hash_token(state)))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let deleted = Entity::delete_by_id(pending.id).exec(db).await?;
+        if deleted.rows_affected == 0
+            || pending.provider != provider
+            || pending.expires_at < Utc::now()
+        {
+            return Ok(None);
+        }
+        Ok(Some(pending))
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -26,6 +26,13 @@ impl ActiveModelBehavior for ActiveModel {
 
 // implement your read-oriented logic here
 impl Model {
+    /// The price after applying the product's discount.
+    #[must_use]
+    pub fn effective_price(&self) -> f64 {
+        let discount = f64::from(self.discount_percentage.unwrap_or(0).clamp(0, 100));
+        self.price * (100.0 - discount) / 100.0
+    }
+
     /// # Errors
     /// Returns an error if the underlying database operation fails.
     pub async fn find_by_id_with_brand_category(
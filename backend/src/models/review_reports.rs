@@ -0,0 +1,52 @@
+pub use super::_entities::review_reports::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, QuerySelect};
+use std::collections::HashMap;
+pub type ReviewReports = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Counts the open reports for each of the given reviews. Reviews without
+    /// reports are missing from the result.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn count_by_review<C: ConnectionTrait>(
+        db: &C,
+        review_ids: impl IntoIterator<Item = i32>,
+    ) -> ModelResult<HashMap<i32, i64>> {
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::ReviewId)
+            .column_as(Column::Id.count(), "count")
+            .filter(Column::ReviewId.is_in(review_ids))
+            .group_by(Column::ReviewId)
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,114 @@
+pub use super::_entities::review_votes::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Expr, OnConflict},
+    QuerySelect, Set, TransactionTrait,
+};
+
+use crate::models::_entities::reviews;
+pub type ReviewVotes = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Records a user's vote on a review, replacing any earlier vote by the
+    /// same user, or withdraws it when `helpful` is `None`. The vote counts
+    /// stored on the review are refreshed in the same transaction, with the
+    /// review locked so concurrent votes are counted one after the other.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn cast(
+        db: &DatabaseConnection,
+        review_id: i32,
+        user_id: i32,
+        helpful: Option<bool>,
+    ) -> ModelResult<reviews::Model> {
+        let txn = db.begin().await?;
+
+        reviews::Entity::find_by_id(review_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| ModelError::EntityNotFound)?;
+
+        if let Some(helpful) = helpful {
+            Entity::insert(ActiveModel {
+                review_id: Set(review_id),
+                user_id: Set(user_id),
+                helpful: Set(helpful),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::columns([Column::ReviewId, Column::UserId])
+                    .update_columns([Column::Helpful, Column::UpdatedAt])
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await?;
+        } else {
+            Entity::delete_many()
+                .filter(Column::ReviewId.eq(review_id))
+                .filter(Column::UserId.eq(user_id))
+                .exec(&txn)
+                .await?;
+        }
+
+        let counts = Entity::find()
+            .select_only()
+            .column(Column::Helpful)
+            .column_as(Column::Id.count(), "count")
+            .filter(Column::ReviewId.eq(review_id))
+            .group_by(Column::Helpful)
+            .into_tuple::<(bool, i64)>()
+            .all(&txn)
+            .await?;
+        let count_of = |helpful: bool| {
+            counts
+                .iter()
+                .find(|(h, _)| *h == helpful)
+                .map_or(0, |(_, count)| i32::try_from(*count).unwrap_or(i32::MAX))
+        };
+
+        // Votes are not edits, so this bypasses the `updated_at` bump
+        reviews::Entity::update_many()
+            .col_expr(reviews::Column::HelpfulCount, Expr::value(count_of(true)))
+            .col_expr(
+                reviews::Column::UnhelpfulCount,
+                Expr::value(count_of(false)),
+            )
+            .filter(reviews::Column::Id.eq(review_id))
+            .exec(&txn)
+            .await?;
+        let review = reviews::Entity::find_by_id(review_id)
+            .one(&txn)
+            .await?
+            .ok_or_else(|| ModelError::EntityNotFound)?;
+
+        txn.commit().await?;
+
+        Ok(review)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
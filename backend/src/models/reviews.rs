@@ -1,12 +1,30 @@
-use crate::models::{_entities::reviews::Column, users};
+use std::sync::OnceLock;
+
+use crate::{
+    models::{
+        _entities::{reviews::Column, sea_orm_active_enums::ReviewStatus},
+        users,
+    },
+    settings::{AutoPublish, ReviewSettings},
+};
 
 pub use super::_entities::reviews::{ActiveModel, Entity, Model};
 use loco_rs::{model::ModelResult, prelude::Validatable};
+use regex::Regex;
 use sea_orm::{entity::prelude::*, DeleteResult};
 use serde::Deserialize;
 use validator::Validate;
 pub type Reviews = Entity;
 
+static LINK_RE: OnceLock<Regex> = OnceLock::new();
+
+fn get_link_re() -> &'static Regex {
+    LINK_RE.get_or_init(|| {
+        Regex::new(r"(?i)https?://|www\.|\b[a-z0-9-]+\.(com|net|org|io|co|ru|xyz|info)\b")
+            .expect("Failed to compile regex")
+    })
+}
+
 #[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
@@ -39,6 +57,37 @@ impl Validatable for ActiveModel {
 
 // implement your read-oriented logic here
 impl Model {
+    /// Checks review content against the blocked word list and, unless
+    /// allowed, for links.
+    #[must_use]
+    pub fn passes_filter(settings: &ReviewSettings, content: Option<&str>) -> bool {
+        let Some(content) = content else {
+            return true;
+        };
+
+        if !settings.allow_links && get_link_re().is_match(content) {
+            return false;
+        }
+
+        !content.split(|c: char| !c.is_alphanumeric()).any(|word| {
+            settings
+                .blocked_words
+                .iter()
+                .any(|blocked| blocked.eq_ignore_ascii_case(word))
+        })
+    }
+
+    /// The status a review gets when it is written or edited.
+    #[must_use]
+    pub fn initial_status(settings: &ReviewSettings, content: Option<&str>) -> ReviewStatus {
+        match settings.auto_publish {
+            AutoPublish::Always => ReviewStatus::Published,
+            AutoPublish::Never => ReviewStatus::Pending,
+            AutoPublish::Clean if Self::passes_filter(settings, content) => ReviewStatus::Published,
+            AutoPublish::Clean => ReviewStatus::Pending,
+        }
+    }
+
     /// # Errors
     /// Returns an error if the underlying database operation fails.
     pub async fn find_by_product_and_user(
@@ -59,8 +108,8 @@ impl Model {
 
     /// # Errors
     /// Returns an error if the underlying database operation fails.
-    pub async fn delete_by_product_and_user(
-        db: &DatabaseConnection,
+    pub async fn delete_by_product_and_user<C: ConnectionTrait>(
+        db: &C,
         product_id: i32,
         user_id: i32,
     ) -> ModelResult<DeleteResult> {
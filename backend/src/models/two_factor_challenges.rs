@@ -0,0 +1,102 @@
+pub use super::_entities::two_factor_challenges::{ActiveModel, Column, Entity, Model};
+use chrono::{Duration, Utc};
+use loco_rs::{hash, model::ModelResult};
+use sea_orm::{entity::prelude::*, sea_query::Expr, ActiveValue};
+
+use super::hash_token;
+
+pub type TwoFactorChallenges = Entity;
+
+const CHALLENGE_TOKEN_LENGTH: usize = 64;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Starts the second step of a login for a user whose password checked
+    /// out. Returns the challenge with its token, which is not stored in
+    /// clear.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn start<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        ttl_minutes: i64,
+    ) -> ModelResult<(Self, String)> {
+        let token = hash::random_string(CHALLENGE_TOKEN_LENGTH);
+        let challenge = ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            token_hash: ActiveValue::Set(hash_token(&token)),
+            expires_at: ActiveValue::Set((Utc::now() + Duration::minutes(ttl_minutes)).into()),
+            attempts: ActiveValue::Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok((challenge, token))
+    }
+
+    /// Finds the challenge of a token, unless it expired or ran out of
+    /// attempts.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_by_token<C: ConnectionTrait>(
+        db: &C,
+        token: &str,
+        max_attempts: i32,
+    ) -> ModelResult<Option<Self>> {
+        Ok(Entity::find()
+            .filter(Column::TokenHash.eq(hash_token(token)))
+            .filter(Column::ExpiresAt.gt(Utc::now()))
+            .filter(Column::Attempts.lt(max_attempts))
+            .one(db)
+            .await?)
+    }
+
+    /// Counts a wrong code against the challenge.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn record_failure<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()> {
+        Entity::update_many()
+            .col_expr(Column::Attempts, Expr::col(Column::Attempts).add(1))
+            .col_expr(Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(Column::Id.eq(self.id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Uses up the challenge once its code checked out. Returns `false` when
+    /// a concurrent request already did.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn consume<C: ConnectionTrait>(&self, db: &C) -> ModelResult<bool> {
+        let deleted = Entity::delete_by_id(self.id).exec(db).await?;
+        Ok(deleted.rows_affected > 0)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
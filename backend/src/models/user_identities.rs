@@ -0,0 +1,95 @@
+pub use super::_entities::user_identities::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+
+pub type UserIdentities = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Finds the identity a provider knows an account by.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_by_subject<C: ConnectionTrait>(
+        db: &C,
+        provider: &str,
+        subject: &str,
+    ) -> ModelResult<Option<Self>> {
+        Ok(Entity::find()
+            .filter(Column::Provider.eq(provider))
+            .filter(Column::Subject.eq(subject))
+            .one(db)
+            .await?)
+    }
+
+    /// Lists the identities linked to a user, oldest first.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn list_for_user<C: ConnectionTrait>(db: &C, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Links a provider account to a user.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails, e.g.
+    /// when the provider account is already linked.
+    pub async fn link<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> ModelResult<Self> {
+        Ok(ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            provider: ActiveValue::Set(provider.to_string()),
+            subject: ActiveValue::Set(subject.to_string()),
+            email: ActiveValue::Set(email.map(ToString::to_string)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Unlinks one of a user's identities. Returns `false` when the user has
+    /// no identity with that id.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn unlink<C: ConnectionTrait>(db: &C, user_id: i32, id: i32) -> ModelResult<bool> {
+        let deleted = Entity::delete_many()
+            .filter(Column::Id.eq(id))
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(deleted.rows_affected > 0)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,99 @@
+pub use super::_entities::user_recovery_codes::{ActiveModel, Column, Entity, Model};
+use chrono::Utc;
+use loco_rs::{hash, model::ModelResult};
+use sea_orm::{entity::prelude::*, sea_query::Expr, ActiveValue};
+
+use super::hash_token;
+
+pub type UserRecoveryCodes = Entity;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// Codes are shown as two groups of five, but may be typed in any case and
+/// with or without the dash.
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Replaces the recovery codes of a user with new ones. The codes are
+    /// returned to be shown once; only their hashes are stored.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn regenerate<C: ConnectionTrait>(db: &C, user_id: i32) -> ModelResult<Vec<String>> {
+        Self::delete_all(db, user_id).await?;
+
+        let codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| hash::random_string(RECOVERY_CODE_LENGTH).to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        Entity::insert_many(codes.iter().map(|code| ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            code_hash: ActiveValue::Set(hash_token(code)),
+            ..Default::default()
+        }))
+        .exec(db)
+        .await?;
+
+        Ok(codes
+            .into_iter()
+            .map(|code| format!("{}-{}", &code[..5], &code[5..]))
+            .collect())
+    }
+
+    /// Uses up a recovery code of a user. Returns whether it was valid and
+    /// unused.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn redeem<C: ConnectionTrait>(db: &C, user_id: i32, code: &str) -> ModelResult<bool> {
+        let redeemed = Entity::update_many()
+            .col_expr(Column::UsedAt, Expr::value(Utc::now()))
+            .col_expr(Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::CodeHash.eq(hash_token(&normalize(code))))
+            .filter(Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(redeemed.rows_affected > 0)
+    }
+
+    /// Deletes every recovery code of a user.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn delete_all<C: ConnectionTrait>(db: &C, user_id: i32) -> ModelResult<()> {
+        Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,170 @@
+pub use super::_entities::user_sessions::{ActiveModel, Column, Entity, Model};
+use chrono::{Duration, Utc};
+use loco_rs::{hash, model::ModelResult};
+use sea_orm::{entity::prelude::*, sea_query::Expr, ActiveValue, TransactionTrait};
+
+use super::hash_token;
+pub type UserSessions = Entity;
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+/// Outcome of exchanging a refresh token.
+#[derive(Debug)]
+pub enum Refresh {
+    /// The token was valid and has been replaced by a new session in the same
+    /// family, returned with its refresh token.
+    Rotated(Model, String),
+    /// The token was already exchanged before. It may have been stolen, so
+    /// the whole family has been revoked.
+    Reused(Model),
+    /// The token is unknown, expired or revoked.
+    Invalid,
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Starts a new session family for a user who just signed in. Returns the
+    /// session with its refresh token, which is not stored in clear.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn start<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        ttl_days: i64,
+    ) -> ModelResult<(Self, String)> {
+        Self::issue(db, user_id, Uuid::new_v4(), ttl_days).await
+    }
+
+    async fn issue<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        family: Uuid,
+        ttl_days: i64,
+    ) -> ModelResult<(Self, String)> {
+        let token = hash::random_string(REFRESH_TOKEN_LENGTH);
+        let session = ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            family: ActiveValue::Set(family),
+            token_hash: ActiveValue::Set(hash_token(&token)),
+            expires_at: ActiveValue::Set((Utc::now() + Duration::days(ttl_days)).into()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok((session, token))
+    }
+
+    /// Exchanges a refresh token for a new one. Each token can be exchanged
+    /// once; presenting it again revokes every session of its family.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn refresh(
+        db: &DatabaseConnection,
+        token: &str,
+        ttl_days: i64,
+    ) -> ModelResult<Refresh> {
+        let txn = db.begin().await?;
+
+        let Some(session) = Entity::find()
+            .filter(Column::TokenHash.eq(hash_token(token)))
+            .one(&txn)
+            .await?
+        else {
+            return Ok(Refresh::Invalid);
+        };
+        if session.revoked_at.is_some() || session.expires_at < Utc::now() {
+            return Ok(Refresh::Invalid);
+        }
+
+        // Only one exchange can mark the token as rotated, even when the
+        // same token is presented twice at the same time
+        let rotated = Entity::update_many()
+            .col_expr(Column::RotatedAt, Expr::value(Utc::now()))
+            .col_expr(Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(Column::Id.eq(session.id))
+            .filter(Column::RotatedAt.is_null())
+            .exec(&txn)
+            .await?;
+        if rotated.rows_affected == 0 {
+            Self::revoke_family(&txn, session.family).await?;
+            txn.commit().await?;
+            return Ok(Refresh::Reused(session));
+        }
+
+        let (next, token) = Self::issue(&txn, session.user_id, session.family, ttl_days).await?;
+        txn.commit().await?;
+
+        Ok(Refresh::Rotated(next, token))
+    }
+
+    /// Signs out the session a refresh token belongs to, on every token of
+    /// its family. Unknown tokens are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn revoke_by_token<C: ConnectionTrait>(db: &C, token: &str) -> ModelResult<()> {
+        let session = Entity::find()
+            .filter(Column::TokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?;
+        if let Some(session) = session {
+            Self::revoke_family(db, session.family).await?;
+        }
+        Ok(())
+    }
+
+    /// Revokes every token of a session family.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn revoke_family<C: ConnectionTrait>(db: &C, family: Uuid) -> ModelResult<()> {
+        Entity::update_many()
+            .col_expr(Column::RevokedAt, Expr::value(Utc::now()))
+            .col_expr(Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(Column::Family.eq(family))
+            .filter(Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Signs a user out everywhere, e.g. after their password changed.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn revoke_all<C: ConnectionTrait>(db: &C, user_id: i32) -> ModelResult<()> {
+        Entity::update_many()
+            .col_expr(Column::RevokedAt, Expr::value(Utc::now()))
+            .col_expr(Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,14 +1,48 @@
+use std::sync::OnceLock;
+
 use async_trait::async_trait;
-use chrono::{offset::Local, Duration};
+use chrono::{offset::Local, Duration, Utc};
 use loco_rs::{auth::jwt, hash, prelude::*};
+use sea_orm::{sea_query::Expr, Condition, QuerySelect, TryIntoModel};
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
 use uuid::Uuid;
 
 pub use super::_entities::users::{self, ActiveModel, Entity, Model};
+use super::{
+    _entities::{auth_throttles, oauth_states, orders, review_votes, reviews},
+    hash_token,
+};
+use crate::{permissions::Permission, settings::LoginSettings, totp};
 
 pub const MAGIC_LINK_LENGTH: i8 = 32;
 pub const MAGIC_LINK_EXPIRATION_MIN: i8 = 5;
+pub const UNLOCK_TOKEN_LENGTH: usize = 32;
+const GENERATED_PASSWORD_LENGTH: usize = 32;
+
+/// Hash of a random password, hashed like real ones so checking against it
+/// takes as long.
+static DUMMY_PASSWORD_HASH: OnceLock<String> = OnceLock::new();
+
+/// Outcome of recording a failed password attempt.
+#[derive(Debug)]
+pub enum FailedLogin {
+    /// The failure was counted.
+    Counted,
+    /// The failure locked the account, and no unlock link sent for an
+    /// earlier lock is still valid. The user is returned with a new unlock
+    /// token, only its hash is stored.
+    Locked(Box<Model>, String),
+}
+
+/// What a password reset token was found to belong to.
+#[derive(Debug)]
+pub enum ResetToken {
+    /// The user who requested the reset, with a token still valid.
+    Valid(Box<Model>),
+    /// The latest token of a user, which is too old to be used.
+    Expired,
+}
 
 #[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct LoginParams {
@@ -95,21 +129,28 @@ impl Model {
         user.ok_or_else(|| ModelError::EntityNotFound)
     }
 
-    /// finds a user by the provided verification token
+    /// finds a user by the provided verification token, unless it was sent
+    /// more than `ttl_hours` ago. Only a hash of the token is stored.
     ///
     /// # Errors
     ///
-    /// When could not find user by the given token or DB query error
+    /// When could not find user by the given token, the token expired or DB
+    /// query error
     pub async fn find_by_verification_token(
         db: &DatabaseConnection,
         token: &str,
+        ttl_hours: i64,
     ) -> ModelResult<Self> {
         let user = users::Entity::find()
             .filter(
                 model::query::condition()
-                    .eq(users::Column::EmailVerificationToken, token)
+                    .eq(users::Column::EmailVerificationToken, hash_token(token))
                     .build(),
             )
+            .filter(
+                users::Column::EmailVerificationSentAt
+                    .gt(Local::now() - Duration::hours(ttl_hours)),
+            )
             .one(db)
             .await?;
         user.ok_or_else(|| ModelError::EntityNotFound)
@@ -124,7 +165,7 @@ impl Model {
         let user = users::Entity::find()
             .filter(
                 query::condition()
-                    .eq(users::Column::MagicLinkToken, token)
+                    .eq(users::Column::MagicLinkToken, hash_token(token))
                     .build(),
             )
             .one(db)
@@ -151,21 +192,98 @@ impl Model {
         }
     }
 
-    /// finds a user by the provided reset token
+    /// finds a user by the provided reset token, and tells whether it was
+    /// sent more than `ttl_minutes` ago. Only a hash of the token is stored.
     ///
     /// # Errors
     ///
     /// When could not find user by the given token or DB query error
-    pub async fn find_by_reset_token(db: &DatabaseConnection, token: &str) -> ModelResult<Self> {
+    pub async fn find_by_reset_token(
+        db: &DatabaseConnection,
+        token: &str,
+        ttl_minutes: i64,
+    ) -> ModelResult<ResetToken> {
         let user = users::Entity::find()
             .filter(
                 model::query::condition()
-                    .eq(users::Column::ResetToken, token)
+                    .eq(users::Column::ResetToken, hash_token(token))
                     .build(),
             )
             .one(db)
             .await?;
-        user.ok_or_else(|| ModelError::EntityNotFound)
+        let user = user.ok_or_else(|| ModelError::EntityNotFound)?;
+
+        let valid_since = Local::now() - Duration::minutes(ttl_minutes);
+        Ok(match user.reset_sent_at {
+            Some(sent_at) if sent_at > valid_since => ResetToken::Valid(Box::new(user)),
+            _ => ResetToken::Expired,
+        })
+    }
+
+    /// finds a user by the token of the unlock link sent when their account
+    /// was locked, unless it was sent more than `ttl_minutes` ago. Only a hash
+    /// of the token is stored.
+    ///
+    /// # Errors
+    ///
+    /// When could not find user by the given token or DB query error or token
+    /// expired
+    pub async fn find_by_unlock_token(
+        db: &DatabaseConnection,
+        token: &str,
+        ttl_minutes: i64,
+    ) -> ModelResult<Self> {
+        let user = users::Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(users::Column::UnlockToken, hash_token(token))
+                    .build(),
+            )
+            .one(db)
+            .await?;
+        let user = user.ok_or_else(|| ModelError::EntityNotFound)?;
+
+        let valid_since = Local::now() - Duration::minutes(ttl_minutes);
+        if user
+            .unlock_sent_at
+            .is_some_and(|sent_at| sent_at > valid_since)
+        {
+            Ok(user)
+        } else {
+            Err(ModelError::msg("unlock token expired"))
+        }
+    }
+
+    /// finds a user by the token of the link emailed to set up two-factor
+    /// authentication, unless it was sent more than `ttl_minutes` ago or
+    /// two-factor authentication was enabled since. Only a hash of the token
+    /// is stored.
+    ///
+    /// # Errors
+    ///
+    /// When could not find user by the given token or DB query error or token
+    /// expired
+    pub async fn find_by_totp_enrollment_token(
+        db: &DatabaseConnection,
+        token: &str,
+        ttl_minutes: i64,
+    ) -> ModelResult<Self> {
+        let user = users::Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(users::Column::TotpEnrollmentToken, hash_token(token))
+                    .build(),
+            )
+            .filter(users::Column::TotpEnabledAt.is_null())
+            .one(db)
+            .await?;
+        let user = user.ok_or_else(|| ModelError::EntityNotFound)?;
+
+        if user.totp_enrollment_link_valid(ttl_minutes) {
+            Ok(user)
+        } else {
+            Err(ModelError::msg("two-factor enrollment token expired"))
+        }
     }
 
     /// finds a user by the provided pid
@@ -213,6 +331,157 @@ impl Model {
         hash::verify_password(password, &self.password)
     }
 
+    /// Checks `password` against a hash no password matches, taking as long
+    /// as [`Model::verify_password`], so answering for an email without an
+    /// account isn't faster than for a wrong password.
+    pub fn verify_dummy_password(password: &str) {
+        let dummy = DUMMY_PASSWORD_HASH.get_or_init(|| {
+            hash::hash_password(&hash::random_string(GENERATED_PASSWORD_LENGTH)).unwrap_or_default()
+        });
+        let _ = hash::verify_password(password, dummy);
+    }
+
+    /// Whether the user is staff, i.e. has a role.
+    #[must_use]
+    pub const fn is_staff(&self) -> bool {
+        self.role.is_some()
+    }
+
+    /// Whether the user's role grants `permission`. Customers have none.
+    #[must_use]
+    pub fn can(&self, permission: Permission) -> bool {
+        self.role
+            .is_some_and(|role| role.permissions().contains(&permission))
+    }
+
+    /// Whether a link to set up two-factor authentication was sent less than
+    /// `ttl_minutes` ago.
+    #[must_use]
+    pub fn totp_enrollment_link_valid(&self, ttl_minutes: i64) -> bool {
+        let valid_since = Local::now() - Duration::minutes(ttl_minutes);
+        self.totp_enrollment_token.is_some()
+            && self
+                .totp_enrollment_sent_at
+                .is_some_and(|sent_at| sent_at > valid_since)
+    }
+
+    /// Whether `token` is the one from the latest link to confirm deleting
+    /// the account, sent less than `ttl_minutes` ago.
+    #[must_use]
+    pub fn deletion_link_valid(&self, token: &str, ttl_minutes: i64) -> bool {
+        let valid_since = Local::now() - Duration::minutes(ttl_minutes);
+        self.deletion_token.as_deref() == Some(hash_token(token).as_str())
+            && self
+                .deletion_sent_at
+                .is_some_and(|sent_at| sent_at > valid_since)
+    }
+
+    /// Checks a code from the user's authenticator app. A code is accepted
+    /// once, and never after a code of a later time step.
+    ///
+    /// # Errors
+    ///
+    /// When has DB query error
+    pub async fn verify_totp(&self, db: &DatabaseConnection, code: &str) -> ModelResult<bool> {
+        let Some(secret) = &self.totp_secret else {
+            return Ok(false);
+        };
+        let Some(step) = totp::verify(secret, code, Utc::now().timestamp()) else {
+            return Ok(false);
+        };
+
+        // Conditional, so that of two requests with the same code only one
+        // gets through
+        let accepted = users::Entity::update_many()
+            .col_expr(users::Column::TotpLastStep, Expr::value(step))
+            .filter(users::Column::Id.eq(self.id))
+            .filter(
+                Condition::any()
+                    .add(users::Column::TotpLastStep.is_null())
+                    .add(users::Column::TotpLastStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+        Ok(accepted.rows_affected > 0)
+    }
+
+    /// Whether another verification email may be sent, at most one per
+    /// `cooldown_minutes`.
+    #[must_use]
+    pub fn verification_resend_allowed(&self, cooldown_minutes: i64) -> bool {
+        self.email_verification_sent_at
+            .is_none_or(|sent_at| sent_at + Duration::minutes(cooldown_minutes) <= Local::now())
+    }
+
+    /// Seconds the user has to wait before their password may be tried again,
+    /// either because the account is locked or because of recent failures.
+    /// Past the free attempts, the wait doubles with every failure.
+    #[must_use]
+    pub fn login_wait(&self, settings: &LoginSettings) -> Option<u64> {
+        let now = Local::now();
+        let retry_at = if self.locked_until.is_some_and(|until| until > now) {
+            self.locked_until
+        } else {
+            let delay = settings.failure_delay(self.failed_login_count)?;
+            self.last_failed_login_at.map(|at| at + delay)
+        }?;
+
+        let wait = retry_at.signed_duration_since(now).num_milliseconds();
+        (wait > 0).then(|| u64::try_from(wait).unwrap_or_default().div_ceil(1000))
+    }
+
+    /// Counts a failed password attempt, and locks the account once there
+    /// were too many in a row.
+    ///
+    /// # Errors
+    ///
+    /// When has DB query error
+    pub async fn record_failed_login(
+        &self,
+        db: &DatabaseConnection,
+        settings: &LoginSettings,
+    ) -> ModelResult<FailedLogin> {
+        let now = Local::now();
+        // Incremented in the database so that concurrent attempts all count
+        users::Entity::update_many()
+            .col_expr(
+                users::Column::FailedLoginCount,
+                Expr::col(users::Column::FailedLoginCount).add(1),
+            )
+            .col_expr(users::Column::LastFailedLoginAt, Expr::value(now))
+            .filter(users::Column::Id.eq(self.id))
+            .exec(db)
+            .await?;
+
+        let user = users::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| ModelError::EntityNotFound)?;
+        if user.failed_login_count < settings.max_failed_attempts {
+            return Ok(FailedLogin::Counted);
+        }
+
+        // An unlock link is valid as long as the lock it was sent for, and
+        // only sent when there is no valid one yet
+        let lockout = Duration::minutes(settings.lockout_minutes);
+        let send_link = user
+            .unlock_sent_at
+            .is_none_or(|sent_at| sent_at + lockout <= now);
+        let mut user = user.into_active_model();
+        user.locked_until = ActiveValue::set(Some((now + lockout).into()));
+        let token = send_link.then(|| hash::random_string(UNLOCK_TOKEN_LENGTH));
+        if let Some(token) = &token {
+            user.unlock_token = ActiveValue::set(Some(hash_token(token)));
+            user.unlock_sent_at = ActiveValue::set(Some(now.into()));
+        }
+        let user = user.update(db).await?;
+
+        Ok(match token {
+            Some(token) => FailedLogin::Locked(Box::new(user), token),
+            None => FailedLogin::Counted,
+        })
+    }
+
     /// Asynchronously creates a user with a password and saves it to the
     /// database.
     ///
@@ -254,6 +523,94 @@ impl Model {
         Ok(user)
     }
 
+    /// Creates a user who signed up with an identity provider that vouched
+    /// for their email. The account gets a random password, which can be
+    /// replaced through a password reset.
+    ///
+    /// # Errors
+    ///
+    /// When the email is already taken or on DB query error
+    pub async fn create_from_identity(
+        db: &DatabaseConnection,
+        email: &str,
+        name: &str,
+    ) -> ModelResult<Self> {
+        let password = hash::random_string(GENERATED_PASSWORD_LENGTH);
+        let user = Self::create_with_password(
+            db,
+            &RegisterParams {
+                email: email.to_string(),
+                password,
+                name: name.to_string(),
+            },
+        )
+        .await?;
+
+        user.into_active_model().verified(db).await
+    }
+
+    /// Deletes the account and the personal data that goes with it. Orders
+    /// are kept as financial records but no longer point to the user and lose
+    /// their shipping address. Everything else the user owns, including their
+    /// sessions, API key and linked identities, is deleted with the user, so
+    /// no credential of theirs works afterwards.
+    ///
+    /// # Errors
+    ///
+    /// When has DB query error
+    pub async fn delete_account(self, db: &DatabaseConnection) -> ModelResult<()> {
+        let txn = db.begin().await?;
+
+        orders::Entity::update_many()
+            .col_expr(orders::Column::UserId, Expr::value(Option::<i32>::None))
+            .col_expr(
+                orders::Column::ShippingAddress,
+                Expr::value(Option::<String>::None),
+            )
+            .filter(orders::Column::UserId.eq(self.id))
+            .exec(&txn)
+            .await?;
+
+        // The user's votes go with them, so take them off the counts of the
+        // reviews they voted on
+        for (helpful, count_column) in [
+            (true, reviews::Column::HelpfulCount),
+            (false, reviews::Column::UnhelpfulCount),
+        ] {
+            let voted = review_votes::Entity::find()
+                .select_only()
+                .column(review_votes::Column::ReviewId)
+                .filter(review_votes::Column::UserId.eq(self.id))
+                .filter(review_votes::Column::Helpful.eq(helpful))
+                .into_tuple::<i32>()
+                .all(&txn)
+                .await?;
+            reviews::Entity::update_many()
+                .col_expr(count_column, Expr::col(count_column).sub(1))
+                .filter(reviews::Column::Id.is_in(voted))
+                .exec(&txn)
+                .await?;
+        }
+
+        oauth_states::Entity::delete_many()
+            .filter(oauth_states::Column::LinkUserId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        auth_throttles::Entity::delete_many()
+            .filter(
+                auth_throttles::Column::Key.eq(format!("emails:to:{}", self.email.to_lowercase())),
+            )
+            .exec(&txn)
+            .await?;
+
+        let pid = self.pid;
+        self.delete(&txn).await?;
+        txn.commit().await?;
+        tracing::info!(pid = pid.to_string(), "account deleted");
+
+        Ok(())
+    }
+
     /// Creates a JWT
     ///
     /// # Errors
@@ -271,7 +628,8 @@ impl ActiveModel {
     /// updates it in the database.
     ///
     /// This method is used to record the timestamp when the email verification
-    /// was sent and generate a unique verification token for the user.
+    /// was sent and generate a unique verification token for the user. The
+    /// token is returned for the email, only its hash is stored.
     ///
     /// # Errors
     ///
@@ -279,10 +637,12 @@ impl ActiveModel {
     pub async fn set_email_verification_sent(
         mut self,
         db: &DatabaseConnection,
-    ) -> ModelResult<Model> {
+    ) -> ModelResult<(Model, String)> {
+        let token = Uuid::new_v4().to_string();
         self.email_verification_sent_at = ActiveValue::set(Some(Local::now().into()));
-        self.email_verification_token = ActiveValue::Set(Some(Uuid::new_v4().to_string()));
-        self.update(db).await.map_err(ModelError::from)
+        self.email_verification_token = ActiveValue::Set(Some(hash_token(&token)));
+        let user = self.update(db).await?;
+        Ok((user, token))
     }
 
     /// Sets the information for a reset password request,
@@ -290,17 +650,21 @@ impl ActiveModel {
     /// database.
     ///
     /// This method records the timestamp when the reset password token is sent
-    /// and generates a unique token for the user.
-    ///
-    /// # Arguments
+    /// and generates a unique token for the user, replacing any earlier one.
+    /// The token is returned for the email, only its hash is stored.
     ///
     /// # Errors
     ///
     /// when has DB query error
-    pub async fn set_forgot_password_sent(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+    pub async fn set_forgot_password_sent(
+        mut self,
+        db: &DatabaseConnection,
+    ) -> ModelResult<(Model, String)> {
+        let token = Uuid::new_v4().to_string();
         self.reset_sent_at = ActiveValue::set(Some(Local::now().into()));
-        self.reset_token = ActiveValue::Set(Some(Uuid::new_v4().to_string()));
-        self.update(db).await.map_err(ModelError::from)
+        self.reset_token = ActiveValue::Set(Some(hash_token(&token)));
+        let user = self.update(db).await?;
+        Ok((user, token))
     }
 
     /// Records the verification time when a user verifies their
@@ -314,6 +678,8 @@ impl ActiveModel {
     /// when has DB query error
     pub async fn verified(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
         self.email_verified_at = ActiveValue::set(Some(Local::now().into()));
+        // Links are single use
+        self.email_verification_token = ActiveValue::set(None);
         self.update(db).await.map_err(ModelError::from)
     }
 
@@ -335,6 +701,105 @@ impl ActiveModel {
             ActiveValue::set(hash::hash_password(password).map_err(|e| ModelError::Any(e.into()))?);
         self.reset_token = ActiveValue::Set(None);
         self.reset_sent_at = ActiveValue::Set(None);
+        self.failed_login_count = ActiveValue::Set(0);
+        self.locked_until = ActiveValue::Set(None);
+        self.unlock_token = ActiveValue::Set(None);
+        self.unlock_sent_at = ActiveValue::Set(None);
+        self.update(db).await.map_err(ModelError::from)
+    }
+
+    /// Clears failed password attempts and any lock, once the user signed in
+    /// or followed their unlock link.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn unlock(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.failed_login_count = ActiveValue::Set(0);
+        self.last_failed_login_at = ActiveValue::Set(None);
+        self.locked_until = ActiveValue::Set(None);
+        self.unlock_token = ActiveValue::Set(None);
+        self.unlock_sent_at = ActiveValue::Set(None);
+        self.update(db).await.map_err(ModelError::from)
+    }
+
+    /// Generates an authenticator secret, which the user confirms with a
+    /// first code. Two-factor authentication stays off until then. A secret
+    /// that is still waiting to be confirmed is kept, so starting again
+    /// doesn't undo an authenticator that was already set up with it.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn start_totp_enrollment(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        if self.totp_secret.as_ref().is_some() {
+            return self.try_into_model().map_err(ModelError::from);
+        }
+        self.totp_secret = ActiveValue::Set(Some(totp::generate_secret()));
+        self.totp_enabled_at = ActiveValue::Set(None);
+        self.totp_last_step = ActiveValue::Set(None);
+        self.update(db).await.map_err(ModelError::from)
+    }
+
+    /// Records that a link to set up two-factor authentication was emailed,
+    /// replacing any earlier one. The token is returned for the email, only
+    /// its hash is stored.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn set_totp_enrollment_sent(
+        mut self,
+        db: &DatabaseConnection,
+    ) -> ModelResult<(Model, String)> {
+        let token = Uuid::new_v4().to_string();
+        self.totp_enrollment_sent_at = ActiveValue::set(Some(Local::now().into()));
+        self.totp_enrollment_token = ActiveValue::Set(Some(hash_token(&token)));
+        let user = self.update(db).await?;
+        Ok((user, token))
+    }
+
+    /// Records that a link to confirm deleting the account was emailed,
+    /// replacing any earlier one. The token is returned for the email, only
+    /// its hash is stored.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn set_deletion_sent(
+        mut self,
+        db: &DatabaseConnection,
+    ) -> ModelResult<(Model, String)> {
+        let token = Uuid::new_v4().to_string();
+        self.deletion_sent_at = ActiveValue::set(Some(Local::now().into()));
+        self.deletion_token = ActiveValue::Set(Some(hash_token(&token)));
+        let user = self.update(db).await?;
+        Ok((user, token))
+    }
+
+    /// Turns two-factor authentication on, once the user confirmed their
+    /// authenticator secret with a code. The link to set it up stops working.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn enable_totp(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.totp_enabled_at = ActiveValue::Set(Some(Local::now().into()));
+        self.totp_enrollment_token = ActiveValue::Set(None);
+        self.totp_enrollment_sent_at = ActiveValue::Set(None);
+        self.update(db).await.map_err(ModelError::from)
+    }
+
+    /// Turns two-factor authentication off and forgets the authenticator
+    /// secret.
+    ///
+    /// # Errors
+    ///
+    /// when has DB query error
+    pub async fn disable_totp(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.totp_secret = ActiveValue::Set(None);
+        self.totp_enabled_at = ActiveValue::Set(None);
+        self.totp_last_step = ActiveValue::Set(None);
         self.update(db).await.map_err(ModelError::from)
     }
 
@@ -342,16 +807,21 @@ impl ActiveModel {
     ///
     /// Generates a random token with a specified length and sets an expiration time
     /// for the magic link. This method is used to initiate the magic link authentication flow.
+    /// The token is returned for the email, only its hash is stored.
     ///
     /// # Errors
     /// - Returns an error if database update fails
-    pub async fn create_magic_link(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+    pub async fn create_magic_link(
+        mut self,
+        db: &DatabaseConnection,
+    ) -> ModelResult<(Model, String)> {
         let random_str = hash::random_string(MAGIC_LINK_LENGTH as usize);
         let expired = Local::now() + Duration::minutes(MAGIC_LINK_EXPIRATION_MIN.into());
 
-        self.magic_link_token = ActiveValue::set(Some(random_str));
+        self.magic_link_token = ActiveValue::set(Some(hash_token(&random_str)));
         self.magic_link_expiration = ActiveValue::set(Some(expired.into()));
-        self.update(db).await.map_err(ModelError::from)
+        let user = self.update(db).await?;
+        Ok((user, random_str))
     }
 
     /// Verifies and invalidates the magic link after successful authentication.
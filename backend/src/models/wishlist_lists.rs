@@ -0,0 +1,121 @@
+pub use super::_entities::wishlist_lists::{ActiveModel, Column, Entity, Model};
+use loco_rs::{
+    hash,
+    model::{ModelError, ModelResult},
+};
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, ActiveValue};
+pub type WishlistLists = Entity;
+
+/// Name of the list that is created for a user when they first save a
+/// product without picking a list.
+pub const DEFAULT_LIST_NAME: &str = "Wishlist";
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Finds a list by id, as long as it belongs to the given user.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_for_user<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+        user_id: i32,
+    ) -> ModelResult<Option<Self>> {
+        Ok(Entity::find_by_id(id)
+            .filter(Column::UserId.eq(user_id))
+            .one(db)
+            .await?)
+    }
+
+    /// Finds a list by its public share token.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_by_share_token<C: ConnectionTrait>(
+        db: &C,
+        token: &str,
+    ) -> ModelResult<Option<Self>> {
+        Ok(Entity::find()
+            .filter(Column::ShareToken.eq(token))
+            .one(db)
+            .await?)
+    }
+
+    /// Returns the user's default list, creating it on first use. Concurrent
+    /// first uses end up with the same list, as a user can only have one
+    /// default list.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn find_or_create_default<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+    ) -> ModelResult<Self> {
+        let existing = Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::IsDefault.eq(true))
+            .one(db)
+            .await?;
+        if let Some(list) = existing {
+            return Ok(list);
+        }
+
+        Entity::insert(ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            name: ActiveValue::Set(DEFAULT_LIST_NAME.to_string()),
+            is_default: ActiveValue::Set(true),
+            ..Default::default()
+        })
+        .on_conflict(OnConflict::new().do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
+
+        Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::IsDefault.eq(true))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// Gives the list a new share token, which invalidates any previously
+    /// shared link.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn share<C: ConnectionTrait>(mut self, db: &C) -> ModelResult<Model> {
+        self.share_token = ActiveValue::Set(Some(hash::random_string(32)));
+        Ok(self.update(db).await?)
+    }
+
+    /// Removes the share token, so the list is no longer publicly readable.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn unshare<C: ConnectionTrait>(mut self, db: &C) -> ModelResult<Model> {
+        self.share_token = ActiveValue::Set(None);
+        Ok(self.update(db).await?)
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,5 +1,8 @@
-pub use super::_entities::wishlists::{ActiveModel, Entity, Model};
-use sea_orm::entity::prelude::*;
+use std::collections::HashMap;
+
+pub use super::_entities::wishlists::{ActiveModel, Column, Entity, Model};
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, QuerySelect, QueryTrait};
 pub type Wishlists = Entity;
 
 #[async_trait::async_trait]
@@ -19,7 +22,57 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// Counts the items in each of the given lists. Empty lists are missing
+    /// from the result.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn count_by_list<C: ConnectionTrait>(
+        db: &C,
+        list_ids: impl IntoIterator<Item = i32>,
+    ) -> ModelResult<HashMap<i32, i64>> {
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::WishlistListId)
+            .column_as(Column::Id.count(), "count")
+            .filter(Column::WishlistListId.is_in(list_ids))
+            .group_by(Column::WishlistListId)
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    /// Deletes the items pinned to a variant about to be deleted from lists
+    /// that also hold its product without a variant, which the pinned items
+    /// would become duplicates of.
+    ///
+    /// # Errors
+    /// Returns an error if the underlying database operation fails.
+    pub async fn delete_pinned_duplicates<C: ConnectionTrait>(
+        db: &C,
+        product_id: i32,
+        variant_id: i32,
+    ) -> ModelResult<()> {
+        Entity::delete_many()
+            .filter(Column::ProductVariantId.eq(variant_id))
+            .filter(
+                Column::WishlistListId.in_subquery(
+                    Entity::find()
+                        .select_only()
+                        .column(Column::WishlistListId)
+                        .filter(Column::ProductId.eq(product_id))
+                        .filter(Column::ProductVariantId.is_null())
+                        .into_query(),
+                ),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
 
 // implement your write-oriented logic here
 impl ActiveModel {}
@@ -0,0 +1,252 @@
+//! Sign in with OpenID Connect providers such as Google: the authorization
+//! code flow with PKCE (RFC 7636), and validation of the ID token against the
+//! keys the provider publishes.
+
+use std::{fmt, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::settings::OAuthProviderSettings;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Asymmetric algorithms only: a token signed with a shared secret could be
+/// forged by anyone who knows the client secret.
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+#[derive(Debug)]
+pub enum Error {
+    /// The provider could not be reached or answered with an error status.
+    Http(reqwest::Error),
+    /// The provider answered with something that doesn't follow the spec,
+    /// e.g. discovery for another issuer or no ID token.
+    Provider(String),
+    /// The ID token is not signed by the provider, not meant for us, expired
+    /// or replayed.
+    InvalidToken(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(err) => write!(f, "request to identity provider failed: {err}"),
+            Self::Provider(msg) => write!(f, "unexpected identity provider response: {msg}"),
+            Self::InvalidToken(msg) => write!(f, "invalid ID token: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self::InvalidToken(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The account a provider signed a user in with.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// The provider's stable identifier of the account. Emails can change,
+    /// so identities are matched on this.
+    pub subject: String,
+    pub email: Option<String>,
+    /// Whether the provider checked that the user owns `email`.
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// The PKCE challenge sent with the authorization request, proving the code
+/// is redeemed by whoever started the flow.
+#[must_use]
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    /// A boolean, though some providers send it as a string.
+    email_verified: Option<serde_json::Value>,
+    name: Option<String>,
+}
+
+/// A provider whose endpoints were discovered.
+pub struct Provider<'a> {
+    settings: &'a OAuthProviderSettings,
+    metadata: Metadata,
+    http: reqwest::Client,
+}
+
+impl<'a> Provider<'a> {
+    /// Fetches the provider's endpoints from its discovery document.
+    ///
+    /// # Errors
+    /// When the document can't be fetched or is for another issuer.
+    pub async fn discover(settings: &'a OAuthProviderSettings) -> Result<Self> {
+        let http = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?;
+        let issuer = settings.issuer.trim_end_matches('/');
+        let metadata: Metadata = http
+            .get(format!("{issuer}/.well-known/openid-configuration"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(Error::Provider(format!(
+                "discovery document is for issuer {}",
+                metadata.issuer
+            )));
+        }
+
+        Ok(Self {
+            settings,
+            metadata,
+            http,
+        })
+    }
+
+    /// The provider page to send the user to for signing in.
+    ///
+    /// # Errors
+    /// When the provider's authorization endpoint is not a valid URL.
+    pub fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String> {
+        let url = Url::parse_with_params(
+            &self.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.settings.client_id.as_str()),
+                ("redirect_uri", self.settings.redirect_uri.as_str()),
+                ("scope", self.settings.scopes.join(" ").as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|err| Error::Provider(format!("invalid authorization endpoint: {err}")))?;
+
+        Ok(url.into())
+    }
+
+    /// Redeems the code the provider sent the user back with, and returns the
+    /// account from the ID token once it checks out.
+    ///
+    /// # Errors
+    /// When the provider refuses the code or the ID token is not valid.
+    pub async fn exchange(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<Identity> {
+        let response: TokenResponse = self
+            .http
+            .post(&self.metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.settings.redirect_uri.as_str()),
+                ("client_id", self.settings.client_id.as_str()),
+                ("client_secret", self.settings.client_secret.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let id_token = response
+            .id_token
+            .ok_or_else(|| Error::Provider("no ID token in token response".to_string()))?;
+
+        let claims = self.validate(&id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(Error::InvalidToken("nonce does not match".to_string()));
+        }
+
+        let email_verified = match &claims.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+
+        Ok(Identity {
+            subject: claims.sub,
+            email: claims.email,
+            email_verified,
+            name: claims.name,
+        })
+    }
+
+    async fn validate(&self, id_token: &str) -> Result<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+        if !ALGORITHMS.contains(&header.alg) {
+            return Err(Error::InvalidToken(format!(
+                "{:?} signatures are not accepted",
+                header.alg
+            )));
+        }
+
+        let keys: JwkSet = self
+            .http
+            .get(&self.metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or_else(|| Error::InvalidToken("signed with an unknown key".to_string()))?;
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.settings.client_id]);
+        // Google also issues tokens naming itself without the scheme
+        let issuer = self.metadata.issuer.as_str();
+        validation.set_issuer(&[issuer, issuer.trim_start_matches("https://")]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        Ok(jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)?.claims)
+    }
+}
@@ -0,0 +1,119 @@
+//! What staff may do. Each staff member has a role, and each role grants a
+//! set of permissions. Handlers reserved for some staff take an
+//! [`Authorized`] extractor naming the permission they need, e.g.
+//! `Authorized<require::ProductsWrite>`; everyone else gets
+//! `403 Forbidden`.
+
+use std::marker::PhantomData;
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::forbidden,
+    models::_entities::{sea_orm_active_enums::UserRole, users},
+};
+
+/// A permission an [`Authorized`] extractor requires.
+pub trait Required {
+    const PERMISSION: Permission;
+}
+
+macro_rules! permissions {
+    ($($(#[$doc:meta])* $variant:ident => $name:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+        pub enum Permission {
+            $($(#[$doc])* #[serde(rename = $name)] $variant,)*
+        }
+
+        impl Permission {
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+        }
+
+        /// One type per permission, for naming it in [`Authorized`].
+        pub mod require {
+            $(
+                pub struct $variant;
+
+                impl super::Required for $variant {
+                    const PERMISSION: super::Permission = super::Permission::$variant;
+                }
+            )*
+        }
+    };
+}
+
+permissions! {
+    /// Create, edit and delete products and their variants.
+    ProductsWrite => "products.write",
+    /// Create, edit and delete brands.
+    BrandsWrite => "brands.write",
+    /// Create, edit and delete categories.
+    CategoriesWrite => "categories.write",
+    /// See every customer's orders, invoices and packing slips.
+    OrdersRead => "orders.read",
+    /// Move orders through their statuses and cancel them.
+    OrdersUpdateStatus => "orders.update_status",
+    /// Change the amount, payment method and shipping address of orders.
+    OrdersWrite => "orders.write",
+    /// Approve, reject, edit and delete customers' reviews.
+    ReviewsModerate => "reviews.moderate",
+    /// See the sales reports.
+    ReportsRead => "reports.read",
+    /// See the audit log.
+    AuditRead => "audit.read",
+    /// Give staff roles and take them away.
+    RolesAssign => "roles.assign",
+}
+
+impl UserRole {
+    #[must_use]
+    pub const fn permissions(self) -> &'static [Permission] {
+        match self {
+            Self::Admin => Permission::ALL,
+            Self::CatalogManager => &[
+                Permission::ProductsWrite,
+                Permission::BrandsWrite,
+                Permission::CategoriesWrite,
+                Permission::ReportsRead,
+            ],
+            Self::Fulfillment => &[Permission::OrdersRead, Permission::OrdersUpdateStatus],
+            Self::Support => &[
+                Permission::OrdersRead,
+                Permission::OrdersUpdateStatus,
+                Permission::ReviewsModerate,
+            ],
+        }
+    }
+}
+
+/// The signed in user, who has the permission `P`.
+pub struct Authorized<P> {
+    pub user: users::Model,
+    permission: PhantomData<P>,
+}
+
+impl<S, P> FromRequestParts<S> for Authorized<P>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    P: Required + Send,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let auth = auth::JWTWithUser::<users::Model>::from_request_parts(parts, state).await?;
+        if !auth.user.can(P::PERMISSION) {
+            return forbidden("You are not authorized to perform this action.");
+        }
+
+        Ok(Self {
+            user: auth.user,
+            permission: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,190 @@
+//! The registration policy: which email domains accounts may be registered
+//! with, and which passwords are strong enough. Violations are returned as
+//! validation errors for the offending field, e.g.
+//! `{"errors": {"password": [{"code": "too_short", ...}]}}`.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+};
+
+use loco_rs::{prelude::*, validation::ModelValidationErrors};
+use validator::{ValidationError, ValidationErrors};
+
+use crate::settings::{PasswordSettings, RegistrationSettings};
+
+/// Commonly used passwords, bundled with the binary.
+const COMMON_PASSWORDS: &str = include_str!("data/common_passwords.txt");
+
+static COMMON_PASSWORD_SET: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+/// Disposable domains by the file they were read from, so a changed setting
+/// is picked up without reading the file on every registration.
+static DISPOSABLE_DOMAINS: Mutex<Option<(String, HashSet<String>)>> = Mutex::new(None);
+
+fn list_entries(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn common_passwords() -> &'static HashSet<&'static str> {
+    COMMON_PASSWORD_SET.get_or_init(|| list_entries(COMMON_PASSWORDS).collect())
+}
+
+fn is_disposable(path: &str, domain: &str) -> Result<bool> {
+    let mut cache = DISPOSABLE_DOMAINS
+        .lock()
+        .map_err(|_| Error::string("disposable domain list lock poisoned"))?;
+    if cache.as_ref().is_none_or(|(cached, _)| cached != path) {
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            Error::string(&format!(
+                "could not read disposable domains from {path}: {err}"
+            ))
+        })?;
+        let domains = list_entries(&content).map(str::to_lowercase).collect();
+        *cache = Some((path.to_string(), domains));
+    }
+
+    Ok(cache
+        .as_ref()
+        .is_some_and(|(_, domains)| matches_any(domain, domains.iter())))
+}
+
+/// Whether `domain` is one of `domains` or a subdomain of one.
+fn matches_any<'a>(domain: &str, domains: impl IntoIterator<Item = &'a String>) -> bool {
+    domains.into_iter().any(|listed| {
+        let listed = listed.trim().to_lowercase();
+        domain == listed
+            || domain
+                .strip_suffix(&listed)
+                .is_some_and(|sub| sub.ends_with('.'))
+    })
+}
+
+fn error(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Borrowed(message))
+}
+
+fn email_errors(settings: &RegistrationSettings, email: &str) -> Result<Vec<ValidationError>> {
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        // malformed addresses are left to the user model's validation
+        return Ok(vec![]);
+    };
+    let domain = domain.trim().to_lowercase();
+
+    let mut errors = vec![];
+    if !settings.allowed_domains.is_empty() && !matches_any(&domain, &settings.allowed_domains) {
+        errors.push(error(
+            "domain_not_allowed",
+            "Accounts can't be registered with this email domain.",
+        ));
+    } else if matches_any(&domain, &settings.denied_domains) {
+        errors.push(error(
+            "domain_denied",
+            "Accounts can't be registered with this email domain.",
+        ));
+    } else if let Some(path) = &settings.disposable_domains_file {
+        if is_disposable(path, &domain)? {
+            errors.push(error(
+                "disposable_domain",
+                "Disposable email addresses can't be used.",
+            ));
+        }
+    }
+
+    Ok(errors)
+}
+
+fn password_errors(settings: &PasswordSettings, password: &str) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    if password.chars().count() < settings.min_length {
+        let mut err = error("too_short", "Password is too short.");
+        err.add_param(Cow::Borrowed("min"), &settings.min_length);
+        errors.push(err);
+    }
+    if settings.require_lowercase && !password.chars().any(char::is_lowercase) {
+        errors.push(error(
+            "missing_lowercase",
+            "Password must contain a lowercase letter.",
+        ));
+    }
+    if settings.require_uppercase && !password.chars().any(char::is_uppercase) {
+        errors.push(error(
+            "missing_uppercase",
+            "Password must contain an uppercase letter.",
+        ));
+    }
+    if settings.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        errors.push(error("missing_digit", "Password must contain a digit."));
+    }
+    if settings.require_symbol && password.chars().all(char::is_alphanumeric) {
+        errors.push(error(
+            "missing_symbol",
+            "Password must contain a character that is neither a letter nor a digit.",
+        ));
+    }
+    if settings.reject_common && common_passwords().contains(password.to_lowercase().as_str()) {
+        errors.push(error(
+            "common_password",
+            "This password is too common, choose another one.",
+        ));
+    }
+
+    errors
+}
+
+fn into_result(fields: Vec<(&'static str, Vec<ValidationError>)>) -> Result<()> {
+    let mut errors = ValidationErrors::new();
+    for (field, field_errors) in fields {
+        for err in field_errors {
+            errors.add(field, err);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation(ModelValidationErrors::from(errors)))
+    }
+}
+
+/// Checks the email address and password someone registers with.
+///
+/// # Errors
+/// A validation error for each field that breaks the policy, or when the
+/// disposable domain list can't be read.
+pub fn check_registration(
+    settings: &RegistrationSettings,
+    email: &str,
+    password: &str,
+) -> Result<()> {
+    into_result(vec![
+        ("email", email_errors(settings, email)?),
+        ("password", password_errors(&settings.password, password)),
+    ])
+}
+
+/// Checks that an email address is from a domain accounts may have.
+///
+/// # Errors
+/// A validation error for `email` when the domain is not allowed, or when
+/// the disposable domain list can't be read.
+pub fn check_email(settings: &RegistrationSettings, email: &str) -> Result<()> {
+    into_result(vec![("email", email_errors(settings, email)?)])
+}
+
+/// Checks a new password for an existing account, reporting problems for
+/// `field`.
+///
+/// # Errors
+/// A validation error for `field` when the password is not strong enough.
+pub fn check_password(
+    settings: &RegistrationSettings,
+    field: &'static str,
+    password: &str,
+) -> Result<()> {
+    into_result(vec![(field, password_errors(&settings.password, password))])
+}
@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+
+use loco_rs::prelude::*;
+use serde::Deserialize;
+
+/// Application specific settings, read from the `settings` section of the
+/// environment config. Every section falls back to its defaults when omitted.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub account_deletion: AccountDeletionSettings,
+    pub carts: CartSettings,
+    pub email_verification: EmailVerificationSettings,
+    pub idempotency: IdempotencySettings,
+    pub login: LoginSettings,
+    pub oauth: OAuthSettings,
+    pub password_reset: PasswordResetSettings,
+    pub rate_limits: RateLimitSettings,
+    pub registration: RegistrationSettings,
+    pub reviews: ReviewSettings,
+    pub sessions: SessionSettings,
+    pub two_factor: TwoFactorSettings,
+    pub wishlists: WishlistSettings,
+}
+
+impl Settings {
+    /// Loads the settings from the application context.
+    ///
+    /// # Errors
+    ///
+    /// When the `settings` section of the config has an invalid shape
+    pub fn from_context(ctx: &AppContext) -> Result<Self> {
+        ctx.config
+            .settings
+            .clone()
+            .map_or_else(|| Ok(Self::default()), |v| Ok(serde_json::from_value(v)?))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CartSettings {
+    /// How long an anonymous cart and its token stay valid.
+    pub guest_cart_ttl_days: i64,
+    /// Largest quantity of a single variant a cart may hold.
+    pub max_quantity_per_line: i32,
+    /// Hours without changes after which a cart counts as abandoned.
+    pub abandoned_after_hours: i64,
+    /// Minimum hours between two reminders about the same cart.
+    pub reminder_interval_hours: i64,
+    /// Reminders sent at most about the same cart.
+    pub max_reminders: u64,
+}
+
+impl Default for CartSettings {
+    fn default() -> Self {
+        Self {
+            guest_cart_ttl_days: 30,
+            max_quantity_per_line: 10,
+            abandoned_after_hours: 24,
+            reminder_interval_hours: 72,
+            max_reminders: 2,
+        }
+    }
+}
+
+/// What users who haven't verified their email address yet are kept from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationGate {
+    /// Nothing, verifying is optional.
+    None,
+    /// Placing orders.
+    #[default]
+    Checkout,
+    /// Signing in at all.
+    Login,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmailVerificationSettings {
+    pub required_for: VerificationGate,
+    /// How long the link in a verification email stays valid.
+    pub token_ttl_hours: i64,
+    /// Minimum time between two verification emails to the same user. Each
+    /// one comes with a new link.
+    pub resend_cooldown_minutes: i64,
+}
+
+impl Default for EmailVerificationSettings {
+    fn default() -> Self {
+        Self {
+            required_for: VerificationGate::default(),
+            token_ttl_hours: 24,
+            resend_cooldown_minutes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct IdempotencySettings {
+    /// How long a request's `Idempotency-Key` and response are kept for
+    /// replaying.
+    pub retention_hours: i64,
+    /// How long a request holds the `Idempotency-Key` it claimed. A retry
+    /// after that takes over a key whose request never stored a response.
+    pub lease_seconds: i64,
+}
+
+impl Default for IdempotencySettings {
+    fn default() -> Self {
+        Self {
+            retention_hours: 24,
+            lease_seconds: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoginSettings {
+    /// Failed password attempts in a row before further attempts are delayed.
+    pub free_failed_attempts: i32,
+    /// Delay after the first failure past the free ones. It doubles with every
+    /// further failure.
+    pub failed_attempt_delay_seconds: u64,
+    /// Failed password attempts in a row after which the account is locked
+    /// and its owner is emailed an unlock link.
+    pub max_failed_attempts: i32,
+    /// How long a locked account stays locked unless unlocked by email.
+    pub lockout_minutes: i64,
+}
+
+impl LoginSettings {
+    /// How long to wait after `failures` failed password attempts in a row
+    /// before the next one, if at all: nothing during the free attempts, the
+    /// lockout once there were too many, and a doubling delay in between.
+    #[must_use]
+    pub fn failure_delay(&self, failures: i32) -> Option<chrono::Duration> {
+        if failures >= self.max_failed_attempts {
+            Some(chrono::Duration::minutes(self.lockout_minutes))
+        } else if failures >= self.free_failed_attempts {
+            let doublings = (failures - self.free_failed_attempts).min(16);
+            let delay = self.failed_attempt_delay_seconds << doublings;
+            Some(chrono::Duration::seconds(
+                i64::try_from(delay).unwrap_or(i64::MAX),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for LoginSettings {
+    fn default() -> Self {
+        Self {
+            free_failed_attempts: 3,
+            failed_attempt_delay_seconds: 2,
+            max_failed_attempts: 5,
+            lockout_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OAuthSettings {
+    /// How long a user may take to sign in at the identity provider.
+    pub state_ttl_minutes: i64,
+    /// OpenID Connect providers users can sign in with, by the name used in
+    /// `/api/auth/oauth/{provider}` paths.
+    pub providers: BTreeMap<String, OAuthProviderSettings>,
+}
+
+impl Default for OAuthSettings {
+    fn default() -> Self {
+        Self {
+            state_ttl_minutes: 10,
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OAuthProviderSettings {
+    /// Endpoints and signing keys are discovered from
+    /// `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    /// Providers without a client id are turned off.
+    pub client_id: String,
+    pub client_secret: String,
+    /// Page the provider sends the browser back to with a code, which posts
+    /// it to the callback endpoint.
+    pub redirect_uri: String,
+    #[serde(default = "default_oauth_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_oauth_scopes() -> Vec<String> {
+    ["openid", "email", "profile"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccountDeletionSettings {
+    /// How long the emailed link to confirm deleting an account with, instead
+    /// of the password, stays valid. Requesting another link replaces it.
+    pub link_ttl_minutes: i64,
+}
+
+impl Default for AccountDeletionSettings {
+    fn default() -> Self {
+        Self {
+            link_ttl_minutes: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PasswordResetSettings {
+    /// How long the link in a password reset email stays valid. Requesting
+    /// another reset replaces the link.
+    pub token_ttl_minutes: i64,
+}
+
+impl Default for PasswordResetSettings {
+    fn default() -> Self {
+        Self {
+            token_ttl_minutes: 60,
+        }
+    }
+}
+
+/// Requests allowed per window on the authentication endpoints. Requests are
+/// counted per client IP, as resolved by the `remote_ip` middleware, and
+/// emails also per recipient address.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub window_minutes: i64,
+    pub logins_per_ip: i32,
+    /// Password resets and account unlocks, which take a token that could
+    /// otherwise be guessed.
+    pub token_attempts_per_ip: i32,
+    /// Password reset, magic link and verification emails.
+    pub emails_per_ip: i32,
+    pub emails_per_address: i32,
+    /// Anonymous carts started, which are kept until they expire.
+    pub guest_carts_per_ip: i32,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            window_minutes: 15,
+            logins_per_ip: 20,
+            token_attempts_per_ip: 10,
+            emails_per_ip: 10,
+            emails_per_address: 3,
+            guest_carts_per_ip: 20,
+        }
+    }
+}
+
+/// Who may register and what passwords they may choose. The password rules
+/// also apply when resetting or changing a password.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RegistrationSettings {
+    /// Email domains accounts may be registered with. Any domain when empty.
+    /// Subdomains of a listed domain are included.
+    pub allowed_domains: Vec<String>,
+    /// Email domains accounts may not be registered with, subdomains included.
+    pub denied_domains: Vec<String>,
+    /// File listing throwaway email providers' domains, one per line, which
+    /// are denied too. Lines starting with `#` are ignored.
+    pub disposable_domains_file: Option<String>,
+    pub password: PasswordSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PasswordSettings {
+    /// Minimum number of characters.
+    pub min_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    /// At least one character that is neither a letter nor a digit.
+    pub require_symbol: bool,
+    /// Whether passwords found in the bundled list of commonly used passwords
+    /// are rejected.
+    pub reject_common: bool,
+}
+
+impl Default for PasswordSettings {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_lowercase: true,
+            require_uppercase: false,
+            require_digit: true,
+            require_symbol: false,
+            reject_common: true,
+        }
+    }
+}
+
+/// When a newly written or edited review goes live.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoPublish {
+    /// Publish every review immediately.
+    Always,
+    /// Publish reviews that pass the content filter, hold the rest.
+    #[default]
+    Clean,
+    /// Hold every review for a staff member.
+    Never,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReviewSettings {
+    pub auto_publish: AutoPublish,
+    /// Words that send a review to the moderation queue.
+    pub blocked_words: Vec<String>,
+    /// Whether reviews containing links can be published without moderation.
+    pub allow_links: bool,
+    /// Number of customer reports after which a published review is hidden
+    /// again until a staff member looks at it.
+    pub report_threshold: u64,
+}
+
+impl Default for ReviewSettings {
+    fn default() -> Self {
+        Self {
+            auto_publish: AutoPublish::default(),
+            blocked_words: ["fuck", "shit", "bitch", "asshole", "bastard"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            allow_links: false,
+            report_threshold: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    /// How long a refresh token stays valid. Every refresh issues a new token
+    /// with a fresh lifetime.
+    pub refresh_token_ttl_days: i64,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            refresh_token_ttl_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TwoFactorSettings {
+    /// Whether staff accounts have to set up two-factor authentication, which
+    /// they are asked to do on their next login with an emailed link.
+    pub required_for_staff: bool,
+    /// Name accounts are listed under in authenticator apps.
+    pub issuer: String,
+    /// How long the second step of a login may take.
+    pub challenge_ttl_minutes: i64,
+    /// Codes that may be tried in the second step of a login.
+    pub max_challenge_attempts: i32,
+    /// How long the emailed link to set up two-factor authentication with
+    /// stays valid.
+    pub enrollment_link_ttl_minutes: i64,
+}
+
+impl Default for TwoFactorSettings {
+    fn default() -> Self {
+        Self {
+            required_for_staff: false,
+            issuer: "Shoes Store".to_string(),
+            challenge_ttl_minutes: 5,
+            max_challenge_attempts: 5,
+            enrollment_link_ttl_minutes: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WishlistSettings {
+    /// Minimum time between two price-drop emails about the same product to
+    /// the same user.
+    pub price_alert_cooldown_hours: i64,
+}
+
+impl Default for WishlistSettings {
+    fn default() -> Self {
+        Self {
+            price_alert_cooldown_hours: 72,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{Duration, Utc};
+use loco_rs::prelude::*;
+use sea_orm::{prelude::DateTimeWithTimeZone, QuerySelect};
+
+use crate::{
+    mailers::cart::CartMailer,
+    models::{
+        _entities::{cart_items, orders, users},
+        cart_reminders, product_variants, products,
+    },
+    settings::Settings,
+};
+
+/// Emails users whose cart hasn't changed for a while and who haven't ordered
+/// since. Every send is recorded against the cart, so running the task again
+/// never mails the same reminder twice.
+pub struct SendCartReminders;
+
+#[async_trait]
+impl Task for SendCartReminders {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "send_cart_reminders".to_string(),
+            detail: "Email users who left items in their cart".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let settings = Settings::from_context(ctx)?.carts;
+        let now = Utc::now();
+        let abandoned_before = now - Duration::hours(settings.abandoned_after_hours);
+        let last_reminder_before = now - Duration::hours(settings.reminder_interval_hours);
+
+        // A cart is identified by its owner and when the shopper last changed
+        // it, so editing the cart starts the reminders over. Viewing it
+        // doesn't, even when that lowers a quantity to what is in stock
+        let carts = cart_items::Entity::find()
+            .select_only()
+            .column(cart_items::Column::UserId)
+            .column_as(cart_items::Column::UpdatedAt.max(), "cart_updated_at")
+            .filter(cart_items::Column::UserId.is_not_null())
+            .group_by(cart_items::Column::UserId)
+            .into_tuple::<(i32, DateTimeWithTimeZone)>()
+            .all(&ctx.db)
+            .await?;
+
+        let mut sent = 0;
+        for (user_id, cart_updated_at) in carts {
+            if cart_updated_at >= abandoned_before {
+                continue;
+            }
+
+            let ordered_since = orders::Entity::find()
+                .filter(orders::Column::UserId.eq(user_id))
+                .filter(orders::Column::CreatedAt.gte(cart_updated_at))
+                .one(&ctx.db)
+                .await?
+                .is_some();
+            if ordered_since {
+                continue;
+            }
+
+            let reminders =
+                cart_reminders::Model::for_cart(&ctx.db, user_id, cart_updated_at).await?;
+            if reminders.len() as u64 >= settings.max_reminders {
+                continue;
+            }
+            if reminders
+                .first()
+                .is_some_and(|last| last.created_at >= last_reminder_before)
+            {
+                continue;
+            }
+
+            let Some(user) = users::Entity::find_by_id(user_id)
+                .filter(users::Column::IsActive.eq(true))
+                .one(&ctx.db)
+                .await?
+            else {
+                continue;
+            };
+
+            let items = cart_items::Entity::find()
+                .filter(cart_items::Column::UserId.eq(user_id))
+                .find_also_related(product_variants::Entity)
+                .and_also_related(products::Entity)
+                .all(&ctx.db)
+                .await?
+                .into_iter()
+                .filter_map(|(item, variant, product)| {
+                    let product = product.filter(|p| p.is_active)?;
+                    Some((item.quantity.unwrap_or(0), variant?, product))
+                })
+                .collect::<Vec<_>>();
+            if items.is_empty() {
+                continue;
+            }
+
+            let sequence = i32::try_from(reminders.len()).unwrap_or(i32::MAX) + 1;
+            if !cart_reminders::Model::claim(&ctx.db, user_id, cart_updated_at, sequence).await? {
+                continue;
+            }
+
+            if let Err(err) = CartMailer::send_reminder(ctx, &user, &items).await {
+                tracing::error!(
+                    err = err.to_string(),
+                    user_pid = user.pid.to_string(),
+                    "could not send cart reminder"
+                );
+                continue;
+            }
+            sent += 1;
+        }
+        tracing::info!(sent, "sent cart reminders");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::Utc;
+use loco_rs::prelude::*;
+
+use crate::models::guest_carts;
+
+/// Deletes anonymous carts, and their lines, once their token has expired.
+pub struct ExpireGuestCarts;
+
+#[async_trait]
+impl Task for ExpireGuestCarts {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "expire_guest_carts".to_string(),
+            detail: "Delete guest carts whose cart token has expired".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let deleted = guest_carts::Entity::delete_many()
+            .filter(guest_carts::Column::ExpiresAt.lte(Utc::now()))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(deleted = deleted.rows_affected, "expired guest carts");
+
+        Ok(())
+    }
+}
@@ -1 +1,5 @@
-
+pub mod cart_reminders;
+pub mod expire_guest_carts;
+pub mod purge_auth_throttles;
+pub mod purge_idempotency_keys;
+pub mod purge_user_sessions;
@@ -0,0 +1,31 @@
+use chrono::{Duration, Utc};
+use loco_rs::prelude::*;
+
+use crate::{models::auth_throttles, settings::Settings};
+
+/// Deletes rate limit counters whose window has passed. The next request
+/// for their key starts a new one anyway.
+pub struct PurgeAuthThrottles;
+
+#[async_trait]
+impl Task for PurgeAuthThrottles {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "purge_auth_throttles".to_string(),
+            detail: "Delete rate limit counters whose window has passed".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let settings = Settings::from_context(ctx)?;
+        let window = Duration::minutes(settings.rate_limits.window_minutes);
+
+        let deleted = auth_throttles::Entity::delete_many()
+            .filter(auth_throttles::Column::WindowStartedAt.lt(Utc::now() - window))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(deleted = deleted.rows_affected, "purged auth throttles");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use chrono::{Duration, Utc};
+use loco_rs::prelude::*;
+
+use crate::{models::idempotency_keys, settings::Settings};
+
+/// Deletes stored idempotency keys and responses past their retention window.
+pub struct PurgeIdempotencyKeys;
+
+#[async_trait]
+impl Task for PurgeIdempotencyKeys {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "purge_idempotency_keys".to_string(),
+            detail: "Delete idempotency keys past their retention window".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let settings = Settings::from_context(ctx)?;
+        let expired_before = Utc::now() - Duration::hours(settings.idempotency.retention_hours);
+        let deleted = idempotency_keys::Entity::delete_many()
+            .filter(idempotency_keys::Column::CreatedAt.lt(expired_before))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(deleted = deleted.rows_affected, "purged idempotency keys");
+
+        Ok(())
+    }
+}
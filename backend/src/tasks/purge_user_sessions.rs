@@ -0,0 +1,45 @@
+use chrono::Utc;
+use loco_rs::prelude::*;
+
+use crate::models::{oauth_states, two_factor_challenges, user_sessions};
+
+/// Deletes sign-in sessions whose refresh tokens have expired. Revoked
+/// sessions are kept until then so reuse of their tokens is still detected.
+/// Expired challenges of two-step logins and sign ins with identity
+/// providers that were never completed go too.
+pub struct PurgeUserSessions;
+
+#[async_trait]
+impl Task for PurgeUserSessions {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "purge_user_sessions".to_string(),
+            detail: "Delete expired sessions, two-factor challenges and OAuth states".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let deleted = user_sessions::Entity::delete_many()
+            .filter(user_sessions::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(deleted = deleted.rows_affected, "purged user sessions");
+
+        let deleted = two_factor_challenges::Entity::delete_many()
+            .filter(two_factor_challenges::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(
+            deleted = deleted.rows_affected,
+            "purged two-factor challenges"
+        );
+
+        let deleted = oauth_states::Entity::delete_many()
+            .filter(oauth_states::Column::ExpiresAt.lt(Utc::now()))
+            .exec(&ctx.db)
+            .await?;
+        tracing::info!(deleted = deleted.rows_affected, "purged oauth states");
+
+        Ok(())
+    }
+}
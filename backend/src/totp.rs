@@ -0,0 +1,129 @@
+//! Time-based one-time passwords (RFC 6238) as generated by authenticator
+//! apps: HMAC-SHA1 over 30 second time steps, truncated to 6 digits.
+
+use std::fmt::Write as _;
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+const SECRET_BYTES: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: usize = 6;
+/// Codes of the previous and next time step are accepted too, for clocks
+/// that drift.
+const SKEW_STEPS: i64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a random secret, base32 encoded as authenticator apps expect.
+#[must_use]
+pub fn generate_secret() -> String {
+    base32_encode(&rand::random::<[u8; SECRET_BYTES]>())
+}
+
+/// The `otpauth://` URI that authenticator apps read from a QR code.
+#[must_use]
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        percent_encode(account)
+    )
+}
+
+/// The code of the time step `unix_time` falls in, or `None` when the
+/// secret is not valid base32.
+#[must_use]
+pub fn code(secret: &str, unix_time: i64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    Some(format!(
+        "{:0DIGITS$}",
+        hotp(&key, unix_time.div_euclid(STEP_SECONDS))
+    ))
+}
+
+/// Checks a code against the time step `unix_time` falls in and its
+/// neighbours. Returns the time step the code belongs to, so callers can
+/// refuse to accept the same code twice.
+#[must_use]
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let key = base32_decode(secret)?;
+
+    let step = unix_time.div_euclid(STEP_SECONDS);
+    (step - SKEW_STEPS..=step + SKEW_STEPS).find(|&step| hotp(&key, step) == code)
+}
+
+/// HOTP (RFC 4226) with dynamic truncation.
+fn hotp(key: &[u8], counter: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS as u32)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(char::from(
+                BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize],
+            ));
+        }
+    }
+    if bits > 0 {
+        encoded.push(char::from(
+            BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize],
+        ));
+    }
+    encoded
+}
+
+/// Decodes base32 the lenient way apps display it: in any case, with spaces
+/// and with or without padding.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| char::from(a) == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | u32::try_from(value).ok()?;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{models::_entities::audit_events, views::users::User};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AuditEvent {
+    #[serde(flatten)]
+    pub event: audit_events::Model,
+
+    /// Who did it, unless unknown or their account was deleted.
+    pub actor: Option<User>,
+}
@@ -1,25 +1,118 @@
+use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
 
-use crate::models::_entities::users;
+use crate::{
+    models::_entities::{
+        cart_items, reviews, sea_orm_active_enums::UserRole, user_identities, users,
+        wishlist_lists, wishlists,
+    },
+    models::guest_carts::MergeReduction,
+    permissions::Permission,
+    totp,
+    views::orders::Order,
+};
 
 #[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
 pub struct LoginResponse {
+    /// Short-lived access token.
     pub token: String,
+    /// Exchanged at `/api/auth/refresh` for a new pair of tokens. Each refresh
+    /// token can only be used once.
+    pub refresh_token: String,
     pub pid: String,
     pub name: String,
     pub is_verified: bool,
     pub is_staff: bool,
+    pub role: Option<UserRole>,
+    /// Set when this login completed the setup of two-factor authentication.
+    /// They are not shown again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+    /// Lines of the guest cart merged at this login whose quantity was
+    /// lowered to what is in stock or allowed per line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cart_reductions: Vec<MergeReduction>,
 }
 
 impl LoginResponse {
     #[must_use]
-    pub fn new(user: &users::Model, token: &String) -> Self {
+    pub fn new(user: &users::Model, token: &String, refresh_token: &String) -> Self {
         Self {
             token: token.to_string(),
+            refresh_token: refresh_token.to_string(),
             pid: user.pid.to_string(),
             name: user.name.clone(),
             is_verified: user.email_verified_at.is_some(),
-            is_staff: user.is_staff,
+            is_staff: user.is_staff(),
+            role: user.role,
+            recovery_codes: None,
+            cart_reductions: Vec::new(),
+        }
+    }
+}
+
+/// Returned by a login that needs a code from an authenticator app before
+/// tokens are issued, and by the link to set up two-factor authentication.
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TwoFactorChallengeResponse {
+    /// Exchanged together with a code at `/api/auth/2fa/verify`.
+    pub challenge_token: String,
+    /// Set when following the link to set up two-factor authentication. The
+    /// code then comes from the authenticator this secret was added to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setup: Option<TotpSetupResponse>,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct TotpSetupResponse {
+    /// Base32 secret, for apps that can't scan the URI.
+    pub secret: String,
+    /// `otpauth://` URI, usually shown as a QR code.
+    pub otpauth_uri: String,
+}
+
+impl TotpSetupResponse {
+    #[must_use]
+    pub fn new(user: &users::Model, issuer: &str) -> Option<Self> {
+        let secret = user.totp_secret.clone()?;
+        Some(Self {
+            otpauth_uri: totp::otpauth_uri(&secret, issuer, &user.email),
+            secret,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// One-time codes that stand in for an authenticator code. They are not
+    /// shown again.
+    pub recovery_codes: Vec<String>,
+}
+
+/// Where to send the user to sign in with an identity provider.
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct OAuthAuthorizationResponse {
+    pub authorization_url: String,
+}
+
+/// An identity provider account linked to the user.
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct IdentityResponse {
+    pub id: i32,
+    pub provider: String,
+    /// Email of the provider account, which may differ from the user's.
+    pub email: Option<String>,
+    pub linked_at: DateTimeWithTimeZone,
+}
+
+impl IdentityResponse {
+    #[must_use]
+    pub fn new(identity: &user_identities::Model) -> Self {
+        Self {
+            id: identity.id,
+            provider: identity.provider.clone(),
+            email: identity.email.clone(),
+            linked_at: identity.created_at,
         }
     }
 }
@@ -30,6 +123,11 @@ pub struct CurrentResponse {
     pub name: String,
     pub email: String,
     pub is_staff: bool,
+    pub role: Option<UserRole>,
+    /// What the user's role allows them to do.
+    pub permissions: Vec<Permission>,
+    pub price_alerts_enabled: bool,
+    pub two_factor_enabled: bool,
 }
 
 impl CurrentResponse {
@@ -39,7 +137,62 @@ impl CurrentResponse {
             pid: user.pid.to_string(),
             name: user.name.clone(),
             email: user.email.clone(),
-            is_staff: user.is_staff,
+            is_staff: user.is_staff(),
+            role: user.role,
+            permissions: user
+                .role
+                .map(|role| role.permissions().to_vec())
+                .unwrap_or_default(),
+            price_alerts_enabled: user.price_alerts_enabled,
+            two_factor_enabled: user.totp_enabled_at.is_some(),
+        }
+    }
+}
+
+/// Everything stored about a user, for them to keep or take elsewhere.
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct AccountExport {
+    pub exported_at: DateTimeWithTimeZone,
+    pub profile: ProfileExport,
+    pub identities: Vec<IdentityResponse>,
+    pub orders: Vec<Order>,
+    pub reviews: Vec<reviews::Model>,
+    pub wishlists: Vec<WishlistExport>,
+    pub cart: Vec<cart_items::Model>,
+}
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct ProfileExport {
+    pub pid: String,
+    pub email: String,
+    pub name: String,
+    pub created_at: DateTimeWithTimeZone,
+    pub email_verified_at: Option<DateTimeWithTimeZone>,
+    pub role: Option<UserRole>,
+    pub price_alerts_enabled: bool,
+    pub two_factor_enabled: bool,
+}
+
+impl ProfileExport {
+    #[must_use]
+    pub fn new(user: &users::Model) -> Self {
+        Self {
+            pid: user.pid.to_string(),
+            email: user.email.clone(),
+            name: user.name.clone(),
+            created_at: user.created_at,
+            email_verified_at: user.email_verified_at,
+            role: user.role,
+            price_alerts_enabled: user.price_alerts_enabled,
+            two_factor_enabled: user.totp_enabled_at.is_some(),
         }
     }
 }
+
+#[derive(Debug, Deserialize, Serialize, utoipa::ToSchema)]
+pub struct WishlistExport {
+    #[serde(flatten)]
+    pub list: wishlist_lists::Model,
+
+    pub items: Vec<wishlists::Model>,
+}
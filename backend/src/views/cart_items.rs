@@ -1,3 +1,4 @@
+use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
 
 use crate::models::{cart_items, product_variants, products};
@@ -10,3 +11,47 @@ pub struct CartItem {
     pub product: Option<products::Model>,
     pub product_variant: Option<product_variants::Model>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct GuestCartResponse {
+    /// Signed token identifying the cart.
+    pub token: String,
+    pub expires_at: DateTimeWithTimeZone,
+}
+
+/// Something that changed about a cart line since it was added, which the
+/// shopper should know about before checking out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CartNotice {
+    /// The effective price differs from the one when the line was added.
+    PriceChanged { old_price: f64, new_price: f64 },
+    /// The variant has no stock left.
+    OutOfStock,
+    /// The product is no longer sold.
+    ProductInactive,
+    /// The quantity was lowered to what is still in stock, or to the most a
+    /// cart line may hold.
+    QuantityReduced { requested: i32, available: i32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CartLine {
+    #[serde(flatten)]
+    pub item: CartItem,
+
+    /// Effective price now, after the product's discount.
+    pub current_price: f64,
+    /// Current price times quantity, or zero when the line can't be ordered.
+    pub line_total: f64,
+    pub notices: Vec<CartNotice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CartResponse {
+    pub items: Vec<CartLine>,
+    /// Number of units across the lines that can be ordered.
+    pub item_count: i32,
+    /// Sum of the line totals, at current prices and discounts.
+    pub subtotal: f64,
+}
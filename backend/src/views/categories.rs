@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::categories;
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: categories::Model,
+
+    /// Products in this category and all of its descendants.
+    pub product_count: i64,
+
+    #[schema(no_recursion)]
+    pub children: Vec<CategoryNode>,
+}
+
+impl CategoryNode {
+    /// Arranges a flat list of categories into trees. Categories whose parent
+    /// is missing from the list become roots.
+    #[must_use]
+    pub fn build_forest(
+        categories: Vec<categories::Model>,
+        direct_product_counts: &HashMap<i32, i64>,
+    ) -> Vec<Self> {
+        let ids = categories.iter().map(|c| c.id).collect::<HashSet<_>>();
+        let mut roots = Vec::new();
+        let mut children_of = HashMap::<i32, Vec<categories::Model>>::new();
+
+        for category in categories {
+            match category.parent_id {
+                Some(parent_id) if ids.contains(&parent_id) => {
+                    children_of.entry(parent_id).or_default().push(category);
+                }
+                _ => roots.push(category),
+            }
+        }
+
+        roots
+            .into_iter()
+            .map(|root| Self::build(root, &mut children_of, direct_product_counts))
+            .collect()
+    }
+
+    fn build(
+        category: categories::Model,
+        children_of: &mut HashMap<i32, Vec<categories::Model>>,
+        direct_product_counts: &HashMap<i32, i64>,
+    ) -> Self {
+        let children = children_of
+            .remove(&category.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| Self::build(child, children_of, direct_product_counts))
+            .collect::<Vec<_>>();
+        let product_count = direct_product_counts
+            .get(&category.id)
+            .copied()
+            .unwrap_or_default()
+            + children.iter().map(|c| c.product_count).sum::<i64>();
+
+        Self {
+            category,
+            product_count,
+            children,
+        }
+    }
+}
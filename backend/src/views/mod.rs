@@ -1,7 +1,12 @@
+pub mod audit_events;
 pub mod auth;
 pub mod cart_items;
+pub mod categories;
 pub mod orders;
 pub mod pagination;
 pub mod products;
+pub mod reports;
 pub mod reviews;
+pub mod roles;
 pub mod users;
+pub mod wishlists;
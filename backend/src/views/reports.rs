@@ -0,0 +1,207 @@
+use std::fmt::Write as _;
+
+use sea_orm::{prelude::Decimal, FromQueryResult};
+use serde::{Deserialize, Serialize};
+
+/// A report row that can be written as a line of CSV.
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn record(&self) -> Vec<String>;
+}
+
+/// Writes the rows as CSV with a header line.
+#[must_use]
+pub fn to_csv<T: CsvRecord>(rows: &[T]) -> String {
+    let mut csv = String::new();
+    push_line(&mut csv, T::HEADER.iter().map(ToString::to_string));
+    for row in rows {
+        push_line(&mut csv, row.record());
+    }
+    csv
+}
+
+fn push_line(csv: &mut String, fields: impl IntoIterator<Item = String>) {
+    let fields = fields
+        .into_iter()
+        .map(|field| quote(&field))
+        .collect::<Vec<_>>();
+    let _ = write!(csv, "{}\r\n", fields.join(","));
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<&T>) -> String {
+    value.map(ToString::to_string).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult, utoipa::ToSchema)]
+pub struct SalesPeriod {
+    /// First day of the day, week or month.
+    pub period: String,
+    pub order_count: i64,
+    pub revenue: Decimal,
+}
+
+impl CsvRecord for SalesPeriod {
+    const HEADER: &'static [&'static str] = &["period", "order_count", "revenue"];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.period.clone(),
+            self.order_count.to_string(),
+            self.revenue.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult, utoipa::ToSchema)]
+pub struct ProductSales {
+    pub product_id: i32,
+    pub name: String,
+    pub units: i64,
+    pub revenue: Decimal,
+}
+
+impl CsvRecord for ProductSales {
+    const HEADER: &'static [&'static str] = &["product_id", "name", "units", "revenue"];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.product_id.to_string(),
+            self.name.clone(),
+            self.units.to_string(),
+            self.revenue.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult, utoipa::ToSchema)]
+pub struct VariantSales {
+    pub product_variant_id: i32,
+    pub sku: String,
+    pub size: Option<String>,
+    pub color: Option<String>,
+    pub product_name: String,
+    pub units: i64,
+    pub revenue: Decimal,
+}
+
+impl CsvRecord for VariantSales {
+    const HEADER: &'static [&'static str] = &[
+        "product_variant_id",
+        "sku",
+        "size",
+        "color",
+        "product_name",
+        "units",
+        "revenue",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.product_variant_id.to_string(),
+            self.sku.clone(),
+            optional(self.size.as_ref()),
+            optional(self.color.as_ref()),
+            self.product_name.clone(),
+            self.units.to_string(),
+            self.revenue.to_string(),
+        ]
+    }
+}
+
+/// Sales of a brand or category. Products without one are grouped under a
+/// row with no `id` and `name`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult, utoipa::ToSchema)]
+pub struct GroupSales {
+    pub id: Option<i32>,
+    pub name: Option<String>,
+    pub units: i64,
+    pub revenue: Decimal,
+}
+
+impl CsvRecord for GroupSales {
+    const HEADER: &'static [&'static str] = &["id", "name", "units", "revenue"];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            optional(self.id.as_ref()),
+            optional(self.name.as_ref()),
+            self.units.to_string(),
+            self.revenue.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SalesSummary {
+    /// Orders placed, including cancelled ones.
+    pub order_count: i64,
+    pub cancelled_count: i64,
+    /// Share of placed orders that were cancelled, between 0 and 1.
+    pub cancellation_rate: f64,
+    /// Total of the orders that were not cancelled.
+    pub revenue: Decimal,
+    pub average_order_value: Decimal,
+}
+
+impl CsvRecord for SalesSummary {
+    const HEADER: &'static [&'static str] = &[
+        "order_count",
+        "cancelled_count",
+        "cancellation_rate",
+        "revenue",
+        "average_order_value",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.order_count.to_string(),
+            self.cancelled_count.to_string(),
+            self.cancellation_rate.to_string(),
+            self.revenue.to_string(),
+            self.average_order_value.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SellThrough {
+    pub product_variant_id: i32,
+    pub sku: String,
+    pub product_name: String,
+    pub units_sold: i64,
+    pub stock: i32,
+    /// Units sold as a share of units sold plus units still in stock,
+    /// between 0 and 1.
+    pub sell_through: f64,
+}
+
+impl CsvRecord for SellThrough {
+    const HEADER: &'static [&'static str] = &[
+        "product_variant_id",
+        "sku",
+        "product_name",
+        "units_sold",
+        "stock",
+        "sell_through",
+    ];
+
+    fn record(&self) -> Vec<String> {
+        vec![
+            self.product_variant_id.to_string(),
+            self.sku.clone(),
+            self.product_name.clone(),
+            self.units_sold.to_string(),
+            self.stock.to_string(),
+            self.sell_through.to_string(),
+        ]
+    }
+}
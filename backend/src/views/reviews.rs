@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     models::reviews,
-    views::{products::Product, users::User},
+    views::{pagination::PageResponse, products::Product, users::User},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
@@ -17,5 +17,12 @@ pub struct Review {
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct ListReviewsResponse {
     pub product: Product,
-    pub reviews: Vec<Review>,
+    pub reviews: PageResponse<Review>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ModerationReview {
+    #[serde(flatten)]
+    pub review: Review,
+    pub report_count: i64,
 }
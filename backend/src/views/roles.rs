@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::_entities::{sea_orm_active_enums::UserRole, users},
+    permissions::Permission,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RoleResponse {
+    pub role: UserRole,
+    pub permissions: Vec<Permission>,
+}
+
+impl RoleResponse {
+    #[must_use]
+    pub fn new(role: UserRole) -> Self {
+        Self {
+            role,
+            permissions: role.permissions().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct StaffMember {
+    pub pid: String,
+    pub name: String,
+    pub email: String,
+    pub role: Option<UserRole>,
+}
+
+impl StaffMember {
+    #[must_use]
+    pub fn new(user: &users::Model) -> Self {
+        Self {
+            pid: user.pid.to_string(),
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: user.role,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{product_variants, wishlist_lists},
+    views::products::Product,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct WishlistItem {
+    #[serde(flatten)]
+    pub product: Product,
+
+    /// Id of the wishlist entry, used to remove it from a list.
+    pub item_id: i32,
+    /// Variant the customer picked, if they saved a specific size.
+    pub variant: Option<product_variants::Model>,
+    /// Effective price when the product was added to the wishlist.
+    pub saved_price: Option<f64>,
+    /// Effective price now, after the product's discount.
+    pub current_price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct WishlistList {
+    #[serde(flatten)]
+    pub list: wishlist_lists::Model,
+
+    pub item_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct WishlistDetail {
+    #[serde(flatten)]
+    pub list: wishlist_lists::Model,
+
+    pub items: Vec<WishlistItem>,
+}
+
+/// Read-only view of a list opened through its share link.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SharedWishlist {
+    pub name: String,
+    pub owner: SharedWishlistOwner,
+    pub items: Vec<WishlistItem>,
+}
+
+/// Owner of a shared list, shown by name only since anyone with the link
+/// can see it.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SharedWishlistOwner {
+    pub name: String,
+}
@@ -0,0 +1,89 @@
+use chrono::{Duration, Utc};
+use loco_rs::prelude::*;
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mailers::wishlist::WishlistMailer,
+    models::{_entities::wishlists, products, users},
+    settings::Settings,
+};
+
+/// Emails the users who wishlisted a product after its price went down.
+pub struct PriceDropWorker {
+    pub ctx: AppContext,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct PriceDropWorkerArgs {
+    pub product_id: i32,
+    /// Effective price before the change.
+    pub old_price: f64,
+}
+
+#[async_trait]
+impl BackgroundWorker<PriceDropWorkerArgs> for PriceDropWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: PriceDropWorkerArgs) -> Result<()> {
+        let Some(product) = products::Entity::find_by_id(args.product_id)
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        // The price may have gone back up by the time the job runs
+        if !product.is_active || product.effective_price() >= args.old_price {
+            return Ok(());
+        }
+
+        let settings = Settings::from_context(&self.ctx)?;
+        let cutoff = Utc::now() - Duration::hours(settings.wishlists.price_alert_cooldown_hours);
+        let not_recently_alerted = wishlists::Column::PriceAlertSentAt
+            .is_null()
+            .or(wishlists::Column::PriceAlertSentAt.lt(cutoff));
+
+        let entries = wishlists::Entity::find()
+            .filter(wishlists::Column::ProductId.eq(product.id))
+            .filter(not_recently_alerted.clone())
+            .find_also_related(users::Entity)
+            .all(&self.ctx.db)
+            .await?;
+
+        for (entry, user) in entries {
+            let Some(user) = user.filter(|u| u.price_alerts_enabled && u.is_active) else {
+                continue;
+            };
+
+            // Claim every entry of the user for this product first, so neither
+            // concurrent jobs nor the same product saved in several lists can
+            // send more than one email
+            let claimed = wishlists::Entity::update_many()
+                .col_expr(wishlists::Column::PriceAlertSentAt, Expr::value(Utc::now()))
+                .filter(wishlists::Column::UserId.eq(entry.user_id))
+                .filter(wishlists::Column::ProductId.eq(product.id))
+                .filter(not_recently_alerted.clone())
+                .exec(&self.ctx.db)
+                .await?;
+            if claimed.rows_affected == 0 {
+                continue;
+            }
+
+            if let Err(err) =
+                WishlistMailer::send_price_drop(&self.ctx, &user, &product, args.old_price).await
+            {
+                tracing::error!(
+                    err = err.to_string(),
+                    user_pid = user.pid.to_string(),
+                    product_id = product.id,
+                    "could not send price drop email"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
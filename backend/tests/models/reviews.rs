@@ -1,6 +1,11 @@
 use loco_rs::testing::prelude::*;
+use rstest::rstest;
 use serial_test::serial;
-use shoes_store_api::app::App;
+use shoes_store_api::{
+    app::App,
+    models::{_entities::sea_orm_active_enums::ReviewStatus, reviews},
+    settings::ReviewSettings,
+};
 
 macro_rules! configure_insta {
     ($($expr:expr),*) => {
@@ -29,3 +34,14 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+#[rstest]
+#[case(Some("Great fit, true to size."), ReviewStatus::Published)]
+#[case(None, ReviewStatus::Published)]
+#[case(Some("Cheaper at www.example.com"), ReviewStatus::Pending)]
+#[case(Some("These are SHIT shoes"), ReviewStatus::Pending)]
+fn screens_new_reviews(#[case] content: Option<&str>, #[case] expected: ReviewStatus) {
+    let settings = ReviewSettings::default();
+
+    assert_eq!(reviews::Model::initial_status(&settings, content), expected);
+}
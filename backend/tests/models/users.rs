@@ -5,7 +5,10 @@ use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
 use serial_test::serial;
 use shoes_store_api::{
     app::App,
-    models::users::{self, Model, RegisterParams},
+    models::{
+        hash_token,
+        users::{self, Model, RegisterParams, ResetToken},
+    },
 };
 
 macro_rules! configure_insta {
@@ -215,6 +218,51 @@ async fn can_set_forgot_password_sent() {
     );
 }
 
+#[tokio::test]
+#[serial]
+async fn reset_tokens_are_hashed_and_expire() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+
+    let user = Model::find_by_pid(db, "11111111-1111-1111-1111-111111111111")
+        .await
+        .unwrap();
+    let (_, first_token) = user
+        .into_active_model()
+        .set_forgot_password_sent(db)
+        .await
+        .unwrap();
+    let user = Model::find_by_pid(db, "11111111-1111-1111-1111-111111111111")
+        .await
+        .unwrap();
+    let (user, token) = user
+        .into_active_model()
+        .set_forgot_password_sent(db)
+        .await
+        .unwrap();
+    assert_eq!(user.reset_token, Some(hash_token(&token)));
+
+    assert!(
+        Model::find_by_reset_token(db, &first_token, 60)
+            .await
+            .is_err(),
+        "a newer reset link replaces the earlier one"
+    );
+    assert!(matches!(
+        Model::find_by_reset_token(db, &token, 60).await,
+        Ok(ResetToken::Valid(found)) if found.id == user.id
+    ));
+
+    let mut user = user.into_active_model();
+    user.reset_sent_at = ActiveValue::Set(Some((Local::now() - Duration::minutes(61)).into()));
+    user.update(db).await.unwrap();
+    assert!(matches!(
+        Model::find_by_reset_token(db, &token, 60).await,
+        Ok(ResetToken::Expired)
+    ));
+}
+
 #[tokio::test]
 #[serial]
 async fn can_verified() {
@@ -231,6 +279,14 @@ async fn can_verified() {
         .await
         .expect("Failed to find user by PID");
 
+    // Seeded users are verified
+    let mut user = user.into_active_model();
+    user.email_verified_at = ActiveValue::Set(None);
+    let user = user
+        .update(&boot.app_context.db)
+        .await
+        .expect("Failed to unverify user");
+
     assert!(
         user.email_verified_at.is_none(),
         "Expected email to be unverified"
@@ -321,6 +377,7 @@ async fn magic_link() {
         "Failed to create magic link: {:?}",
         create_result.unwrap_err()
     );
+    let (_, magic_link_token) = create_result.unwrap();
 
     let updated_user =
         Model::find_by_pid(&boot.app_context.db, "11111111-1111-1111-1111-111111111111")
@@ -332,12 +389,16 @@ async fn magic_link() {
         "Magic link token should be set after creation"
     );
 
-    let magic_link_token = updated_user.magic_link_token.unwrap();
     assert_eq!(
         magic_link_token.len(),
         users::MAGIC_LINK_LENGTH as usize,
         "Magic link token length does not match expected length"
     );
+    assert_eq!(
+        updated_user.magic_link_token,
+        Some(hash_token(&magic_link_token)),
+        "Only a hash of the magic link token should be stored"
+    );
 
     assert!(
         updated_user.magic_link_expiration.is_some(),
@@ -0,0 +1,113 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use shoes_store_api::app::App;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn customers_cannot_see_audit_log() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .get("/api/admin/audit")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn records_staff_changes_with_diffs() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .patch("/api/products/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "price": 99.5 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .delete("/api/brands/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .add_query_param("force", true)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/admin/audit")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .add_query_param("entity_type", "product")
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let page = res.json::<serde_json::Value>();
+        assert_eq!(page["total_items"], 1);
+        let event = &page["items"][0];
+        assert_eq!(event["action"], "update");
+        assert_eq!(event["entity_id"], "1");
+        assert_eq!(event["actor"]["id"], staff.user.id);
+        assert_eq!(event["before"], serde_json::json!({ "price": 120.0 }));
+        assert_eq!(event["after"], serde_json::json!({ "price": 99.5 }));
+
+        let res = request
+            .get("/api/admin/audit")
+            .add_header(auth_key, auth_value)
+            .add_query_param("action", "delete")
+            .await;
+        let page = res.json::<serde_json::Value>();
+        assert_eq!(page["total_items"], 1);
+        let event = &page["items"][0];
+        assert_eq!(event["entity_type"], "brand");
+        assert_eq!(event["before"]["id"], 2);
+        assert!(event["after"].is_null());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn records_logins_and_password_changes() {
+    request::<App, _, _>(|request, ctx| async move {
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "wrong-password-1" }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let res = request
+            .post("/api/auth/change-password")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "old_password": "shoes-store-42", "new_password": "new-password-5678" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/admin/audit")
+            .add_header(auth_key, auth_value)
+            .add_query_param("entity_id", staff.user.pid.to_string())
+            .await;
+        let actions = res.json::<serde_json::Value>()["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| (event["action"].clone(), event["actor"]["id"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                ("password_change".into(), staff.user.id.into()),
+                ("login_failed".into(), serde_json::Value::Null),
+                ("login".into(), staff.user.id.into()),
+            ]
+        );
+    })
+    .await;
+}
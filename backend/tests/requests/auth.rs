@@ -1,10 +1,19 @@
+use chrono::{Duration, Local};
 use insta::{assert_debug_snapshot, with_settings};
-use loco_rs::testing::prelude::*;
+use loco_rs::{testing::prelude::*, TestServer};
 use rstest::rstest;
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, IntoActiveModel};
 use serial_test::serial;
-use shoes_store_api::{app::App, models::users};
+use shoes_store_api::{
+    app::App,
+    models::{_entities::orders, hash_token, user_identities, users},
+    totp,
+};
 
-use super::prepare_data;
+use super::{
+    mock_oidc::{Account, MockProvider, ISSUER},
+    prepare_data,
+};
 
 // TODO: see how to dedup / extract this to app-local test utils
 // not to framework, because that would require a runtime dep on insta
@@ -17,6 +26,25 @@ macro_rules! configure_insta {
     };
 }
 
+/// Redacts the user model like `cleanup_user_model`, and also the stored
+/// hashes of emailed tokens.
+fn cleanup_user_model_with_tokens() -> Vec<(&'static str, &'static str)> {
+    let mut filters = cleanup_user_model();
+    filters.push((r"[0-9a-f]{64}", "TOKEN_HASH"));
+    filters
+}
+
+/// Redacts login responses like `cleanup_user_model`, and also the refresh
+/// token.
+fn cleanup_login_response() -> Vec<(&'static str, &'static str)> {
+    let mut filters = cleanup_user_model();
+    filters.push((
+        r#"refresh_token\\":\\"[A-Za-z0-9]+"#,
+        r#"refresh_token\":\"REFRESH_TOKEN"#,
+    ));
+    filters
+}
+
 #[tokio::test]
 #[serial]
 async fn can_register() {
@@ -27,7 +55,7 @@ async fn can_register() {
         let payload = serde_json::json!({
             "name": "loco",
             "email": email,
-            "password": "12341234"
+            "password": "shoes-store-42"
         });
 
         let response = request.post("/api/auth/register").json(&payload).await;
@@ -39,7 +67,7 @@ async fn can_register() {
         let saved_user = users::Model::find_by_email(&ctx.db, email).await;
 
         with_settings!({
-            filters => cleanup_user_model()
+            filters => cleanup_user_model_with_tokens()
         }, {
             assert_debug_snapshot!(saved_user);
         });
@@ -57,7 +85,7 @@ async fn can_register() {
 }
 
 #[rstest]
-#[case("login_with_valid_password", "12341234")]
+#[case("login_with_valid_password", "shoes-store-42")]
 #[case("login_with_invalid_password", "invalid-password")]
 #[tokio::test]
 #[serial]
@@ -69,7 +97,7 @@ async fn can_login_with_verify(#[case] test_name: &str, #[case] password: &str)
         let register_payload = serde_json::json!({
             "name": "loco",
             "email": email,
-            "password": "12341234"
+            "password": "shoes-store-42"
         });
 
         //Creating a new user
@@ -84,10 +112,7 @@ async fn can_login_with_verify(#[case] test_name: &str, #[case] password: &str)
             "Register request should succeed"
         );
 
-        let user = users::Model::find_by_email(&ctx.db, email).await.unwrap();
-        let email_verification_token = user
-            .email_verification_token
-            .expect("Email verification token should be generated");
+        let email_verification_token = prepare_data::emailed_token(&ctx, "/api/auth/verify/");
         request
             .get(&format!("/api/auth/verify/{email_verification_token}"))
             .await;
@@ -112,7 +137,7 @@ async fn can_login_with_verify(#[case] test_name: &str, #[case] password: &str)
         );
 
         with_settings!({
-            filters => cleanup_user_model()
+            filters => cleanup_login_response()
         }, {
             assert_debug_snapshot!(test_name, (response.status_code(), response.text()));
         });
@@ -140,6 +165,62 @@ async fn login_with_un_existing_email() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn reset_links_expire_and_are_replaced() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await.user;
+        let forgot = || {
+            request
+                .post("/api/auth/forgot")
+                .json(&serde_json::json!({ "email": user.email }))
+        };
+        let reset = |token: String| {
+            request.post("/api/auth/reset").json(&serde_json::json!({
+                "token": token,
+                "password": "new-password-42",
+            }))
+        };
+        let login = |password: &'static str| {
+            request
+                .post("/api/auth/login")
+                .json(&serde_json::json!({ "email": user.email, "password": password }))
+        };
+
+        forgot().await;
+        let first_token = prepare_data::emailed_token(&ctx, "/reset#");
+        forgot().await;
+        let token = prepare_data::emailed_token(&ctx, "/reset#");
+        assert_ne!(first_token, token);
+
+        let stored = users::Model::find_by_email(&ctx.db, &user.email)
+            .await
+            .unwrap();
+        assert_eq!(
+            stored.reset_token,
+            Some(hash_token(&token)),
+            "only a hash of the token is stored"
+        );
+
+        assert_eq!(reset(first_token).await.status_code(), 200);
+        assert_eq!(
+            login("new-password-42").await.status_code(),
+            401,
+            "a newer link replaces the earlier one"
+        );
+
+        let mut stored = stored.into_active_model();
+        stored.reset_sent_at =
+            ActiveValue::set(Some((Local::now() - Duration::minutes(61)).into()));
+        stored.update(&ctx.db).await.unwrap();
+        let res = reset(token).await;
+        assert_eq!(res.status_code(), 410, "links expire");
+        assert_eq!(res.json::<serde_json::Value>()["error"], "gone");
+        assert_eq!(login("shoes-store-42").await.status_code(), 200);
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn can_login_without_verify() {
@@ -147,7 +228,7 @@ async fn can_login_without_verify() {
 
     request::<App, _, _>(|request, _ctx| async move {
         let email = "test@loco.com";
-        let password = "12341234";
+        let password = "shoes-store-42";
         let register_payload = serde_json::json!({
             "name": "loco",
             "email": email,
@@ -182,7 +263,7 @@ async fn can_login_without_verify() {
         );
 
         with_settings!({
-            filters => cleanup_user_model()
+            filters => cleanup_login_response()
         }, {
             assert_debug_snapshot!(login_response.text());
         });
@@ -190,6 +271,77 @@ async fn can_login_without_verify() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn verification_links_expire_and_gate_checkout() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let register = |email: &'static str| {
+            request.post("/api/auth/register").json(&serde_json::json!({
+                "name": "loco",
+                "email": email,
+                "password": "shoes-store-42"
+            }))
+        };
+
+        register("test@loco.com").await;
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .unwrap();
+        assert!(
+            user.email_verified_at.is_none(),
+            "registering doesn't verify the email"
+        );
+
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await;
+        assert_eq!(res.status_code(), 200, "unverified users can sign in");
+        let token = res.json::<serde_json::Value>()["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let (auth_key, auth_value) = prepare_data::auth_header(&token);
+        let place_order = || {
+            request
+                .post("/api/orders")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({
+                    "payment_method": "Cod",
+                    "shipping_address": "1 Main Street",
+                    "items": [{ "product_variant_id": 1, "quantity": 1 }],
+                }))
+        };
+        assert_eq!(place_order().await.status_code(), 403);
+
+        let link = format!(
+            "/api/auth/verify/{}",
+            prepare_data::emailed_token(&ctx, "/api/auth/verify/")
+        );
+        assert_eq!(request.get(&link).await.status_code(), 200);
+        assert_eq!(
+            request.get(&link).await.status_code(),
+            401,
+            "links can only be used once"
+        );
+        assert_eq!(place_order().await.status_code(), 200);
+
+        register("late@loco.com").await;
+        let user = users::Model::find_by_email(&ctx.db, "late@loco.com")
+            .await
+            .unwrap();
+        let token = prepare_data::emailed_token(&ctx, "/api/auth/verify/");
+        let mut user = user.into_active_model();
+        user.email_verification_sent_at =
+            ActiveValue::set(Some((Local::now() - Duration::hours(25)).into()));
+        user.update(&ctx.db).await.unwrap();
+        let res = request.get(&format!("/api/auth/verify/{token}")).await;
+        assert_eq!(res.status_code(), 401, "links expire");
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn invalid_verification_token() {
@@ -234,9 +386,9 @@ async fn can_reset_password() {
             "Expected reset_sent_at to be set, but it was None. User: {user:?}"
         );
 
-        let new_password = "new-password";
+        let new_password = "new-password-42";
         let reset_payload = serde_json::json!({
-            "token": user.reset_token,
+            "token": prepare_data::emailed_token(&ctx, "/reset#"),
             "password": new_password,
         });
 
@@ -317,8 +469,9 @@ async fn can_auth_with_magic_link() {
     request::<App, _, _>(|request, ctx| async move {
         seed::<App>(&ctx).await.unwrap();
 
+        // Staff are asked for a second factor, so a customer signs in
         let payload = serde_json::json!({
-            "email": "user1@example.com",
+            "email": "user2@example.com",
         });
         let response = request.post("/api/auth/magic-link").json(&payload).await;
         assert_eq!(
@@ -327,7 +480,7 @@ async fn can_auth_with_magic_link() {
             "Magic link request should succeed"
         );
 
-        let deliveries = ctx.mailer.unwrap().deliveries();
+        let deliveries = ctx.mailer.as_ref().unwrap().deliveries();
         assert_eq!(deliveries.count, 1, "Exactly one email should be sent");
 
         // let redact_token = format!("[a-zA-Z0-9]{{{}}}", users::MAGIC_LINK_LENGTH);
@@ -341,13 +494,7 @@ async fn can_auth_with_magic_link() {
         //     assert_debug_snapshot!(deliveries.messages);
         // });
 
-        let user = users::Model::find_by_email(&ctx.db, "user1@example.com")
-            .await
-            .expect("User should be found");
-
-        let magic_link_token = user
-            .magic_link_token
-            .expect("Magic link token should be generated");
+        let magic_link_token = prepare_data::emailed_token(&ctx, "/api/auth/magic-link/");
         let magic_link_response = request
             .get(&format!("/api/auth/magic-link/{magic_link_token}"))
             .await;
@@ -358,7 +505,7 @@ async fn can_auth_with_magic_link() {
         );
 
         with_settings!({
-            filters => cleanup_user_model()
+            filters => cleanup_login_response()
         }, {
             assert_debug_snapshot!(magic_link_response.text());
         });
@@ -385,6 +532,71 @@ async fn can_reject_invalid_email() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn registration_policy_rejects_weak_passwords_and_domains() {
+    request::<App, _, _>(|request, ctx| async move {
+        let register = |email: &'static str, password: &'static str| {
+            request.post("/api/auth/register").json(&serde_json::json!({
+                "name": "loco",
+                "email": email,
+                "password": password
+            }))
+        };
+        let codes = |body: &serde_json::Value, field: &str| -> Vec<String> {
+            body["errors"][field]
+                .as_array()
+                .map(|errors| {
+                    errors
+                        .iter()
+                        .map(|err| err["code"].as_str().unwrap().to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let res = register("someone@mail.yopmail.com", "abc").await;
+        assert_eq!(res.status_code(), 400);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(codes(&body, "email"), ["disposable_domain"]);
+        assert_eq!(codes(&body, "password"), ["too_short", "missing_digit"]);
+        assert_eq!(body["errors"]["password"][0]["params"]["min"], 8);
+
+        let res = register("someone@loco.com", "Password123").await;
+        assert_eq!(res.status_code(), 400);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(codes(&body, "email"), Vec::<String>::new());
+        assert_eq!(codes(&body, "password"), ["common_password"]);
+        assert!(users::Model::find_by_email(&ctx.db, "someone@loco.com")
+            .await
+            .is_err());
+
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/auth/change-password")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "old_password": "shoes-store-42", "new_password": "ALLCAPS42" }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        assert_eq!(
+            codes(&res.json::<serde_json::Value>(), "new_password"),
+            ["missing_lowercase"]
+        );
+
+        let res = request
+            .post("/api/auth/reset")
+            .json(&serde_json::json!({ "token": "any", "password": "qwertyuiop" }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        assert_eq!(
+            codes(&res.json::<serde_json::Value>(), "password"),
+            ["missing_digit", "common_password"]
+        );
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn can_reject_invalid_magic_link_token() {
@@ -412,7 +624,7 @@ async fn can_resend_verification_email() {
         let payload = serde_json::json!({
             "name": "loco",
             "email": email,
-            "password": "12341234"
+            "password": "shoes-store-42"
         });
 
         let response = request.post("/api/auth/register").json(&payload).await;
@@ -424,6 +636,24 @@ async fn can_resend_verification_email() {
 
         let resend_payload = serde_json::json!({ "email": email });
 
+        let resend_response = request
+            .post("/api/auth/resend-verification-mail")
+            .json(&resend_payload)
+            .await;
+        assert_eq!(resend_response.status_code(), 200);
+        assert_eq!(
+            ctx.mailer.as_ref().unwrap().deliveries().count,
+            1,
+            "No email is resent within the cooldown"
+        );
+
+        let user = users::Model::find_by_email(&ctx.db, email).await.unwrap();
+        let first_token = user.email_verification_token.clone();
+        let mut user = user.into_active_model();
+        user.email_verification_sent_at =
+            ActiveValue::set(Some((Local::now() - Duration::minutes(10)).into()));
+        user.update(&ctx.db).await.unwrap();
+
         let resend_response = request
             .post("/api/auth/resend-verification-mail")
             .json(&resend_payload)
@@ -445,9 +675,13 @@ async fn can_resend_verification_email() {
         let user = users::Model::find_by_email(&ctx.db, email)
             .await
             .expect("User should exist");
+        assert_ne!(
+            user.email_verification_token, first_token,
+            "Resending issues a new link"
+        );
 
         with_settings!({
-            filters => cleanup_user_model()
+            filters => cleanup_user_model_with_tokens()
         }, {
             assert_debug_snapshot!("resend_verification_user", user);
         });
@@ -465,16 +699,14 @@ async fn cannot_resend_email_if_already_verified() {
         let payload = serde_json::json!({
             "name": "verified",
             "email": email,
-            "password": "12341234"
+            "password": "shoes-store-42"
         });
 
         request.post("/api/auth/register").json(&payload).await;
 
         // Verify user
-        let user = users::Model::find_by_email(&ctx.db, email).await.unwrap();
-        if let Some(token) = user.email_verification_token.clone() {
-            request.get(&format!("/api/auth/verify/{token}")).await;
-        }
+        let token = prepare_data::emailed_token(&ctx, "/api/auth/verify/");
+        request.get(&format!("/api/auth/verify/{token}")).await;
 
         // Try resending verification email
         let resend_payload = serde_json::json!({ "email": email });
@@ -498,3 +730,880 @@ async fn cannot_resend_email_if_already_verified() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn rotates_refresh_tokens_and_detects_reuse() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+        let login = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await
+            .json::<serde_json::Value>();
+        let first = login["refresh_token"].clone();
+
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": first }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let refreshed = res.json::<serde_json::Value>();
+        assert!(refreshed["token"].is_string());
+        let second = refreshed["refresh_token"].clone();
+        assert_ne!(first, second);
+
+        // Replaying the first token revokes the second one too
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": first }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": second }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn logout_and_password_change_revoke_sessions() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let login = |password: &'static str| {
+            request
+                .post("/api/auth/login")
+                .json(&serde_json::json!({ "email": "test@loco.com", "password": password }))
+        };
+
+        let refresh_token =
+            login("shoes-store-42").await.json::<serde_json::Value>()["refresh_token"].clone();
+        let res = request
+            .post("/api/auth/logout")
+            .json(&serde_json::json!({ "refresh_token": refresh_token }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": refresh_token }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+
+        let refresh_token =
+            login("shoes-store-42").await.json::<serde_json::Value>()["refresh_token"].clone();
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/auth/change-password")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "old_password": "shoes-store-42", "new_password": "new-password-5678" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": refresh_token }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+
+        assert_eq!(login("new-password-5678").await.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn delays_and_locks_out_failed_logins() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+        let login = |password: &'static str| {
+            request
+                .post("/api/auth/login")
+                .json(&serde_json::json!({ "email": "test@loco.com", "password": password }))
+        };
+        // Moves the last failure back in time, as if the client had waited
+        let wait = || async {
+            let mut user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+                .await
+                .unwrap()
+                .into_active_model();
+            user.last_failed_login_at =
+                ActiveValue::set(Some((Local::now() - Duration::hours(1)).into()));
+            user.update(&ctx.db).await.unwrap();
+        };
+
+        for _ in 0..3 {
+            assert_eq!(login("wrong").await.status_code(), 401);
+        }
+        let res = login("shoes-store-42").await;
+        assert_eq!(
+            res.status_code(),
+            429,
+            "attempts after 3 failures are delayed"
+        );
+        assert_eq!(res.header("retry-after"), "2");
+
+        wait().await;
+        assert_eq!(login("wrong").await.status_code(), 401);
+        wait().await;
+        assert_eq!(login("wrong").await.status_code(), 401);
+        wait().await;
+        let res = login("shoes-store-42").await;
+        assert_eq!(res.status_code(), 429, "5 failures lock the account");
+
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .unwrap();
+        assert!(user.locked_until.is_some());
+        let deliveries = ctx.mailer.as_ref().unwrap().deliveries();
+        assert_eq!(deliveries.count, 2, "welcome and unlock emails");
+
+        let token = prepare_data::emailed_token(&ctx, "/api/auth/unlock/");
+        assert_eq!(user.unlock_token, Some(hash_token(&token)));
+        let unlock = format!("/api/auth/unlock/{token}");
+        assert_eq!(request.get(&unlock).await.status_code(), 200);
+        assert_eq!(request.get(&unlock).await.status_code(), 401);
+        assert_eq!(login("shoes-store-42").await.status_code(), 200);
+
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .unwrap();
+        assert_eq!(user.failed_login_count, 0);
+        assert!(user.locked_until.is_none());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn unlock_links_expire() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+        let mut user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .unwrap()
+            .into_active_model();
+        user.failed_login_count = ActiveValue::set(4);
+        user.update(&ctx.db).await.unwrap();
+
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "wrong" }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let token = prepare_data::emailed_token(&ctx, "/api/auth/unlock/");
+
+        let mut user = users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .unwrap()
+            .into_active_model();
+        user.unlock_sent_at = ActiveValue::set(Some((Local::now() - Duration::hours(1)).into()));
+        user.update(&ctx.db).await.unwrap();
+
+        let res = request.get(&format!("/api/auth/unlock/{token}")).await;
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn delays_logins_to_unknown_emails_like_accounts() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+        let login = |email: &'static str| {
+            request
+                .post("/api/auth/login")
+                .json(&serde_json::json!({ "email": email, "password": "wrong" }))
+        };
+
+        let mut responses = Vec::new();
+        for email in ["test@loco.com", "nobody@loco.com"] {
+            let mut attempts = Vec::new();
+            for _ in 0..4 {
+                let res = login(email).await;
+                attempts.push((res.status_code(), res.text()));
+            }
+            responses.push(attempts);
+        }
+        assert_eq!(responses[0], responses[1]);
+        assert_eq!(
+            responses[1][3].0, 429,
+            "attempts after 3 failures are delayed"
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rate_limits_auth_emails() {
+    request::<App, _, _>(|request, _ctx| async move {
+        let forgot = |email: String, ip: &'static str| {
+            request
+                .post("/api/auth/forgot")
+                .add_header("x-forwarded-for", ip)
+                .json(&serde_json::json!({ "email": email }))
+        };
+
+        for _ in 0..3 {
+            let res = forgot("user1@example.com".to_string(), "203.0.113.1").await;
+            assert_eq!(res.status_code(), 200);
+        }
+        let res = forgot("user1@example.com".to_string(), "203.0.113.2").await;
+        assert_eq!(res.status_code(), 429, "4th email to the same address");
+        let res = request
+            .post("/api/auth/magic-link")
+            .add_header("x-forwarded-for", "203.0.113.3")
+            .json(&serde_json::json!({ "email": "user1@example.com" }))
+            .await;
+        assert_eq!(
+            res.status_code(),
+            429,
+            "limits are shared by email endpoints"
+        );
+
+        // The first IP asked for 3 emails so far, 7 more are allowed
+        for i in 0..7 {
+            let res = forgot(format!("customer{i}@example.com"), "203.0.113.1").await;
+            assert_eq!(res.status_code(), 200);
+        }
+        let res = forgot("customer7@example.com".to_string(), "203.0.113.1").await;
+        assert_eq!(res.status_code(), 429, "11th email from the same IP");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ignores_client_supplied_forwarded_addresses() {
+    request::<App, _, _>(|request, _ctx| async move {
+        // Each proxy appends the address it saw, anything before the first
+        // one that isn't a trusted proxy came from the client
+        let login = |i: u32, forwarded: String| {
+            request
+                .post("/api/auth/login")
+                .add_header("x-forwarded-for", forwarded)
+                .json(&serde_json::json!({
+                    "email": format!("nobody{i}@loco.com"),
+                    "password": "wrong"
+                }))
+        };
+
+        for i in 0..20 {
+            let forwarded = if i % 2 == 0 {
+                format!("198.51.100.{i}, 203.0.113.9")
+            } else {
+                format!("198.51.100.{i}, 203.0.113.9, 127.0.0.2")
+            };
+            assert_eq!(login(i, forwarded).await.status_code(), 401);
+        }
+        let res = login(20, "198.51.100.20, 203.0.113.9".to_string()).await;
+        assert_eq!(res.status_code(), 429, "21st login from the same client");
+        let res = login(21, "198.51.100.21, 203.0.113.10".to_string()).await;
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn logs_in_with_two_factor_authentication() {
+    request::<App, _, _>(|request, ctx| async move {
+        // RFC 6238 test vector
+        assert_eq!(
+            totp::code("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 59).as_deref(),
+            Some("287082")
+        );
+
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let setup = request
+            .post("/api/auth/2fa/setup")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await
+            .json::<serde_json::Value>();
+        let secret = setup["secret"].as_str().unwrap().to_string();
+        assert!(setup["otpauth_uri"]
+            .as_str()
+            .unwrap()
+            .starts_with("otpauth://totp/Shoes%20Store:test%40loco.com?secret="));
+
+        let res = request
+            .post("/api/auth/2fa/enable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "code": "abcdef" }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let code = totp::code(&secret, Local::now().timestamp()).unwrap();
+        let res = request
+            .post("/api/auth/2fa/enable")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "code": code }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let recovery_codes = res.json::<serde_json::Value>()["recovery_codes"].clone();
+        assert_eq!(recovery_codes.as_array().unwrap().len(), 10);
+
+        let login = || {
+            request.post("/api/auth/login").json(
+                &serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }),
+            )
+        };
+        let verify = |challenge: serde_json::Value, code: serde_json::Value| {
+            request
+                .post("/api/auth/2fa/verify")
+                .json(&serde_json::json!({ "challenge_token": challenge, "code": code }))
+        };
+
+        let res = login().await;
+        assert_eq!(res.status_code(), 202, "login asks for a code");
+        let challenge = res.json::<serde_json::Value>();
+        assert!(challenge.get("token").is_none());
+        assert!(challenge.get("setup").is_none());
+        let challenge = challenge["challenge_token"].clone();
+
+        let res = verify(challenge.clone(), serde_json::json!(code)).await;
+        assert_eq!(res.status_code(), 401, "codes can't be used twice");
+        let res = verify(challenge.clone(), recovery_codes[0].clone()).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.json::<serde_json::Value>()["token"].is_string());
+        let res = verify(challenge, recovery_codes[1].clone()).await;
+        assert_eq!(res.status_code(), 401, "challenges can't be used twice");
+
+        let challenge = login().await.json::<serde_json::Value>()["challenge_token"].clone();
+        let res = verify(challenge, recovery_codes[0].clone()).await;
+        assert_eq!(res.status_code(), 401, "recovery codes can't be used twice");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn delays_wrong_two_factor_codes_across_challenges() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let setup = request
+            .post("/api/auth/2fa/setup")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await
+            .json::<serde_json::Value>();
+        let code = totp::code(setup["secret"].as_str().unwrap(), Local::now().timestamp());
+        let res = request
+            .post("/api/auth/2fa/enable")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "code": code }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        // A fresh challenge for every guess doesn't reset the count
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            let res = request
+                .post("/api/auth/login")
+                .json(
+                    &serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }),
+                )
+                .await;
+            assert_eq!(res.status_code(), 202);
+            let challenge = res.json::<serde_json::Value>()["challenge_token"].clone();
+            let res = request
+                .post("/api/auth/2fa/verify")
+                .json(&serde_json::json!({ "challenge_token": challenge, "code": "abcdef" }))
+                .await;
+            statuses.push(res.status_code());
+        }
+        assert_eq!(statuses, [401, 401, 401, 429]);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn staff_must_set_up_two_factor_authentication() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_staff_login(&request, &ctx).await;
+        let login = || {
+            request.post("/api/auth/login").json(
+                &serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }),
+            )
+        };
+        let sent = || ctx.mailer.as_ref().unwrap().deliveries().count;
+
+        // The password alone doesn't hand out an authenticator secret
+        let res = login().await;
+        assert_eq!(res.status_code(), 403);
+        assert_eq!(res.json::<serde_json::Value>()["error"], "forbidden");
+        let emails = sent();
+        let enroll = format!(
+            "/api/auth/2fa/enroll/{}",
+            prepare_data::emailed_token(&ctx, "/api/auth/2fa/enroll/")
+        );
+        let res = login().await;
+        assert_eq!(res.status_code(), 403);
+        assert_eq!(
+            sent(),
+            emails,
+            "the link is only sent again once it expired"
+        );
+
+        let res = request.get("/api/auth/2fa/enroll/not-a-token").await;
+        assert_eq!(res.status_code(), 401);
+        let res = request.get(&enroll).await;
+        assert_eq!(res.status_code(), 202);
+        let challenge = res.json::<serde_json::Value>();
+        let secret = challenge["setup"]["secret"].as_str().unwrap().to_string();
+        let res = request.get(&enroll).await;
+        assert_eq!(res.status_code(), 202);
+        let challenge = res.json::<serde_json::Value>();
+        assert_eq!(
+            challenge["setup"]["secret"], secret,
+            "the pending secret is kept"
+        );
+
+        let res = request
+            .post("/api/auth/2fa/verify")
+            .json(&serde_json::json!({
+                "challenge_token": challenge["challenge_token"],
+                "code": totp::code(&secret, Local::now().timestamp()),
+            }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(body["recovery_codes"].as_array().unwrap().len(), 10);
+        assert_eq!(sent(), emails + 1, "the user is told it was turned on");
+        let res = request.get(&enroll).await;
+        assert_eq!(res.status_code(), 401, "links can't be used once it is on");
+
+        let (auth_key, auth_value) = prepare_data::auth_header(body["token"].as_str().unwrap());
+        let res = request
+            .post("/api/auth/2fa/disable")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "password": "shoes-store-42", "code": body["recovery_codes"][0] }))
+            .await;
+        assert_eq!(res.status_code(), 403, "staff can't turn it off");
+    })
+    .await;
+}
+
+const OIDC_ACCOUNT: Account = Account {
+    subject: "mock-1",
+    email: "oidc@example.com",
+    email_verified: true,
+    name: "Oidc User",
+};
+
+/// Starts an authorization at `path`, as the user with `token` if given, and
+/// returns the URL to send the user to.
+async fn authorization_url(request: &TestServer, path: &str, token: Option<&str>) -> String {
+    let mut req = request.post(path);
+    if let Some(token) = token {
+        let (auth_key, auth_value) = prepare_data::auth_header(token);
+        req = req.add_header(auth_key, auth_value);
+    }
+    let res = req.await;
+    assert_eq!(res.status_code(), 200);
+    res.json::<serde_json::Value>()["authorization_url"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+#[serial]
+async fn signs_in_with_identity_provider() {
+    request::<App, _, _>(|request, ctx| async move {
+        let provider = MockProvider::start().await;
+        let authorize = "/api/auth/oauth/mock/authorize";
+        let callback = "/api/auth/oauth/mock/callback";
+
+        let res = request.post("/api/auth/oauth/unknown/authorize").await;
+        assert_eq!(res.status_code(), 404);
+
+        let url = authorization_url(&request, authorize, None).await;
+        assert!(url.starts_with(&format!("{ISSUER}/authorize?")));
+        let (code, state) = provider.authorize(&url, OIDC_ACCOUNT);
+        let params = serde_json::json!({ "code": code, "state": state });
+
+        let res = request.post(callback).json(&params).await;
+        assert_eq!(res.status_code(), 200, "signs up a new user");
+        let body = res.json::<serde_json::Value>();
+        assert!(body["token"].is_string());
+        assert_eq!(body["name"], "Oidc User");
+        assert_eq!(body["is_verified"], true);
+
+        let res = request.post(callback).json(&params).await;
+        assert_eq!(res.status_code(), 401, "states can't be used twice");
+
+        // The provider account is matched on its subject, not its email
+        let url = authorization_url(&request, authorize, None).await;
+        let (code, state) = provider.authorize(
+            &url,
+            Account {
+                email: "renamed@example.com",
+                ..OIDC_ACCOUNT
+            },
+        );
+        let res = request
+            .post(callback)
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["pid"], body["pid"]);
+
+        // A code only works with the verifier of the state it was issued for
+        let first = authorization_url(&request, authorize, None).await;
+        let second = authorization_url(&request, authorize, None).await;
+        let (code, _) = provider.authorize(&first, OIDC_ACCOUNT);
+        let (_, state) = provider.authorize(&second, OIDC_ACCOUNT);
+        let res = request
+            .post(callback)
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+
+        let url = authorization_url(&request, authorize, None).await;
+        let (code, state) = provider.authorize(
+            &url,
+            Account {
+                subject: "mock-2",
+                email: "unverified@example.com",
+                email_verified: false,
+                ..OIDC_ACCOUNT
+            },
+        );
+        let res = request
+            .post(callback)
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 401, "email not verified by the provider");
+        assert!(
+            users::Model::find_by_email(&ctx.db, "unverified@example.com")
+                .await
+                .is_err()
+        );
+
+        // New accounts follow the registration policy like a sign-up would
+        let url = authorization_url(&request, authorize, None).await;
+        let (code, state) = provider.authorize(
+            &url,
+            Account {
+                subject: "mock-3",
+                email: "someone@mailinator.com",
+                ..OIDC_ACCOUNT
+            },
+        );
+        let res = request
+            .post(callback)
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        assert_eq!(
+            res.json::<serde_json::Value>()["errors"]["email"][0]["code"],
+            "disposable_domain"
+        );
+        assert!(
+            users::Model::find_by_email(&ctx.db, "someone@mailinator.com")
+                .await
+                .is_err()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn links_identity_provider_to_account_with_same_email() {
+    request::<App, _, _>(|request, ctx| async move {
+        let provider = MockProvider::start().await;
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let account = Account {
+            email: "test@loco.com",
+            ..OIDC_ACCOUNT
+        };
+
+        // Someone else could have registered the email without owning it
+        let mut unverified = user.user.clone().into_active_model();
+        unverified.email_verified_at = ActiveValue::set(None);
+        unverified.update(&ctx.db).await.unwrap();
+
+        let url = authorization_url(&request, "/api/auth/oauth/mock/authorize", None).await;
+        let (code, state) = provider.authorize(&url, account.clone());
+        let res = request
+            .post("/api/auth/oauth/mock/callback")
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 409);
+
+        let mut verified = user.user.clone().into_active_model();
+        verified.email_verified_at = ActiveValue::set(Some(Local::now().into()));
+        verified.update(&ctx.db).await.unwrap();
+
+        let url = authorization_url(&request, "/api/auth/oauth/mock/authorize", None).await;
+        let (code, state) = provider.authorize(&url, account);
+        let res = request
+            .post("/api/auth/oauth/mock/callback")
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            res.json::<serde_json::Value>()["pid"],
+            user.user.pid.to_string()
+        );
+
+        let identities = user_identities::Model::list_for_user(&ctx.db, user.user.id)
+            .await
+            .unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].subject, "mock-1");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn links_and_unlinks_identities() {
+    request::<App, _, _>(|request, ctx| async move {
+        let provider = MockProvider::start().await;
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let link = "/api/auth/oauth/mock/link";
+
+        let url = authorization_url(&request, link, Some(&user.token)).await;
+        let (code, state) = provider.authorize(&url, OIDC_ACCOUNT);
+        let params = serde_json::json!({ "code": code, "state": state });
+
+        let res = request
+            .post("/api/auth/oauth/mock/link/callback")
+            .json(&params)
+            .await;
+        assert_eq!(res.status_code(), 401, "linking needs the user's token");
+        let res = request
+            .post("/api/auth/oauth/mock/link/callback")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&params)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let identity = res.json::<serde_json::Value>();
+        assert_eq!(identity["provider"], "mock");
+        assert_eq!(identity["email"], "oidc@example.com");
+
+        let res = request
+            .get("/api/auth/identities")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>().as_array().unwrap().len(), 1);
+
+        // The linked account now signs in to the user
+        let url = authorization_url(&request, "/api/auth/oauth/mock/authorize", None).await;
+        let (code, state) = provider.authorize(&url, OIDC_ACCOUNT);
+        let res = request
+            .post("/api/auth/oauth/mock/callback")
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            res.json::<serde_json::Value>()["pid"],
+            user.user.pid.to_string()
+        );
+
+        // A linking state can't be used to sign in
+        let url = authorization_url(&request, link, Some(&user.token)).await;
+        let (code, state) = provider.authorize(&url, OIDC_ACCOUNT);
+        let res = request
+            .post("/api/auth/oauth/mock/callback")
+            .json(&serde_json::json!({ "code": code, "state": state }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+
+        let path = format!("/api/auth/identities/{}", identity["id"]);
+        let res = request
+            .delete(&path)
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .delete(&path)
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 404);
+        let res = request
+            .get("/api/auth/identities")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert!(res
+            .json::<serde_json::Value>()
+            .as_array()
+            .unwrap()
+            .is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn exports_account_data() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/orders")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "payment_method": "Cod",
+                "shipping_address": "1 Main Street",
+                "items": [{ "product_variant_id": 1, "quantity": 1 }],
+            }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/auth/current/export")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            res.header("content-disposition"),
+            "attachment; filename=\"account-export.json\""
+        );
+        let export = res.json::<serde_json::Value>();
+        assert_eq!(export["profile"]["email"], "test@loco.com");
+        assert_eq!(export["profile"]["pid"], user.user.pid.to_string());
+        let orders = export["orders"].as_array().unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["shipping_address"], "1 Main Street");
+        assert_eq!(orders[0]["items"].as_array().unwrap().len(), 1);
+        assert!(export["reviews"].as_array().unwrap().is_empty());
+        assert!(export["identities"].as_array().unwrap().is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deletes_account_and_keeps_anonymized_orders() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/orders")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "payment_method": "Cod",
+                "shipping_address": "1 Main Street",
+                "items": [{ "product_variant_id": 1, "quantity": 1 }],
+            }))
+            .await;
+        let order_id = res.json::<serde_json::Value>()["id"].as_i64().unwrap();
+        let refresh_token = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await
+            .json::<serde_json::Value>()["refresh_token"]
+            .clone();
+
+        let res = request
+            .delete("/api/auth/current")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "password": "not-the-password-1" }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        assert!(users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .is_ok());
+
+        let res = request
+            .delete("/api/auth/current")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "password": "shoes-store-42" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        assert!(users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .is_err());
+        let order = orders::Entity::find_by_id(i32::try_from(order_id).unwrap())
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(order.user_id, None);
+        assert_eq!(order.shipping_address, None);
+
+        let res = request
+            .get("/api/auth/current")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let res = request
+            .post("/api/auth/refresh")
+            .json(&serde_json::json!({ "refresh_token": refresh_token }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await;
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deletes_account_with_emailed_link_and_delays_wrong_passwords() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let delete = |body: serde_json::Value| {
+            request
+                .delete("/api/auth/current")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&body)
+        };
+
+        assert_eq!(delete(serde_json::json!({})).await.status_code(), 400);
+
+        // Wrong passwords count against the account on every endpoint
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            let res = request
+                .post("/api/auth/change-password")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({ "old_password": "wrong", "new_password": "new-password-5678" }))
+                .await;
+            statuses.push(res.status_code());
+        }
+        statuses.push(
+            delete(serde_json::json!({ "password": "shoes-store-42" }))
+                .await
+                .status_code(),
+        );
+        assert_eq!(statuses, [400, 400, 400, 429]);
+
+        let res = request
+            .post("/api/auth/current/deletion-link")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let token = prepare_data::emailed_token(&ctx, "/delete-account#");
+
+        let res = delete(serde_json::json!({ "token": "not-the-token" })).await;
+        assert_eq!(res.status_code(), 400);
+        let res = delete(serde_json::json!({ "token": token })).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(users::Model::find_by_email(&ctx.db, "test@loco.com")
+            .await
+            .is_err());
+    })
+    .await;
+}
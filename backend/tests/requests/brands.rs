@@ -2,6 +2,8 @@ use loco_rs::testing::prelude::*;
 use serial_test::serial;
 use shoes_store_api::app::App;
 
+use super::prepare_data;
+
 #[tokio::test]
 #[serial]
 async fn can_get_brands() {
@@ -14,3 +16,46 @@ async fn can_get_brands() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn can_list_brand_products_by_slug() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let res = request.get("/api/brands/nike").await;
+        assert_eq!(res.status_code(), 200);
+        let brand: serde_json::Value = res.json();
+        assert_eq!(brand["id"], 1);
+
+        let res = request.get("/api/brands/nike/products?page_size=1").await;
+        assert_eq!(res.status_code(), 200);
+        let page: serde_json::Value = res.json();
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+        assert_eq!(page["items"][0]["brand"]["slug"], "nike");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn cannot_delete_brand_with_products_unless_forced() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .delete("/api/brands/nike")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 409);
+
+        let res = request
+            .delete("/api/brands/nike?force=true")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+    })
+    .await;
+}
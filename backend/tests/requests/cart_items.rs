@@ -2,6 +2,8 @@ use loco_rs::testing::prelude::*;
 use serial_test::serial;
 use shoes_store_api::app::App;
 
+use super::prepare_data;
+
 #[tokio::test]
 #[serial]
 async fn can_get_cart_items() {
@@ -14,3 +16,265 @@ async fn can_get_cart_items() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn merges_guest_cart_on_login() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/cart")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "product_variant_id": 1, "quantity": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request.post("/api/cart/guest").await;
+        assert_eq!(res.status_code(), 200);
+        let guest: serde_json::Value = res.json();
+        let token = guest["token"].as_str().unwrap().to_string();
+
+        for (variant, quantity) in [(1, 2), (2, 1)] {
+            let res = request
+                .post("/api/cart")
+                .add_header("x-cart-token", token.clone())
+                .json(&serde_json::json!({
+                    "product_variant_id": variant,
+                    "quantity": quantity,
+                }))
+                .await;
+            assert_eq!(res.status_code(), 200);
+        }
+
+        let res = request
+            .post("/api/auth/login")
+            .add_header("x-cart-token", token.clone())
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/cart")
+            .add_header(auth_key, auth_value)
+            .await;
+        let cart: serde_json::Value = res.json();
+        let items = cart["items"].as_array().unwrap();
+        let quantity_of = |variant: i64| {
+            items
+                .iter()
+                .find(|item| item["product_variant_id"] == variant)
+                .map(|item| item["quantity"].clone())
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(quantity_of(1), Some(serde_json::json!(3)));
+        assert_eq!(quantity_of(2), Some(serde_json::json!(1)));
+
+        // The guest cart is gone once merged
+        let res = request
+            .get("/api/cart")
+            .add_header("x-cart-token", token)
+            .await;
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn merging_guest_cart_caps_quantity_at_stock() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        // Variant 2 has 5 in stock
+        let res = request
+            .post("/api/cart")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "product_variant_id": 2, "quantity": 4 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request.post("/api/cart/guest").await;
+        let token = res.json::<serde_json::Value>()["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let res = request
+            .post("/api/cart")
+            .add_header("x-cart-token", token.clone())
+            .json(&serde_json::json!({ "product_variant_id": 2, "quantity": 3 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .post("/api/auth/login")
+            .add_header("x-cart-token", token)
+            .json(&serde_json::json!({ "email": "test@loco.com", "password": "shoes-store-42" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            res.json::<serde_json::Value>()["cart_reductions"],
+            serde_json::json!([{ "product_variant_id": 2, "requested": 7, "available": 5 }])
+        );
+
+        let res = request
+            .get("/api/cart/2")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.json::<serde_json::Value>()["quantity"], 5);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn add_increments_and_validates_quantity() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        for quantity in [2, 3] {
+            let res = request
+                .post("/api/cart")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({ "product_variant_id": 2, "quantity": quantity }))
+                .await;
+            assert_eq!(res.status_code(), 200);
+        }
+        let res = request
+            .get("/api/cart/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let item: serde_json::Value = res.json();
+        assert_eq!(item["quantity"], 5);
+
+        let res = request
+            .patch("/api/cart/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "quantity": 0 }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+
+        // Variant 2 only has 5 in stock
+        let res = request
+            .post("/api/cart")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "product_variant_id": 2, "quantity": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 409);
+        let res = request
+            .get("/api/cart/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.json::<serde_json::Value>()["quantity"], 5);
+
+        // Concurrent adds of the same variant both count
+        let add = || {
+            request
+                .post("/api/cart")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({ "product_variant_id": 1, "quantity": 1 }))
+        };
+        let (first, second) = tokio::join!(add(), add());
+        assert_eq!(first.status_code(), 200);
+        assert_eq!(second.status_code(), 200);
+        let res = request
+            .get("/api/cart/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.json::<serde_json::Value>()["quantity"], 2);
+
+        let res = request
+            .delete("/api/cart")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/cart")
+            .add_header(auth_key, auth_value)
+            .await;
+        let cart: serde_json::Value = res.json();
+        assert_eq!(cart["items"], serde_json::json!([]));
+        assert_eq!(cart["subtotal"], 0.0);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn reports_price_and_stock_changes() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/cart")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "product_variant_id": 2, "quantity": 4 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let added: serde_json::Value = res.json();
+
+        let res = request
+            .patch("/api/products/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "discount_percentage": 25 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .put("/api/products/1/variants/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "stock": 3 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/cart")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let cart: serde_json::Value = res.json();
+        let line = &cart["items"][0];
+        assert_eq!(line["quantity"], 3);
+        // Viewing the cart doesn't count as changing it
+        assert_eq!(line["updated_at"], added["updated_at"]);
+        assert_eq!(line["current_price"], 90.0);
+        assert_eq!(
+            line["notices"],
+            serde_json::json!([
+                { "kind": "quantity_reduced", "requested": 4, "available": 3 },
+                { "kind": "price_changed", "old_price": 120.0, "new_price": 90.0 },
+            ])
+        );
+        assert_eq!(cart["item_count"], 3);
+        assert_eq!(cart["subtotal"], 270.0);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rate_limits_guest_carts() {
+    request::<App, _, _>(|request, _ctx| async move {
+        let start = |ip: &'static str| {
+            request
+                .post("/api/cart/guest")
+                .add_header("x-forwarded-for", ip)
+        };
+
+        for _ in 0..20 {
+            assert_eq!(start("203.0.113.10").await.status_code(), 200);
+        }
+        let res = start("203.0.113.10").await;
+        assert_eq!(res.status_code(), 429, "21st cart from the same IP");
+        assert_eq!(start("203.0.113.11").await.status_code(), 200);
+    })
+    .await;
+}
@@ -1,6 +1,12 @@
 use loco_rs::testing::prelude::*;
+use sea_orm::EntityTrait;
 use serial_test::serial;
-use shoes_store_api::app::App;
+use shoes_store_api::{
+    app::App,
+    models::{categories, products},
+};
+
+use super::prepare_data;
 
 #[tokio::test]
 #[serial]
@@ -14,3 +20,135 @@ async fn can_get_categories() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn can_get_category_tree() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let res = request.get("/api/categories/tree").await;
+        assert_eq!(res.status_code(), 200);
+
+        let tree: Vec<serde_json::Value> = res.json();
+        let men = tree.iter().find(|c| c["slug"] == "men").unwrap();
+        assert!(men["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|c| c["slug"] == "men-running"));
+        assert!(men["product_count"].as_i64().unwrap() >= 2);
+
+        let res = request.get("/api/categories/men-running/ancestors").await;
+        let chain: Vec<serde_json::Value> = res.json();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0]["slug"], "men");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn cannot_move_category_under_descendant() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .patch("/api/categories/men")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "parent_id": 4 }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+
+        let res = request
+            .delete("/api/categories/men")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 409);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_delete_category_and_reparent_children() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/categories")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "name": "Trail",
+                "slug": "men-running-trail",
+                "parent_id": 4,
+            }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let trail = res.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+        let res = request
+            .delete("/api/categories/men-running?children=reparent")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        // The subcategory moves up to its grandparent
+        let trail = categories::Entity::find_by_id(i32::try_from(trail).unwrap())
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(trail.parent_id, Some(1));
+        assert!(categories::Entity::find_by_id(4)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .is_none());
+        let product = products::Entity::find_by_id(1)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(product.category_id, None);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_delete_category_with_descendants() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .delete("/api/categories/women?children=cascade")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        for id in [2, 7, 8, 9] {
+            assert!(categories::Entity::find_by_id(id)
+                .one(&ctx.db)
+                .await
+                .unwrap()
+                .is_none());
+        }
+        // Products of the deleted categories are kept without a category
+        for id in [3, 4] {
+            let product = products::Entity::find_by_id(id)
+                .one(&ctx.db)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(product.category_id, None);
+        }
+    })
+    .await;
+}
@@ -1,11 +1,16 @@
+mod audit_events;
 mod auth;
+mod mock_oidc;
 mod prepare_data;
 pub mod products;
 
 pub mod brands;
 pub mod cart_items;
 pub mod categories;
+pub mod moderation;
 pub mod orders;
 pub mod product_variants;
+pub mod reports;
 pub mod reviews;
+pub mod roles;
 pub mod wishlists;
@@ -0,0 +1,99 @@
+use loco_rs::testing::prelude::*;
+use sea_orm::EntityTrait;
+use serial_test::serial;
+use shoes_store_api::{
+    app::App,
+    models::{_entities::sea_orm_active_enums::ReviewStatus, reviews},
+};
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn customers_cannot_see_moderation_queue() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .get("/api/admin/reviews")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_list_and_reject_reported_reviews() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .get("/api/admin/reviews")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["total_items"], 1);
+        assert_eq!(body["items"][0]["id"], 2);
+        assert_eq!(body["items"][0]["report_count"], 1);
+
+        let res = request
+            .post("/api/admin/reviews/moderate")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "review_ids": [2], "action": "reject" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let review = reviews::Entity::find_by_id(2)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(review.status, ReviewStatus::Rejected);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn author_edits_keep_rejected_reviews_hidden() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/products/1/reviews")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "rating": 1, "content": "Fell apart in a week." }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let review: serde_json::Value = res.json();
+
+        let res = request
+            .post("/api/admin/reviews/moderate")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "review_ids": [review["id"]], "action": "reject" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .patch(&format!("/api/products/1/reviews/{}", user.user.id))
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "content": "Fell apart in two weeks." }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["status"], "Rejected");
+
+        let res = request.get("/api/products/1/reviews?rating=1").await;
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["reviews"]["total_items"], 0);
+    })
+    .await;
+}
@@ -1,6 +1,9 @@
 use loco_rs::testing::prelude::*;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
 use serial_test::serial;
-use shoes_store_api::app::App;
+use shoes_store_api::{app::App, models::idempotency_keys};
+
+use super::prepare_data;
 
 #[tokio::test]
 #[serial]
@@ -14,3 +17,277 @@ async fn can_get_orders() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn replays_order_creation_with_idempotency_key() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let order = serde_json::json!({
+            "payment_method": "Cod",
+            "shipping_address": "1 Main Street",
+            "items": [{ "product_variant_id": 1, "quantity": 1 }],
+        });
+        let mut ids = Vec::new();
+        for replayed in [false, true] {
+            let res = request
+                .post("/api/orders")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .add_header("idempotency-key", "order-1")
+                .json(&order)
+                .await;
+            assert_eq!(res.status_code(), 200);
+            assert_eq!(res.maybe_header("idempotent-replayed").is_some(), replayed);
+            ids.push(res.json::<serde_json::Value>()["id"].clone());
+        }
+        assert_eq!(ids[0], ids[1]);
+
+        let res = request
+            .post("/api/orders")
+            .add_header(auth_key, auth_value)
+            .add_header("idempotency-key", "order-1")
+            .json(&serde_json::json!({
+                "payment_method": "Cod",
+                "shipping_address": "1 Main Street",
+                "items": [{ "product_variant_id": 1, "quantity": 2 }],
+            }))
+            .await;
+        assert_eq!(res.status_code(), 422);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn retries_take_over_abandoned_idempotency_keys() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let order = serde_json::json!({
+            "payment_method": "Cod",
+            "shipping_address": "1 Main Street",
+            "items": [{ "product_variant_id": 1, "quantity": 1 }],
+        });
+
+        // A claim of a request that is still running
+        let res = request
+            .post("/api/orders")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .add_header("idempotency-key", "order-1")
+            .json(&order)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let hold = |locked_until: chrono::DateTime<chrono::Utc>| {
+            idempotency_keys::Entity::update_many()
+                .col_expr(
+                    idempotency_keys::Column::ResponseStatus,
+                    Expr::value(Option::<i16>::None),
+                )
+                .col_expr(
+                    idempotency_keys::Column::LockedUntil,
+                    Expr::value(locked_until),
+                )
+                .filter(idempotency_keys::Column::UserId.eq(user.user.id))
+                .filter(idempotency_keys::Column::Key.eq("order-1"))
+                .exec(&ctx.db)
+        };
+        hold(chrono::Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        let res = request
+            .post("/api/orders")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .add_header("idempotency-key", "order-1")
+            .json(&order)
+            .await;
+        assert_eq!(res.status_code(), 409);
+
+        // Once its hold lapsed, the request is taken to be gone
+        hold(chrono::Utc::now() - chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        for replayed in [false, true] {
+            let res = request
+                .post("/api/orders")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .add_header("idempotency-key", "order-1")
+                .json(&order)
+                .await;
+            assert_eq!(res.status_code(), 200);
+            assert_eq!(res.maybe_header("idempotent-replayed").is_some(), replayed);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn replays_cart_adds_with_idempotency_key() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        for replayed in [false, true] {
+            let res = request
+                .post("/api/cart")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .add_header("idempotency-key", "cart-1")
+                .json(&serde_json::json!({ "product_variant_id": 1, "quantity": 1 }))
+                .await;
+            assert_eq!(res.status_code(), 200);
+            assert_eq!(res.maybe_header("idempotent-replayed").is_some(), replayed);
+            assert_eq!(res.json::<serde_json::Value>()["quantity"], 1);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ignores_idempotency_keys_outside_listed_routes() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        // Responses of the auth routes hold secrets, they are never stored
+        let res = request
+            .post("/api/auth/2fa/setup")
+            .add_header(auth_key, auth_value)
+            .add_header("idempotency-key", "setup-1")
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            idempotency_keys::Entity::find()
+                .filter(idempotency_keys::Column::UserId.eq(user.user.id))
+                .count(&ctx.db)
+                .await
+                .unwrap(),
+            0
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_filter_and_paginate_orders() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .get("/api/orders")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["total_items"], 0);
+
+        let res = request
+            .get("/api/orders?email=user1")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .get("/api/orders?page=1&page_size=1&sort=amount_low")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let page = res.json::<serde_json::Value>();
+        assert_eq!(page["total_items"], 2);
+        assert_eq!(page["total_pages"], 2);
+        assert_eq!(page["items"][0]["id"], 1);
+
+        for (filter, id) in [
+            ("status=Pending", 2),
+            ("payment_method=Stripe", 1),
+            ("min_amount=130", 2),
+            ("email=USER1@", 1),
+            ("sku=CHUCK", 2),
+        ] {
+            let res = request
+                .get(&format!("/api/orders?{filter}"))
+                .add_header(auth_key.clone(), auth_value.clone())
+                .await;
+            assert_eq!(res.status_code(), 200, "{filter}");
+            let page = res.json::<serde_json::Value>();
+            assert_eq!(page["total_items"], 1, "{filter}");
+            assert_eq!(page["items"][0]["id"], id, "{filter}");
+            assert!(page["items"][0]["items"]
+                .as_array()
+                .is_some_and(|items| !items.is_empty()));
+        }
+
+        // Wildcards in a filter are matched literally
+        for filter in ["email=%25", "email=user_", "sku=%25"] {
+            let res = request
+                .get(&format!("/api/orders?{filter}"))
+                .add_header(auth_key.clone(), auth_value.clone())
+                .await;
+            assert_eq!(res.status_code(), 200, "{filter}");
+            assert_eq!(
+                res.json::<serde_json::Value>()["total_items"],
+                0,
+                "{filter}"
+            );
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn invoices_paid_orders() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .get("/api/orders/2/invoice")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 409);
+
+        // Paying again later does not use up another number
+        for status in ["Paid", "Shipped", "Paid"] {
+            let res = request
+                .patch("/api/orders/2")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({ "status": status }))
+                .await;
+            assert_eq!(res.status_code(), 200);
+            assert_eq!(res.json::<serde_json::Value>()["invoice_number"], 1);
+        }
+
+        let res = request
+            .get("/api/orders/2/invoice")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let invoice = res.text();
+        assert!(invoice.contains("INV-000001"));
+        assert!(invoice.contains("CV-CHUCK-38-WHT"));
+        assert!(invoice.contains("150.00"));
+
+        let res = request
+            .get("/api/orders/2/packing-slip?format=pdf")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.header("content-type"), "application/pdf");
+        assert!(res.as_bytes().starts_with(b"%PDF-"));
+    })
+    .await;
+}
@@ -1,9 +1,13 @@
 use axum::http::{HeaderName, HeaderValue};
 use loco_rs::{app::AppContext, TestServer};
-use shoes_store_api::{models::users, views::auth::LoginResponse};
+use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+use shoes_store_api::{
+    models::{_entities::sea_orm_active_enums::UserRole, users},
+    views::auth::LoginResponse,
+};
 
 const USER_EMAIL: &str = "test@loco.com";
-const USER_PASSWORD: &str = "1234";
+const USER_PASSWORD: &str = "shoes-store-42";
 
 pub struct LoggedInUser {
     pub user: users::Model,
@@ -22,15 +26,8 @@ pub async fn init_user_login(request: &TestServer, ctx: &AppContext) -> LoggedIn
         .post("/api/auth/register")
         .json(&register_payload)
         .await;
-    let user = users::Model::find_by_email(&ctx.db, USER_EMAIL)
-        .await
-        .unwrap();
-
-    let verify_payload = serde_json::json!({
-        "token": user.email_verification_token,
-    });
-
-    request.post("/api/auth/verify").json(&verify_payload).await;
+    let token = emailed_token(ctx, "/api/auth/verify/");
+    request.get(&format!("/api/auth/verify/{token}")).await;
 
     let response = request
         .post("/api/auth/login")
@@ -50,6 +47,49 @@ pub async fn init_user_login(request: &TestServer, ctx: &AppContext) -> LoggedIn
     }
 }
 
+pub async fn init_staff_login(request: &TestServer, ctx: &AppContext) -> LoggedInUser {
+    init_login_with_role(request, ctx, UserRole::Admin).await
+}
+
+pub async fn init_login_with_role(
+    request: &TestServer,
+    ctx: &AppContext,
+    role: UserRole,
+) -> LoggedInUser {
+    let logged_in = init_user_login(request, ctx).await;
+
+    let mut user = logged_in.user.into_active_model();
+    user.role = ActiveValue::set(Some(role));
+    let user = user.update(&ctx.db).await.unwrap();
+
+    LoggedInUser {
+        user,
+        token: logged_in.token,
+    }
+}
+
+/// The token from the latest email containing a link that starts with `link`.
+/// Only hashes of tokens are stored, so the emails are the only place to get
+/// them from.
+pub fn emailed_token(ctx: &AppContext, link: &str) -> String {
+    let deliveries = ctx.mailer.as_ref().unwrap().deliveries();
+    deliveries
+        .messages
+        .iter()
+        .rev()
+        .find_map(|message| {
+            // long lines are quoted-printable encoded, with soft line breaks
+            let message = message.replace("=\r\n", "");
+            let (_, rest) = message.split_once(link)?;
+            Some(
+                rest.chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                    .collect(),
+            )
+        })
+        .unwrap_or_else(|| panic!("no email with a {link} link was sent"))
+}
+
 pub fn auth_header(token: &str) -> (HeaderName, HeaderValue) {
     let auth_header_value = HeaderValue::from_str(&format!("Bearer {}", &token)).unwrap();
 
@@ -1,6 +1,9 @@
 use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
 use serial_test::serial;
-use shoes_store_api::app::App;
+use shoes_store_api::{app::App, models::wishlists};
+
+use super::prepare_data;
 
 #[tokio::test]
 #[serial]
@@ -14,3 +17,91 @@ async fn can_get_product_variants() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn reports_invalid_variants_field_by_field() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .post("/api/products/1/variants")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "sku": "NK-PEG-43-WHT", "stock": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 409);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(body["error"], "already_exists");
+        assert_eq!(body["errors"]["sku"][0]["code"], "already_exists");
+
+        let res = request
+            .post("/api/products/999/variants")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "sku": "NEW-SKU", "stock": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 422);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(body["error"], "invalid_reference");
+        assert_eq!(body["errors"]["product_id"][0]["code"], "invalid_reference");
+
+        let res = request
+            .put("/api/products/1/variants/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "stock": 3_000_000_000_u32 }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(body["error"], "validation_failed");
+        assert_eq!(body["errors"]["stock"][0]["code"], "range");
+
+        // Rejections from axum get the same envelope
+        let res = request
+            .put("/api/products/1/variants/first")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "stock": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let body = res.json::<serde_json::Value>();
+        assert_eq!(body["error"], "bad_request");
+        assert!(body["description"].is_string());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deleting_variant_keeps_one_wishlist_entry_of_its_product() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        // List 3 holds product 1 pinned to variant 1, save it unpinned too
+        wishlists::ActiveModel {
+            user_id: Set(2),
+            wishlist_list_id: Set(3),
+            product_id: Set(1),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        let res = request
+            .delete("/api/products/1/variants/1")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let items = wishlists::Entity::find()
+            .filter(wishlists::Column::WishlistListId.eq(3))
+            .all(&ctx.db)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].product_variant_id, None);
+    })
+    .await;
+}
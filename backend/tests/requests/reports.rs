@@ -0,0 +1,82 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use shoes_store_api::app::App;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn customers_cannot_see_reports() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .get("/api/admin/reports/summary")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_report_sales() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .get("/api/admin/reports/sales?interval=month")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let sales = res.json::<serde_json::Value>();
+        assert_eq!(sales[0]["period"], "2023-11-01");
+        assert_eq!(sales[0]["order_count"], 2);
+
+        let res = request
+            .get("/api/admin/reports/sales?from=2024-01-01T00:00:00Z")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.json::<serde_json::Value>(), serde_json::json!([]));
+
+        let res = request
+            .get("/api/admin/reports/products?by=units&limit=2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let products = res.json::<serde_json::Value>();
+        assert_eq!(products[0]["product_id"], 4);
+        assert_eq!(products[0]["units"], 2);
+        assert_eq!(products[1]["product_id"], 1);
+
+        let res = request
+            .get("/api/admin/reports/summary")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let summary = res.json::<serde_json::Value>();
+        assert_eq!(summary["order_count"], 2);
+        assert_eq!(summary["cancelled_count"], 0);
+        assert_eq!(summary["cancellation_rate"], 0.0);
+
+        let res = request
+            .get("/api/admin/reports/sell-through?format=csv")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.header("content-type"), "text/csv; charset=utf-8");
+        let csv = res.text();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("product_variant_id,sku,product_name,units_sold,stock,sell_through")
+        );
+        assert!(lines
+            .next()
+            .is_some_and(|line| line.starts_with("5,CV-CHUCK-38-WHT,")));
+    })
+    .await;
+}
@@ -2,6 +2,8 @@ use loco_rs::testing::prelude::*;
 use serial_test::serial;
 use shoes_store_api::app::App;
 
+use super::prepare_data;
+
 #[tokio::test]
 #[serial]
 async fn can_get_reviews() {
@@ -14,3 +16,74 @@ async fn can_get_reviews() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn can_vote_review_helpful() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .put("/api/products/1/reviews/1/vote")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "helpful": true }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let review: serde_json::Value = res.json();
+        assert_eq!(review["helpful_count"], 1);
+        assert_eq!(review["unhelpful_count"], 0);
+
+        // Changing the vote replaces it instead of adding a second one
+        let res = request
+            .put("/api/products/1/reviews/1/vote")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "helpful": false }))
+            .await;
+        let review: serde_json::Value = res.json();
+        assert_eq!(review["helpful_count"], 0);
+        assert_eq!(review["unhelpful_count"], 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn cannot_vote_on_own_review() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .put(&format!("/api/products/1/reviews/{}/vote", user.user.id))
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "helpful": true }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_list_reviews_by_rating() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let res = request
+            .get("/api/products/1/reviews?rating=5&sort=most_helpful")
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["reviews"]["total_items"], 1);
+
+        let res = request.get("/api/products/1/reviews?rating=1").await;
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["reviews"]["total_items"], 0);
+    })
+    .await;
+}
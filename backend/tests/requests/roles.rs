@@ -0,0 +1,129 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use shoes_store_api::{
+    app::App,
+    models::{_entities::sea_orm_active_enums::UserRole, users},
+};
+
+use super::prepare_data;
+
+const CUSTOMER_PID: &str = "22222222-2222-2222-2222-222222222222";
+
+#[tokio::test]
+#[serial]
+async fn customers_cannot_assign_roles() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .get("/api/admin/roles")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .put(&format!("/api/admin/users/{}/role", user.user.pid))
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "role": "Admin" }))
+            .await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn fulfillment_staff_can_only_change_order_status() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let staff = prepare_data::init_login_with_role(&request, &ctx, UserRole::Fulfillment).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&staff.token);
+
+        let res = request
+            .patch("/api/products/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "price": 99.5 }))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .get("/api/orders/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .patch("/api/orders/2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "amount": "1.0" }))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .patch("/api/orders/2")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "status": "Paid" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["status"], "Paid");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn admins_assign_roles() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let admin = prepare_data::init_staff_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&admin.token);
+
+        let res = request
+            .get("/api/admin/roles")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let roles = res.json::<serde_json::Value>();
+        assert_eq!(roles.as_array().unwrap().len(), 4);
+        assert_eq!(
+            roles[2],
+            serde_json::json!({
+                "role": "Fulfillment",
+                "permissions": ["orders.read", "orders.update_status"]
+            })
+        );
+
+        let res = request
+            .put(&format!("/api/admin/users/{CUSTOMER_PID}/role"))
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "role": "Support" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["role"], "Support");
+        let user = users::Model::find_by_pid(&ctx.db, CUSTOMER_PID)
+            .await
+            .unwrap();
+        assert_eq!(user.role, Some(UserRole::Support));
+
+        let res = request
+            .put(&format!("/api/admin/users/{}/role", admin.user.pid))
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "role": null }))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .get("/api/admin/audit")
+            .add_header(auth_key, auth_value)
+            .add_query_param("action", "role_change")
+            .await;
+        let page = res.json::<serde_json::Value>();
+        assert_eq!(page["total_items"], 1);
+        let event = &page["items"][0];
+        assert_eq!(event["entity_id"], CUSTOMER_PID);
+        assert_eq!(event["before"], serde_json::json!({ "role": null }));
+        assert_eq!(event["after"], serde_json::json!({ "role": "Support" }));
+    })
+    .await;
+}
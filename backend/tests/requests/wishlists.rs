@@ -2,6 +2,8 @@ use loco_rs::testing::prelude::*;
 use serial_test::serial;
 use shoes_store_api::app::App;
 
+use super::prepare_data;
+
 #[tokio::test]
 #[serial]
 async fn can_get_wishlists() {
@@ -14,3 +16,108 @@ async fn can_get_wishlists() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn emails_price_drop_on_wishlisted_product() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_staff_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/products/1/wishlist")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .patch("/api/products/1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "discount_percentage": 20 }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        // Welcome email, plus one price drop email for each user who
+        // wishlisted the product
+        let deliveries = ctx.mailer.unwrap().deliveries();
+        assert_eq!(deliveries.count, 3);
+
+        let res = request
+            .get("/api/wishlist")
+            .add_header(auth_key, auth_value)
+            .await;
+        let items: Vec<serde_json::Value> = res.json();
+        let item = items.iter().find(|item| item["id"] == 1).unwrap();
+        assert_eq!(item["saved_price"], 120.0);
+        assert_eq!(item["current_price"], 96.0);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_share_named_wishlist() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+        let res = request
+            .post("/api/wishlists")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "name": "Gift ideas" }))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let list: serde_json::Value = res.json();
+        let list_id = list["id"].as_i64().unwrap();
+
+        // Variants must belong to the product being saved
+        let res = request
+            .post(&format!("/api/wishlists/{list_id}/items"))
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "product_id": 2, "variant_id": 1 }))
+            .await;
+        assert_eq!(res.status_code(), 400);
+
+        // Saving the same item again keeps a single entry
+        for _ in 0..2 {
+            let res = request
+                .post(&format!("/api/wishlists/{list_id}/items"))
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({ "product_id": 1, "variant_id": 1 }))
+                .await;
+            assert_eq!(res.status_code(), 200);
+        }
+
+        let res = request
+            .post(&format!("/api/wishlists/{list_id}/share"))
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let list: serde_json::Value = res.json();
+        let token = list["share_token"].as_str().unwrap().to_string();
+
+        let res = request.get(&format!("/api/shared-wishlists/{token}")).await;
+        assert_eq!(res.status_code(), 200);
+        let shared: serde_json::Value = res.json();
+        assert_eq!(shared["name"], "Gift ideas");
+        assert_eq!(
+            shared["owner"],
+            serde_json::json!({ "name": user.user.name })
+        );
+        assert_eq!(shared["items"].as_array().unwrap().len(), 1);
+        assert_eq!(shared["items"][0]["id"], 1);
+        assert_eq!(shared["items"][0]["variant"]["id"], 1);
+
+        let res = request
+            .delete(&format!("/api/wishlists/{list_id}/share"))
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request.get(&format!("/api/shared-wishlists/{token}")).await;
+        assert_eq!(res.status_code(), 404);
+    })
+    .await;
+}
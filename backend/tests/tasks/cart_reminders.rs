@@ -0,0 +1,60 @@
+use chrono::{Duration, Utc};
+use loco_rs::{boot::run_task, task, testing::prelude::*};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+use serial_test::serial;
+use shoes_store_api::{
+    app::App,
+    models::{cart_items, cart_reminders, users},
+};
+
+#[tokio::test]
+#[serial]
+async fn sends_each_cart_reminder_once() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let ctx = &boot.app_context;
+
+    let user = users::Model::create_with_password(
+        &ctx.db,
+        &users::RegisterParams {
+            email: "shopper@example.com".to_string(),
+            password: "1234".to_string(),
+            name: "Shopper".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    let three_days_ago = Utc::now() - Duration::days(3);
+    cart_items::ActiveModel {
+        user_id: ActiveValue::set(Some(user.id)),
+        product_variant_id: ActiveValue::set(1),
+        quantity: ActiveValue::set(Some(1)),
+        unit_price: ActiveValue::set(Some(120.0)),
+        created_at: ActiveValue::set(three_days_ago.into()),
+        updated_at: ActiveValue::set(three_days_ago.into()),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .unwrap();
+
+    let name = "send_cart_reminders".to_string();
+    for _ in 0..2 {
+        run_task::<App>(ctx, Some(&name), &task::Vars::default())
+            .await
+            .unwrap();
+    }
+
+    // The fixture users ordered after their carts last changed
+    let deliveries = ctx.mailer.as_ref().unwrap().deliveries();
+    assert_eq!(deliveries.count, 1);
+
+    let reminders = cart_reminders::Entity::find()
+        .filter(cart_reminders::Column::UserId.eq(user.id))
+        .count(&ctx.db)
+        .await
+        .unwrap();
+    assert_eq!(reminders, 1);
+}
@@ -1 +1 @@
-
+mod cart_reminders;